@@ -0,0 +1,34 @@
+//! Encode/decode cost of a single dictionary read, the per-transaction CPU
+//! overhead of high-rate polling (excluding serial I/O).
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use cryomech_api::internals;
+use std::hint::black_box;
+
+// Reply to a read of 0x0D8F[2] (helium temp) carrying 30.0 °C
+const HELIUM_TEMP_REPLY: [u8; 8] = [0x63, 0x0D, 0x8F, 0x02, 0x00, 0x00, 0x01, 0x2C];
+
+fn bench_encode(c: &mut Criterion) {
+    c.bench_function("encode_read_v2", |b| {
+        b.iter(|| internals::encode_read_v2(black_box(0x10), black_box(0x0D8F), black_box(0x02)))
+    });
+    c.bench_function("encode_read_v3", |b| {
+        b.iter(|| {
+            internals::encode_read_v3(
+                black_box(0x10),
+                black_box(0x20),
+                black_box(0x0D8F),
+                black_box(0x02),
+            )
+        })
+    });
+}
+
+fn bench_decode(c: &mut Criterion) {
+    c.bench_function("decode_reply_data", |b| {
+        b.iter(|| internals::decode_reply_data(black_box(&HELIUM_TEMP_REPLY)))
+    });
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+//! Snapshot latency against a real compressor: `read_all()` with plain
+//! sequential reads versus pipelined reads. Needs hardware and SMDP V3+, so it
+//! is skipped unless CRYOMECH_PORT is set.
+//!
+//! `CRYOMECH_PORT=/dev/ttyUSB0 cargo bench --bench snapshot`
+
+use cryomech_api::{CryomechApiSmdpBuilder, SmdpVersion};
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 20;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(port) = std::env::var("CRYOMECH_PORT") else {
+        eprintln!("CRYOMECH_PORT not set, skipping snapshot benchmark");
+        return Ok(());
+    };
+    // The port is opened exclusively, so each handle is dropped before the next
+    for pipelining in [false, true] {
+        let mut api = CryomechApiSmdpBuilder::new(&port)
+            .version(SmdpVersion::V3Plus)
+            .pipelining(pipelining)
+            .build()?;
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            api.read_all()?;
+        }
+        let name = if pipelining {
+            "pipelined"
+        } else {
+            "sequential"
+        };
+        report(name, start.elapsed());
+    }
+    Ok(())
+}
+
+fn report(name: &str, total: Duration) {
+    println!(
+        "{:<20} {:>8.1} ms/snapshot",
+        name,
+        total.as_secs_f64() * 1e3 / ROUNDS as f64
+    );
+}
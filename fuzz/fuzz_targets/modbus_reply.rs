@@ -0,0 +1,13 @@
+//! Buffered Modbus TCP read reply. The first byte picks the register count
+//! requested, the rest is the frame.
+
+#![no_main]
+
+use cryomech_api::internals;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((&count, frame)) = data.split_first() {
+        let _ = internals::decode_modbus_reply(frame, u16::from(count));
+    }
+});
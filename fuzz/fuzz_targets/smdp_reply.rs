@@ -0,0 +1,22 @@
+//! Bytes received in reply to an SMDP dictionary read, run through the same
+//! frame parsing, reply checks and data decoding as a read from a device.
+//! The first byte picks the protocol version and the read path. Decoding
+//! must reject anything malformed without panicking.
+
+#![no_main]
+
+use cryomech_api::{Register, SmdpVersion, codec, internals};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&mode, rx)) = data.split_first() else {
+        return;
+    };
+    let version = if mode & 1 == 0 {
+        SmdpVersion::V2
+    } else {
+        SmdpVersion::V3Plus
+    };
+    let _ = internals::read_replayed(rx, version, mode & 2 != 0);
+    let _ = codec::decode_reading(Register::HeliumTemp, rx);
+});
@@ -0,0 +1,378 @@
+/* Alarm conditions derived from snapshots, with severity and acknowledgement */
+
+use crate::{
+    audit::{Audit, AuditAction, AuditEntry, AuditSink},
+    clock::{Clock, SystemClock},
+    labels::DeviceLabels,
+    poller::ReadingSink,
+    provenance::Provenance,
+    status::CompressorStatus,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Conditions reported by the compressor's status flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlarmKind {
+    /// The panel reports an active error code.
+    ErrorCode,
+    TempSensorFail,
+    PressureSensorFail,
+    ClockBatteryLow,
+    /// The panel lost its stored settings.
+    MemoryLoss,
+}
+impl AlarmKind {
+    const ALL: [AlarmKind; 5] = [
+        Self::ErrorCode,
+        Self::TempSensorFail,
+        Self::PressureSensorFail,
+        Self::ClockBatteryLow,
+        Self::MemoryLoss,
+    ];
+    fn active(self, status: &CompressorStatus) -> bool {
+        match self {
+            Self::ErrorCode => status.err_code_status,
+            Self::TempSensorFail => status.temp_sensor_fail,
+            Self::PressureSensorFail => status.pressure_sensor_fail,
+            Self::ClockBatteryLow => status.clock_batt_low,
+            Self::MemoryLoss => status.mem_loss,
+        }
+    }
+    fn default_severity(self) -> Severity {
+        match self {
+            Self::ErrorCode => Severity::Critical,
+            Self::TempSensorFail | Self::PressureSensorFail => Severity::Warning,
+            Self::ClockBatteryLow | Self::MemoryLoss => Severity::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alarm {
+    pub kind: AlarmKind,
+    pub severity: Severity,
+    /// When the condition was first seen active.
+    pub since: SystemTime,
+    /// Set by `AlarmBoard::acknowledge()`, cleared when the condition clears.
+    pub acknowledged: bool,
+}
+
+/// Receives each newly raised alarm, e.g. to page someone. Closures taking
+/// `&Alarm` implement this trait.
+pub trait AlarmSink: Send {
+    fn raised(&mut self, alarm: &Alarm);
+}
+impl<F: FnMut(&Alarm) + Send> AlarmSink for F {
+    fn raised(&mut self, alarm: &Alarm) {
+        self(alarm)
+    }
+}
+
+/// Sink holder so the board can keep deriving Debug.
+struct Notify(Box<dyn AlarmSink>);
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Notify(..)")
+    }
+}
+
+/// Start or end of a maintenance period, see `AlarmBoard::maintenance_mode()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceEntry {
+    pub at: SystemTime,
+    pub on: bool,
+    pub reason: String,
+}
+
+/// Active alarms of one compressor. A condition stays listed, acknowledged or
+/// not, for as long as it is active; if it clears and comes back it is raised
+/// as a new, unacknowledged alarm.
+#[derive(Debug)]
+pub struct AlarmBoard {
+    active: BTreeMap<AlarmKind, Alarm>,
+    severities: BTreeMap<AlarmKind, Severity>,
+    clock: Arc<dyn Clock>,
+    maintenance: bool,
+    // Alarms raised while in maintenance, not notified yet
+    suppressed: BTreeSet<AlarmKind>,
+    maintenance_log: Vec<MaintenanceEntry>,
+    notify: Option<Notify>,
+    audit: Option<Audit>,
+    // Identify the unit in audit entries
+    device_addr: u8,
+    labels: Arc<DeviceLabels>,
+}
+impl Default for AlarmBoard {
+    fn default() -> Self {
+        Self {
+            active: BTreeMap::new(),
+            severities: BTreeMap::new(),
+            clock: Arc::new(SystemClock),
+            maintenance: false,
+            suppressed: BTreeSet::new(),
+            maintenance_log: Vec::new(),
+            notify: None,
+            audit: None,
+            device_addr: 0,
+            labels: Arc::default(),
+        }
+    }
+}
+impl AlarmBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Timestamps alarms from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Overrides the default severity of `kind`.
+    pub fn severity(mut self, kind: AlarmKind, severity: Severity) -> Self {
+        self.severities.insert(kind, severity);
+        self
+    }
+    /// Hands each alarm returned by `update()` to `sink`. With `SharedAlarms`
+    /// the sink runs with the board locked, so it must not lock it again.
+    pub fn alarm_sink(mut self, sink: impl AlarmSink + 'static) -> Self {
+        self.notify = Some(Notify(Box::new(sink)));
+        self
+    }
+    /// Records each `maintenance_mode()` change in `sink`, alongside the
+    /// writes audited by API handles. `device_addr` and `labels` identify
+    /// the unit in the entries, e.g. `api.labels().clone()`.
+    pub fn audit_sink(
+        mut self,
+        device_addr: u8,
+        labels: DeviceLabels,
+        sink: impl AuditSink + 'static,
+    ) -> Self {
+        self.audit = Some(Audit(Box::new(sink)));
+        self.device_addr = device_addr;
+        self.labels = Arc::new(labels);
+        self
+    }
+    /// Updates the active alarms from a snapshot. Returns the alarms raised by
+    /// this snapshot, i.e. the ones to notify about, after passing them to the
+    /// alarm sink. Nothing is returned in maintenance mode, but alarms are
+    /// still tracked and logged.
+    pub fn update(&mut self, status: &CompressorStatus) -> Vec<Alarm> {
+        let now = self.clock.system_time();
+        let mut raised = Vec::new();
+        for kind in AlarmKind::ALL {
+            if !kind.active(status) {
+                self.active.remove(&kind);
+                self.suppressed.remove(&kind);
+                continue;
+            }
+            if self.active.contains_key(&kind) {
+                continue;
+            }
+            let alarm = Alarm {
+                kind,
+                severity: self
+                    .severities
+                    .get(&kind)
+                    .copied()
+                    .unwrap_or(kind.default_severity()),
+                since: now,
+                acknowledged: false,
+            };
+            tracing::warn!(
+                ?kind,
+                severity = ?alarm.severity,
+                maintenance = self.maintenance,
+                "alarm raised"
+            );
+            if self.maintenance {
+                self.suppressed.insert(kind);
+            } else {
+                raised.push(alarm.clone());
+            }
+            self.active.insert(kind, alarm);
+        }
+        if let Some(Notify(sink)) = &mut self.notify {
+            raised.iter().for_each(|alarm| sink.raised(alarm));
+        }
+        raised
+    }
+    /// Suppresses alarm notifications while technicians service the unit.
+    /// Each change is kept in `maintenance_log()` and recorded in the audit
+    /// sink, if one is set. Alarms raised during
+    /// maintenance that are still active afterwards are raised again by the
+    /// next `update()`.
+    pub fn maintenance_mode(&mut self, on: bool, reason: impl Into<String>) {
+        let reason = reason.into();
+        tracing::info!(on, reason, "maintenance mode");
+        if self.maintenance && !on {
+            for kind in std::mem::take(&mut self.suppressed) {
+                self.active.remove(&kind);
+            }
+        }
+        self.maintenance = on;
+        let at = self.clock.system_time();
+        if let Some(Audit(sink)) = &mut self.audit {
+            sink.record(&AuditEntry {
+                at,
+                device_addr: self.device_addr,
+                action: AuditAction::Maintenance { on },
+                error: None,
+                reason: Some(reason.clone()),
+                labels: Arc::clone(&self.labels),
+            });
+        }
+        self.maintenance_log
+            .push(MaintenanceEntry { at, on, reason });
+    }
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance
+    }
+    pub fn maintenance_log(&self) -> &[MaintenanceEntry] {
+        &self.maintenance_log
+    }
+    /// Marks an active alarm as acknowledged. False if `kind` isn't active.
+    pub fn acknowledge(&mut self, kind: AlarmKind) -> bool {
+        self.active
+            .get_mut(&kind)
+            .map(|alarm| alarm.acknowledged = true)
+            .is_some()
+    }
+    /// All active alarms, acknowledged ones included, most severe first.
+    pub fn active(&self) -> Vec<Alarm> {
+        let mut alarms: Vec<_> = self.active.values().cloned().collect();
+        alarms.sort_by_key(|alarm| Reverse(alarm.severity));
+        alarms
+    }
+    /// Active alarms nobody has acknowledged yet, for sinks that re-alert
+    /// while a condition persists. Empty in maintenance mode.
+    pub fn unacknowledged(&self) -> Vec<Alarm> {
+        if self.maintenance {
+            return Vec::new();
+        }
+        self.active()
+            .into_iter()
+            .filter(|alarm| !alarm.acknowledged)
+            .collect()
+    }
+    /// Most severe active alarm, if any.
+    pub fn highest_severity(&self) -> Option<Severity> {
+        self.active.values().map(|alarm| alarm.severity).max()
+    }
+}
+
+/// Alarm board shared between the poller thread and the application.
+pub type SharedAlarms = Arc<Mutex<AlarmBoard>>;
+
+/// Updates the alarm board with each poller reading. Raised alarms go to the
+/// board's alarm sink.
+impl ReadingSink<CompressorStatus> for SharedAlarms {
+    fn on_snapshot(&mut self, value: &CompressorStatus) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).update(value);
+    }
+}
+/// Same, for a poller reading with `read_all_with_provenance()`.
+impl ReadingSink<(CompressorStatus, Provenance)> for SharedAlarms {
+    fn on_snapshot(&mut self, value: &(CompressorStatus, Provenance)) {
+        self.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .update(&value.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{clock::MockClock, history::test::status};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_acknowledge() {
+        let clock = Arc::new(MockClock::new(UNIX_EPOCH));
+        let mut board = AlarmBoard::new()
+            .with_clock(clock.clone())
+            .severity(AlarmKind::ClockBatteryLow, Severity::Warning);
+        let mut s = status(60.0);
+        s.err_code_status = true;
+        s.clock_batt_low = true;
+        let raised = board.update(&s);
+        assert_eq!(raised.len(), 2);
+        assert_eq!(board.highest_severity(), Some(Severity::Critical));
+        assert_eq!(board.active()[1].severity, Severity::Warning);
+        assert!(board.acknowledge(AlarmKind::ErrorCode));
+        assert!(!board.acknowledge(AlarmKind::MemoryLoss));
+        // Still active, but no longer pending and not raised again
+        clock.advance(Duration::from_secs(60));
+        assert!(board.update(&s).is_empty());
+        assert_eq!(board.active().len(), 2);
+        assert_eq!(board.unacknowledged().len(), 1);
+        // Clearing and recurring raises a fresh alarm
+        s.err_code_status = false;
+        board.update(&s);
+        s.err_code_status = true;
+        let raised = board.update(&s);
+        assert_eq!(raised.len(), 1);
+        assert!(!raised[0].acknowledged);
+        assert_eq!(raised[0].since, UNIX_EPOCH + Duration::from_secs(60));
+    }
+    #[test]
+    fn test_shared_alarms_notify() {
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&notified);
+        let mut alarms: SharedAlarms = Arc::new(Mutex::new(
+            AlarmBoard::new()
+                .alarm_sink(move |alarm: &Alarm| sink.lock().unwrap().push(alarm.kind)),
+        ));
+        let mut s = status(60.0);
+        s.temp_sensor_fail = true;
+        alarms.on_snapshot(&s);
+        alarms.on_snapshot(&s);
+        assert_eq!(*notified.lock().unwrap(), [AlarmKind::TempSensorFail]);
+    }
+    #[test]
+    fn test_maintenance_mode() {
+        let audited = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&audited);
+        let mut board = AlarmBoard::new().audit_sink(
+            0x10,
+            DeviceLabels::new().name("cryo-a"),
+            move |entry: &AuditEntry| sink.lock().unwrap().push(entry.clone()),
+        );
+        let mut s = status(60.0);
+        board.maintenance_mode(true, "oil adsorber swap");
+        s.pressure_sensor_fail = true;
+        s.mem_loss = true;
+        assert!(board.update(&s).is_empty());
+        assert_eq!(board.active().len(), 2);
+        assert!(board.unacknowledged().is_empty());
+        // Cleared during maintenance, so only the sensor fault is raised after
+        s.mem_loss = false;
+        board.update(&s);
+        board.maintenance_mode(false, "done");
+        let raised = board.update(&s);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].kind, AlarmKind::PressureSensorFail);
+        let log = board.maintenance_log();
+        assert_eq!(log.len(), 2);
+        assert!(log[0].on && !log[1].on);
+        assert_eq!(log[0].reason, "oil adsorber swap");
+        let audited = audited.lock().unwrap();
+        assert_eq!(audited.len(), 2);
+        assert_eq!(audited[0].action, AuditAction::Maintenance { on: true });
+        assert_eq!(audited[0].reason.as_deref(), Some("oil adsorber swap"));
+        assert_eq!(audited[1].device_addr, 0x10);
+        assert_eq!(audited[1].labels.name.as_deref(), Some("cryo-a"));
+    }
+}
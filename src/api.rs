@@ -1,16 +1,68 @@
 /* The user facing API for communication with Cryomech compressors */
+// The comm path must never panic on device input
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::indexing_slicing
+    )
+)]
 
 use crate::{
-    CResult, Error,
-    packet::{CPacketSmdp, RequestType},
+    CResult, Error, Real,
+    audit::{Audit, AuditAction, AuditEntry, AuditSink},
+    cache::{CacheTtl, ReadCache},
+    capability::{self, Backend, Capability, ProbeCache},
+    capture::{CaptureFormat, SessionCapture},
+    clock::{Clock, SystemClock},
+    control::{self, Control, ControlToken},
+    core::{
+        packet::{CPacketSmdp, RequestType, ResponseData, SMDP_OPCODE, SmdpFrame},
+        register::Register,
+    },
+    diagnostics::{Diagnostics, ErrorRecord, TransactionContext},
+    dictionary::{
+        CLEAR_MIN_MAX_HASH, DictionaryEntry, FW_CHECKSUM_HASH, FromRaw, START_HASH, STOP_HASH,
+        dictionary,
+    },
+    discovery::{self, UsbMatch},
+    guard::{ApiGuard, OnDrop},
+    interlock::{InterlockLimits, InterlockReadings},
+    labels::DeviceLabels,
+    link::{self, Direction, LinkState},
+    model::CompressorModel,
+    provenance::{CRATE_VERSION, Provenance},
+    reboot::{DeviceRebooted, RebootDetector, RebootSignature},
+    shutdown::{ShutdownCheck, ShutdownReport},
+    startup::{StartCheck, StartReport},
+    stats::{AutoTimeout, LinkStats, TimeoutTuner},
+    status::CompressorStatus,
+    verify::{WriteVerifier, WriteVerifiers},
 };
 use serialport::SerialPort;
 use smdp::{SmdpPacketHandler, SmdpPacketV2, SmdpPacketV3, format::ResponseCode};
 use std::{
+    collections::{BTreeMap, HashMap},
     io::{Read, Write},
-    time::Duration,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+// Delay between resends while the device reports busy
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(10);
+// Upper bound on frames drained after a reply, guards against a babbling device
+const MAX_EXTRA_FRAMES: usize = 8;
+/// Frames sent to this address are acted on by every device on the bus, none
+/// of which reply.
+pub const BROADCAST_ADDR: u8 = 0xFF;
+// SRLNO of the first V3+ request of a session
+const INITIAL_SRLNO: u8 = 0x17;
+// Stop writes sent by emergency_stop(), in case one is lost on a noisy line
+const EMERGENCY_STOP_REPEATS: usize = 3;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// Flags the SMDP frame format to be used.
 pub enum SmdpVersion {
@@ -20,15 +72,137 @@ pub enum SmdpVersion {
     V3Plus,
 }
 
-/// SMDP API to Cryomech devices. Assumes point-to-point communication, not multi-drop.
+/// Outcome of a compressor start/stop request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlOutcome {
+    /// Compressor was already in the requested state, nothing was written.
+    AlreadyInState,
+    /// Command was sent and the new state was read back.
+    Verified,
+    /// Command was sent but the compressor did not report the new state.
+    NotVerified,
+    /// Command was sent, verification is disabled.
+    Unchecked,
+}
+
+/// How state-changing writes are verified after they are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyPolicy {
+    /// Don't read back the new state.
+    Disabled,
+    /// Re-check the state up to `attempts` times, waiting `delay` before each
+    /// check. Stops at the first successful check.
+    Recheck { attempts: u32, delay: Duration },
+    /// Check right away, then every `interval` until the state is reached or
+    /// `timeout` has elapsed.
+    PollUntil {
+        interval: Duration,
+        timeout: Duration,
+    },
+}
+impl Default for VerifyPolicy {
+    fn default() -> Self {
+        Self::PollUntil {
+            interval: Duration::from_millis(100),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// What to do with frames that arrive after the reply to a request, e.g.
+/// duplicate responses from flaky firmware. Any policy other than `Ignore`
+/// waits one read timeout after each reply to drain the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtraFramePolicy {
+    /// Don't check for extra frames, they are left for the next transaction.
+    #[default]
+    Ignore,
+    /// Use the first reply and discard the rest.
+    TakeFirst,
+    /// Use the last reply received.
+    TakeLast,
+    /// Fail the transaction.
+    Error,
+}
+
+/// How the serial port waits for reply bytes. The SMDP handler keeps reading
+/// until a full frame arrives or the read timeout expires; this sets how long
+/// each individual read may block in the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RxPolling {
+    /// Non-blocking reads retried in a loop. Lowest latency, keeps a core busy
+    /// while waiting for a reply.
+    #[default]
+    Spin,
+    /// Each read blocks for up to the given duration. Returns as soon as bytes
+    /// arrive, but the read timeout may overshoot by up to one slice.
+    Blocking(Duration),
+}
+impl RxPolling {
+    fn port_timeout(self) -> Duration {
+        match self {
+            Self::Spin => Duration::ZERO,
+            Self::Blocking(slice) => slice,
+        }
+    }
+}
+
+/// SMDP API to Cryomech devices. Talks to one device address at a time; on a
+/// multi-drop bus, share the handle between devices with `SharedBus`, and use
+/// `broadcast_write()` to write to every device at once.
 #[derive(Debug)]
 pub struct CryomechApiSmdp<T: Read + Write> {
     smdp_handler: SmdpPacketHandler<T>,
     read_timeout: usize,
     com_port: String,
+    baud: u32,
+    max_framesize: usize,
     dev_addr: u8,
     version: SmdpVersion,
     srlno: u8,
+    link: Arc<LinkState>,
+    reconnect_attempts: u32,
+    reconnect_delay: Duration,
+    validate_response: bool,
+    suppress_echo: bool,
+    verify_policy: VerifyPolicy,
+    diagnostics: Diagnostics,
+    extra_frames: ExtraFramePolicy,
+    read_cache: Option<ReadCache>,
+    pipelining: bool,
+    rx_polling: RxPolling,
+    interlock: InterlockLimits,
+    require_arming: bool,
+    armed_until: Option<Instant>,
+    audit: Option<Audit>,
+    audit_reason: Option<String>,
+    handle_id: u64,
+    require_token: bool,
+    token_issued: bool,
+    token_active: bool,
+    // Set while an internal stop (emergency, watchdog, schedule) is sent
+    bypass_write_gates: bool,
+    fault_lockout: bool,
+    model: Option<CompressorModel>,
+    clock: Arc<dyn Clock>,
+    link_stats: LinkStats,
+    probed: ProbeCache,
+    labels: Arc<DeviceLabels>,
+    timeout_tuner: Option<TimeoutTuner>,
+    // Second handle on the open port, used to rebuild the SMDP handler when
+    // the read timeout changes. The port is opened exclusively, so it can't
+    // simply be reopened while the handler holds it. Only kept with
+    // auto_timeout(), to save an OS handle per unit otherwise.
+    spare_port: Option<Box<dyn SerialPort>>,
+    link_dead_after: Option<u32>,
+    silent_transactions: u32,
+    corrupt_frame_retries: u32,
+    write_verifiers: WriteVerifiers,
+    reboot_detector: Option<RebootDetector>,
+    last_reboot: Option<DeviceRebooted>,
+    firmware_checksum: Option<u32>,
+    // Per-entry read times, collected while read_all_with_provenance() runs
+    field_latency: Option<BTreeMap<u16, Duration>>,
 }
 impl CryomechApiSmdp<Box<dyn SerialPort>> {
     pub fn new(
@@ -38,27 +212,259 @@ impl CryomechApiSmdp<Box<dyn SerialPort>> {
         dev_addr: u8,
         max_framesize: usize,
         version: SmdpVersion,
+    ) -> CResult<Self> {
+        Self::open(
+            com_port,
+            baud,
+            read_timeout_ms,
+            dev_addr,
+            max_framesize,
+            version,
+            RxPolling::default(),
+        )
+    }
+    fn open(
+        com_port: &str,
+        baud: u32,
+        read_timeout_ms: usize,
+        dev_addr: u8,
+        max_framesize: usize,
+        version: SmdpVersion,
+        rx_polling: RxPolling,
     ) -> CResult<Self> {
         // Build serialport instance then self
-        let io = serialport::new(com_port, baud)
-            .open()
-            .map_err(|e| Error::Serial(e))?;
-        Ok(Self {
+        let link = Arc::new(LinkState::default());
+        let io = link::open_port(com_port, baud, rx_polling.port_timeout(), &link)?;
+        let mut api = Self::on_port(io, link, com_port, read_timeout_ms, max_framesize, false);
+        api.baud = baud;
+        api.dev_addr = dev_addr;
+        api.version = version;
+        api.rx_polling = rx_polling;
+        Ok(api)
+    }
+    /// Handle with default settings on a port that is already open.
+    fn on_port(
+        io: Box<dyn SerialPort>,
+        link: Arc<LinkState>,
+        com_port: &str,
+        read_timeout_ms: usize,
+        max_framesize: usize,
+        keep_spare: bool,
+    ) -> Self {
+        let spare_port = if keep_spare {
+            io.try_clone().ok()
+        } else {
+            None
+        };
+        Self {
             smdp_handler: SmdpPacketHandler::new(io, read_timeout_ms, max_framesize),
             read_timeout: read_timeout_ms,
             com_port: com_port.into(),
-            dev_addr,
-            version,
-            srlno: 0x17,
-        })
+            baud: 115200,
+            max_framesize,
+            dev_addr: 0x10,
+            version: SmdpVersion::V2,
+            srlno: INITIAL_SRLNO,
+            link,
+            reconnect_attempts: 3,
+            reconnect_delay: Duration::from_millis(500),
+            validate_response: true,
+            suppress_echo: false,
+            verify_policy: VerifyPolicy::default(),
+            diagnostics: Diagnostics::default(),
+            extra_frames: ExtraFramePolicy::default(),
+            read_cache: None,
+            pipelining: false,
+            rx_polling: RxPolling::default(),
+            interlock: InterlockLimits::default(),
+            require_arming: false,
+            armed_until: None,
+            audit: None,
+            audit_reason: None,
+            handle_id: control::next_handle_id(),
+            require_token: false,
+            token_issued: false,
+            token_active: false,
+            bypass_write_gates: false,
+            fault_lockout: false,
+            model: None,
+            clock: Arc::new(SystemClock),
+            link_stats: LinkStats::default(),
+            probed: ProbeCache::new(),
+            labels: Arc::default(),
+            timeout_tuner: None,
+            spare_port,
+            link_dead_after: None,
+            silent_transactions: 0,
+            corrupt_frame_retries: 2,
+            write_verifiers: WriteVerifiers::default(),
+            reboot_detector: Some(RebootDetector::default()),
+            last_reboot: None,
+            firmware_checksum: None,
+            field_latency: None,
+        }
     }
-    /// In ms
+    /// In ms. With `auto_timeout()` this is the currently tuned value.
     pub fn read_timeout(&self) -> usize {
         self.read_timeout
     }
     pub fn com_port(&self) -> &str {
         &self.com_port
     }
+    /// Response time histograms per dictionary entry, for spotting registers
+    /// the panel services slowly and tuning the read timeout.
+    pub fn link_stats(&self) -> &LinkStats {
+        &self.link_stats
+    }
+    /// Starts new link statistics and returns the ones collected so far, e.g.
+    /// for a report at a shift boundary.
+    pub fn reset_link_stats(&mut self) -> LinkStats {
+        std::mem::take(&mut self.link_stats)
+    }
+    /// Calls `hook` with every chunk of bytes written to or read from the
+    /// port, e.g. for protocol analyzers or terminal views. Chunks follow the
+    /// port's reads and writes, so a frame may arrive in several pieces. The
+    /// hook runs on the comm path and should return quickly.
+    pub fn set_wire_hook(&mut self, hook: impl Fn(&Direction, &[u8]) + Send + Sync + 'static) {
+        self.link.set_hook(Some(Arc::new(hook)));
+    }
+    pub fn clear_wire_hook(&mut self) {
+        self.link.set_hook(None);
+    }
+    /// Writes all wire traffic to `path` until the wire hook is cleared or
+    /// replaced. Uses the wire hook slot.
+    pub fn capture_session(
+        &mut self,
+        path: impl AsRef<Path>,
+        format: CaptureFormat,
+    ) -> CResult<()> {
+        let capture = SessionCapture::create(path, format)?;
+        self.set_wire_hook(move |dir, bytes| capture.record(dir, bytes));
+        Ok(())
+    }
+    pub fn labels(&self) -> &DeviceLabels {
+        &self.labels
+    }
+    pub fn set_labels(&mut self, labels: DeviceLabels) {
+        self.labels = Arc::new(labels);
+    }
+    /// Shared copy of the labels, for records that outlive a borrow.
+    pub(crate) fn labels_arc(&self) -> Arc<DeviceLabels> {
+        Arc::clone(&self.labels)
+    }
+    /// Name label for tracing events, empty if unset.
+    pub(crate) fn device_name(&self) -> &str {
+        self.labels.name.as_deref().unwrap_or_default()
+    }
+    /// Model selected with `CryomechApiSmdpBuilder::for_model()`, if any.
+    pub fn model(&self) -> Option<CompressorModel> {
+        self.model
+    }
+    /// Allows writes for the next `window` when the handle was built with
+    /// `armed_writes(true)`. Has no effect otherwise.
+    pub fn arm_writes(&mut self, window: Duration) {
+        self.armed_until = Some(self.clock.now() + window);
+    }
+    /// Ends the arming window early.
+    pub fn disarm_writes(&mut self) {
+        self.armed_until = None;
+    }
+    /// Hands out this handle's control token. Returns None after the first call.
+    pub fn take_control_token(&mut self) -> Option<ControlToken> {
+        if self.token_issued {
+            return None;
+        }
+        self.token_issued = true;
+        Some(ControlToken {
+            handle_id: self.handle_id,
+        })
+    }
+    /// Write access for the holder of this handle's token. Required for writes
+    /// when the handle was built with `control_token(true)`.
+    pub fn control(&mut self, token: &ControlToken) -> CResult<Control<'_>> {
+        if token.handle_id != self.handle_id {
+            return Err(Error::ControlTokenRequired);
+        }
+        Ok(Control::new(self))
+    }
+    pub(crate) fn set_token_active(&mut self, active: bool) {
+        self.token_active = active;
+    }
+    /// Checks the optional write gates in order: arming window, control token
+    /// and, for anything but a stop, the active fault lockout. Internal stops
+    /// sent through `ungated()` skip all of them.
+    fn write_gate(&mut self, hashval: u16) -> CResult<()> {
+        if self.bypass_write_gates {
+            return Ok(());
+        }
+        self.access_gate()?;
+        if self.fault_lockout && hashval != STOP_HASH && self.err_code_status()? {
+            return Err(Error::ActiveFault);
+        }
+        Ok(())
+    }
+    /// The gates that need no device read: arming window and control token.
+    fn access_gate(&self) -> CResult<()> {
+        if self.bypass_write_gates {
+            return Ok(());
+        }
+        if self.require_arming && self.armed_until.is_none_or(|t| self.clock.now() >= t) {
+            return Err(Error::WritesNotArmed);
+        }
+        if self.require_token && !self.token_active {
+            return Err(Error::ControlTokenRequired);
+        }
+        Ok(())
+    }
+    /// Reason recorded in the audit log with the next write sent.
+    pub fn audit_reason(&mut self, reason: impl Into<String>) {
+        self.audit_reason = Some(reason.into());
+    }
+    /// Retargets the handle at another device on the same bus. Cached values
+    /// belong to the previous device and are dropped.
+    pub(crate) fn set_device_addr(&mut self, addr: u8) {
+        if addr != self.dev_addr {
+            self.dev_addr = addr;
+            if let Some(cache) = self.read_cache.as_mut() {
+                cache.clear();
+            }
+            self.probed.clear();
+            // Read again for the next provenance record
+            self.firmware_checksum = None;
+            // Errors seen on the previous device say nothing about this one
+            if let Some(detector) = self.reboot_detector.as_mut() {
+                detector.switch_device();
+            }
+        }
+    }
+    /// Most recent error seen on this handle, with the transaction it came from.
+    pub fn last_error(&self) -> Option<&ErrorRecord> {
+        self.diagnostics.last_error.as_ref()
+    }
+    pub fn interlock_limits(&self) -> &InterlockLimits {
+        &self.interlock
+    }
+    /// Replaces the limits checked by `start_compressor_checked()`, e.g. after
+    /// the site policy changed. Takes effect with the next checked start.
+    pub fn set_interlock_limits(&mut self, limits: InterlockLimits) {
+        self.interlock = limits;
+    }
+    /// Reloads the interlock limits from a policy file, see
+    /// `InterlockLimits::from_file()`. The current limits stay in place if the
+    /// file can't be read or parsed.
+    #[cfg(feature = "policy")]
+    pub fn reload_interlock_policy(&mut self, path: impl AsRef<Path>) -> CResult<()> {
+        self.interlock = InterlockLimits::from_file(path)?;
+        Ok(())
+    }
+    /// Most recent panel reboot detected on this handle.
+    pub fn last_reboot(&self) -> Option<&DeviceRebooted> {
+        self.last_reboot.as_ref()
+    }
+    /// Transaction counters and last error for this handle.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
     /// Increments SRLNO using appropriate logic (valid SRLNO: [16 - 255]). Returns the current
     /// value of the srlno for use.
     fn increment_srlno(&mut self) -> u8 {
@@ -70,355 +476,841 @@ impl CryomechApiSmdp<Box<dyn SerialPort>> {
         }
         ret
     }
+    /// SRLNO most recently handed out by `increment_srlno`.
+    fn last_srlno(&self) -> u8 {
+        if self.srlno == 0x11 {
+            u8::MAX
+        } else {
+            self.srlno.saturating_sub(1)
+        }
+    }
+    /// Checks that a reply came from the addressed device and carries the
+    /// Cryomech opcode. Skipped if validation was relaxed in the builder.
+    fn check_reply_header(&self, addr: u8, cmd_rsp: u8, opcode: u8) -> CResult<()> {
+        if !self.validate_response {
+            return Ok(());
+        }
+        if addr != self.dev_addr {
+            return Err(Error::AddressMismatch {
+                expected: self.dev_addr,
+                got: addr,
+            });
+        }
+        if !CPacketSmdp::is_reply_to(cmd_rsp, opcode) {
+            return Err(Error::UnexpectedOpcode(cmd_rsp));
+        }
+        Ok(())
+    }
+    /// Applies the verify policy to a write that was just sent, using `check`
+    /// to read back whether the new state has been reached.
+    fn verify_write(
+        &mut self,
+        mut check: impl FnMut(&mut Self) -> CResult<bool>,
+    ) -> CResult<ControlOutcome> {
+        match self.verify_policy {
+            VerifyPolicy::Disabled => return Ok(ControlOutcome::Unchecked),
+            VerifyPolicy::Recheck { attempts, delay } => {
+                for _ in 0..attempts {
+                    self.clock.sleep(delay);
+                    if check(self)? {
+                        return Ok(ControlOutcome::Verified);
+                    }
+                }
+            }
+            VerifyPolicy::PollUntil { interval, timeout } => {
+                let start = self.clock.now();
+                loop {
+                    if check(self)? {
+                        return Ok(ControlOutcome::Verified);
+                    }
+                    if self.elapsed(start) >= timeout {
+                        break;
+                    }
+                    self.clock
+                        .sleep(interval.min(timeout.saturating_sub(self.elapsed(start))));
+                }
+            }
+        }
+        Ok(ControlOutcome::NotVerified)
+    }
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+    fn elapsed(&self, since: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(since)
+    }
+    /// Reopens the device path after the link reported the device as gone. Makes up to
+    /// `reconnect_attempts` tries, waiting `reconnect_delay` before each one.
+    fn reopen(&mut self) -> CResult<()> {
+        let mut result = Ok(());
+        for _ in 0..self.reconnect_attempts {
+            self.clock.sleep(self.reconnect_delay);
+            result = self.open_port();
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+    /// Opens `com_port` and swaps it in for the current port.
+    fn open_port(&mut self) -> CResult<()> {
+        let io = link::open_port(
+            &self.com_port,
+            self.baud,
+            self.rx_polling.port_timeout(),
+            &self.link,
+        )?;
+        if self.timeout_tuner.is_some() {
+            self.spare_port = io.try_clone().ok();
+        }
+        self.smdp_handler = SmdpPacketHandler::new(io, self.read_timeout, self.max_framesize);
+        self.link.reset();
+        self.silent_transactions = 0;
+        Ok(())
+    }
+    /// Feeds the outcome of a transaction to the timeout tuner and swaps in a
+    /// handler with the new timeout if it changed. Keeps the old timeout if the
+    /// port can't be cloned.
+    fn tune_timeout<T>(&mut self, result: &CResult<T>, latency: Duration) {
+        let Some(tuner) = &mut self.timeout_tuner else {
+            return;
+        };
+        let retuned = match result {
+            Ok(_) => tuner.observe(latency),
+            Err(e) if !e.is_nak() => tuner.failed(),
+            Err(_) => None,
+        };
+        if let Some(timeout) = retuned {
+            self.set_handler_timeout(timeout);
+        }
+    }
+    fn set_handler_timeout(&mut self, timeout: Duration) {
+        if let Some(port) = &self.spare_port
+            && let Ok(io) = port.try_clone()
+        {
+            self.read_timeout = timeout.as_millis() as usize;
+            self.smdp_handler = SmdpPacketHandler::new(io, self.read_timeout, self.max_framesize);
+        }
+    }
+    /// True once the link has reported the device as gone and it hasn't been
+    /// reopened since.
+    pub(crate) fn link_down(&self) -> bool {
+        self.link.device_gone()
+    }
+    /// True while the last `link_dead_after` transactions received no bytes at
+    /// all, i.e. the adapter or cable is dead rather than the compressor
+    /// rejecting or garbling requests.
+    pub fn link_dead(&self) -> bool {
+        self.link_dead_after
+            .is_some_and(|n| self.silent_transactions >= n)
+    }
+    pub(crate) fn silent_transactions(&self) -> u32 {
+        self.silent_transactions
+    }
+    /// Counts transactions that failed without a single byte received. Past
+    /// the threshold, such failures are reported as `Error::LinkDead`.
+    fn check_link_dead<T>(&mut self, result: CResult<T>, rx_before: u64) -> CResult<T> {
+        if result.is_ok() || self.link.rx_bytes() != rx_before {
+            self.silent_transactions = 0;
+            return result;
+        }
+        self.silent_transactions = self.silent_transactions.saturating_add(1);
+        match result {
+            Err(_) if self.link_dead() => Err(Error::LinkDead(self.silent_transactions)),
+            result => result,
+        }
+    }
+    /// Moves the handle to `com_port`, e.g. after an adapter was replugged and
+    /// came back under a different name.
+    pub(crate) fn rebind(&mut self, com_port: &str) -> CResult<()> {
+        self.com_port = com_port.into();
+        self.open_port()
+    }
     /// Helper function that writes/reads to/from the wire and handles
-    /// SMDP protocol error checking
+    /// SMDP protocol error checking. If the transaction failed because the
+    /// device disappeared (e.g. USB hiccup), the port is reopened and the
+    /// transaction retried once.
     fn comm_handler(
         &mut self,
         req_type: RequestType,
         hashval: u16,
         array_idx: u8,
+    ) -> CResult<Option<u32>> {
+        if self.dev_addr == BROADCAST_ADDR && matches!(req_type, RequestType::Read) {
+            return Err(Error::BroadcastRead);
+        }
+        if let (RequestType::Read, Some(cache)) = (req_type, &self.read_cache)
+            && let Some(value) = cache.get(hashval, array_idx, self.clock.now())
+        {
+            return Ok(Some(value));
+        }
+        let write = match req_type {
+            RequestType::Read => None,
+            RequestType::Write(value) => Some(AuditAction::Write {
+                hashval,
+                array_idx,
+                value,
+            }),
+        };
+        let result = self.dispatch(write, hashval, array_idx, |api| {
+            let result = api.transaction(req_type, hashval, array_idx);
+            api.check_reboot(req_type, hashval, array_idx, result)
+        });
+        if let (Ok(Some(value)), Some(cache)) = (&result, &mut self.read_cache) {
+            cache.insert(hashval, array_idx, *value, self.clock.now());
+        }
+        result
+    }
+    /// Steps shared by dictionary requests and raw commands: write gates and
+    /// auditing for writes, one reopen and resend if the device disappeared,
+    /// diagnostics, and dropping cached values after a successful write.
+    fn dispatch<T>(
+        &mut self,
+        write: Option<AuditAction>,
+        hashval: u16,
+        array_idx: u8,
+        mut send: impl FnMut(&mut Self) -> CResult<T>,
+    ) -> CResult<T> {
+        if let Some(action) = &write
+            && let Err(e) = self.write_gate(hashval)
+        {
+            let result = Err(e);
+            self.audit(action.clone(), &result);
+            return result;
+        }
+        let result = match send(self) {
+            Err(_) if self.link.device_gone() && self.reconnect_attempts > 0 => {
+                self.reopen().and_then(|()| send(self))
+            }
+            result => result,
+        };
+        let context = TransactionContext {
+            hashval,
+            array_idx,
+            write: write.is_some(),
+            srlno: (self.version == SmdpVersion::V3Plus).then(|| self.last_srlno()),
+        };
+        if let Some(action) = write {
+            self.audit(action, &result);
+            // A write may change any cached value (e.g. clearing min/max)
+            if let (Ok(_), Some(cache)) = (&result, &mut self.read_cache) {
+                cache.clear();
+            }
+        }
+        self.diagnostics.record(&result, context);
+        result
+    }
+    /// Feeds the reboot detector. After a reboot the unit is handshaken
+    /// again and a failed read is retried once; a failed write is returned
+    /// as is, since it may or may not have taken effect.
+    fn check_reboot(
+        &mut self,
+        req_type: RequestType,
+        hashval: u16,
+        array_idx: u8,
+        result: CResult<Option<u32>>,
+    ) -> CResult<Option<u32>> {
+        let Some(signature) = self.reboot_detector.as_mut().and_then(|detector| {
+            let is_read = matches!(req_type, RequestType::Read);
+            detector.observe(self.dev_addr, is_read, hashval, &result)
+        }) else {
+            return result;
+        };
+        self.rehandshake(signature);
+        match req_type {
+            RequestType::Read => self.transaction(req_type, hashval, array_idx),
+            RequestType::Write(_) => result,
+        }
+    }
+    /// Starts a fresh session with a rebooted panel: SRLNO starts over,
+    /// cached values and probe results are dropped, and the firmware checksum
+    /// is read to confirm the unit answers.
+    fn rehandshake(&mut self, signature: RebootSignature) {
+        self.srlno = INITIAL_SRLNO;
+        if let Some(cache) = self.read_cache.as_mut() {
+            cache.clear();
+        }
+        self.probed.clear();
+        let firmware_checksum = self
+            .transaction(RequestType::Read, FW_CHECKSUM_HASH, 0x00)
+            .ok()
+            .flatten();
+        self.firmware_checksum = firmware_checksum;
+        tracing::warn!(
+            device = self.device_name(),
+            ?signature,
+            ?firmware_checksum,
+            "panel rebooted, session restarted"
+        );
+        self.last_reboot = Some(DeviceRebooted {
+            at: self.clock.system_time(),
+            signature,
+            firmware_checksum,
+            labels: self.labels_arc(),
+        });
+    }
+    fn audit<T>(&mut self, action: AuditAction, result: &CResult<T>) {
+        // The reason covers one write method call, which is a single write
+        let reason = self.audit_reason.take();
+        if let Some(Audit(sink)) = &mut self.audit {
+            sink.record(&AuditEntry {
+                at: self.clock.system_time(),
+                device_addr: self.dev_addr,
+                action,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                reason,
+                labels: Arc::clone(&self.labels),
+            });
+        }
+    }
+    /// Single request/response exchange on the wire. Busy replies are retried
+    /// until the read timeout budget is used up. If frame capture is enabled,
+    /// protocol errors carry a dump of the bytes exchanged.
+    fn transaction(
+        &mut self,
+        req_type: RequestType,
+        hashval: u16,
+        array_idx: u8,
+    ) -> CResult<Option<u32>> {
+        if self.dev_addr == BROADCAST_ADDR {
+            return self.send_broadcast(req_type, hashval, array_idx);
+        }
+        self.retry_exchange(Some(hashval), |api| {
+            api.exchange(req_type, hashval, array_idx)
+        })
+    }
+    /// Runs `exchange` until it isn't answered busy or with a corrupt frame,
+    /// within the retry limits. Latency is recorded under `hashval`, if given.
+    fn retry_exchange<T>(
+        &mut self,
+        hashval: Option<u16>,
+        mut exchange: impl FnMut(&mut Self) -> CResult<T>,
+    ) -> CResult<T> {
+        let start = self.clock.now();
+        let rx_before = self.link.rx_bytes();
+        let budget = Duration::from_millis(self.read_timeout as u64);
+        let mut corrupt_retries = 0;
+        let result = loop {
+            self.link.clear_capture();
+            let attempt_rx = self.link.rx_bytes();
+            match exchange(self) {
+                Err(Error::DeviceBusy) if self.elapsed(start) + BUSY_RETRY_DELAY < budget => {
+                    self.clock.sleep(BUSY_RETRY_DELAY)
+                }
+                // Bytes arrived but the smdp layer rejected the frame, e.g. a
+                // checksum failure from line noise
+                Err(Error::Smdp(e))
+                    if self.link.rx_bytes() != attempt_rx && !self.link.device_gone() =>
+                {
+                    self.link_stats.record_corrupt();
+                    if corrupt_retries >= self.corrupt_frame_retries {
+                        break Err(Error::Smdp(e));
+                    }
+                    corrupt_retries += 1;
+                }
+                result => break result,
+            }
+        };
+        let latency = self.elapsed(start);
+        if let (Ok(_), Some(hashval)) = (&result, hashval) {
+            self.link_stats.record(hashval, latency);
+        }
+        self.tune_timeout(&result, latency);
+        let result = self.check_link_dead(result, rx_before);
+        self.attach_frames(result)
+    }
+    /// Reads `indices` with pipelined requests (V3+ only). Cached values are
+    /// served as usual, the batch is resent once after a reconnect, and
+    /// indices the panel reports busy are retried through the regular path.
+    fn pipelined_reads(&mut self, hashval: u16, indices: &[u8]) -> CResult<Vec<u32>> {
+        let mut values = Vec::with_capacity(indices.len());
+        let mut missing = Vec::new();
+        for &idx in indices {
+            let value = self
+                .read_cache
+                .as_ref()
+                .and_then(|cache| cache.get(hashval, idx, self.clock.now()));
+            if value.is_none() {
+                missing.push(idx);
+            }
+            values.push(value);
+        }
+        if !missing.is_empty() {
+            let mut fetched = self.pipelined_batch(hashval, &missing)?.into_iter();
+            for value in values.iter_mut().filter(|value| value.is_none()) {
+                *value = fetched.next();
+            }
+        }
+        values
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::InvalidFormat("Missing reply in pipelined read"))
+    }
+    fn pipelined_batch(&mut self, hashval: u16, indices: &[u8]) -> CResult<Vec<u32>> {
+        let result = match self.pipelined_exchange(hashval, indices) {
+            Err(_) if self.link.device_gone() && self.reconnect_attempts > 0 => self
+                .reopen()
+                .and_then(|()| self.pipelined_exchange(hashval, indices)),
+            result => result,
+        };
+        let replies = match self.attach_frames(result) {
+            Ok(replies) => replies,
+            Err(e) => {
+                let result = Err(e);
+                let context = self.read_context(hashval, indices.first().copied());
+                self.diagnostics.record(&result, context);
+                return result;
+            }
+        };
+        let mut values = Vec::with_capacity(indices.len());
+        for (&idx, reply) in indices.iter().zip(replies) {
+            if let Err(Error::DeviceBusy) = reply {
+                values.push(self.read_entry(hashval, idx)?);
+                continue;
+            }
+            let context = self.read_context(hashval, Some(idx));
+            self.diagnostics.record(&reply, context);
+            let value = reply?;
+            if let Some(cache) = &mut self.read_cache {
+                cache.insert(hashval, idx, value, self.clock.now());
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+    fn read_context(&self, hashval: u16, array_idx: Option<u8>) -> TransactionContext {
+        TransactionContext {
+            hashval,
+            array_idx: array_idx.unwrap_or_default(),
+            write: false,
+            srlno: (self.version == SmdpVersion::V3Plus).then(|| self.last_srlno()),
+        }
+    }
+    /// Sends a read request for every index before reading any reply, matching
+    /// replies to requests by SRLNO. Replies are read until every request is
+    /// answered or the line goes quiet, even after a bad one, so nothing is
+    /// left on the line for the next transaction. Stray frames (echoes, late
+    /// replies to an earlier exchange) are skipped. Returns one result per
+    /// index.
+    fn pipelined_exchange(&mut self, hashval: u16, indices: &[u8]) -> CResult<Vec<CResult<u32>>> {
+        self.link.clear_capture();
+        let mut requests = Vec::with_capacity(indices.len());
+        let mut send_error = None;
+        for &idx in indices {
+            let srlno = self.increment_srlno();
+            let cpkt =
+                CPacketSmdp::new(self.dev_addr, Some(srlno), RequestType::Read, hashval, idx);
+            let req: SmdpPacketV3 = cpkt.try_into()?;
+            if let Err(e) = self.smdp_handler.write_once(&req) {
+                send_error = Some(Error::Smdp(e));
+                break;
+            }
+            requests.push(req);
+        }
+        let mut replies: Vec<Option<CResult<u32>>> = requests.iter().map(|_| None).collect();
+        let mut outstanding = requests.len();
+        // Room for an echo of each request on top of the usual extra frames
+        let echoes = if self.suppress_echo {
+            requests.len()
+        } else {
+            0
+        };
+        let mut stray_budget = MAX_EXTRA_FRAMES + echoes;
+        let mut line_error = None;
+        while outstanding > 0 {
+            let rx_before = self.link.rx_bytes();
+            let resp: SmdpPacketV3 = match self.smdp_handler.poll_once() {
+                Ok(resp) => resp,
+                Err(e) => {
+                    line_error.get_or_insert(Error::Smdp(e));
+                    // A garbled frame used up one reply, a timeout means the
+                    // rest isn't coming
+                    if self.link.rx_bytes() == rx_before || self.link.device_gone() {
+                        break;
+                    }
+                    outstanding -= 1;
+                    continue;
+                }
+            };
+            let echo = self.suppress_echo
+                && requests
+                    .iter()
+                    .any(|req| is_echo((req.cmd_rsp(), req.data()), (resp.cmd_rsp(), resp.data())));
+            let slot = requests
+                .iter()
+                .position(|req| req.srlno() == resp.srlno())
+                .and_then(|i| replies.get_mut(i))
+                .filter(|slot| slot.is_none());
+            match slot {
+                Some(slot) if !echo => {
+                    *slot = Some(self.accept_entry_reply(true, &resp, true).and_then(|data| {
+                        data.ok_or(Error::InvalidFormat("Expected data in response, got none."))
+                    }));
+                    outstanding -= 1;
+                }
+                _ if stray_budget > 0 => stray_budget -= 1,
+                _ => {
+                    line_error.get_or_insert(Error::SrlnoMismatch);
+                    break;
+                }
+            }
+        }
+        if let Some(e) = send_error {
+            return Err(e);
+        }
+        if replies.iter().any(Option::is_none) {
+            return Err(
+                line_error.unwrap_or(Error::InvalidFormat("Missing reply in pipelined read"))
+            );
+        }
+        Ok(replies.into_iter().flatten().collect())
+    }
+    /// Attaches a dump of the bytes exchanged to protocol errors, if frame
+    /// capture is enabled.
+    fn attach_frames<T>(&self, result: CResult<T>) -> CResult<T> {
+        match result {
+            Err(e) if e.is_protocol() && self.link.capture_enabled() => Err(Error::WithFrames {
+                source: Box::new(e),
+                dump: self.link.take_capture(),
+            }),
+            result => result,
+        }
+    }
+    /// Reads the reply to `req`, skipping an echo of the request and applying
+    /// the extra frame policy.
+    fn receive<P: SmdpFrame>(&mut self, req: &P) -> CResult<P> {
+        let mut resp: P = self.smdp_handler.poll_once().map_err(Error::Smdp)?;
+        if self.suppress_echo && is_echo((req.cmd_rsp(), req.data()), (resp.cmd_rsp(), resp.data()))
+        {
+            resp = self.smdp_handler.poll_once().map_err(Error::Smdp)?;
+        }
+        if self.extra_frames == ExtraFramePolicy::Ignore {
+            return Ok(resp);
+        }
+        let mut extra = 0;
+        while extra < MAX_EXTRA_FRAMES {
+            let next: Result<P, _> = self.smdp_handler.poll_once();
+            match next {
+                Ok(pkt) if self.extra_frames == ExtraFramePolicy::TakeLast => resp = pkt,
+                Ok(_) => {}
+                Err(_) => break,
+            }
+            extra += 1;
+        }
+        self.check_extra_frames(extra)?;
+        Ok(resp)
+    }
+    fn check_extra_frames(&self, extra: usize) -> CResult<()> {
+        if extra > 0 && self.extra_frames == ExtraFramePolicy::Error {
+            Err(Error::ExtraFrames(extra))
+        } else {
+            Ok(())
+        }
+    }
+    /// Validates a reply to a request with `opcode`: header, SRLNO, busy and
+    /// response code.
+    fn accept_reply(&self, opcode: u8, resp: &impl SmdpFrame, srlno_matches: bool) -> CResult<()> {
+        let cmd_rsp = resp.cmd_rsp();
+        self.check_reply_header(resp.addr(), cmd_rsp, opcode)?;
+        if !srlno_matches {
+            return Err(Error::SrlnoMismatch);
+        }
+        if CPacketSmdp::is_busy_reply(cmd_rsp) {
+            return Err(Error::DeviceBusy);
+        }
+        match resp.rsp().map_err(Error::Smdp)? {
+            ResponseCode::Ok => Ok(()),
+            other => Err(Error::ResponseNotOk(other)),
+        }
+    }
+    /// Validates a reply to a dictionary request and extracts its data if the
+    /// request was a read.
+    fn accept_entry_reply(
+        &self,
+        is_read: bool,
+        resp: &impl SmdpFrame,
+        srlno_matches: bool,
+    ) -> CResult<Option<u32>> {
+        self.accept_reply(SMDP_OPCODE, resp, srlno_matches)?;
+        if is_read {
+            CPacketSmdp::extract_data(resp.data()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    /// Request/response exchange for a non-dictionary command.
+    fn exchange_raw(&mut self, opcode: u8, data: &[u8]) -> CResult<Vec<u8>> {
+        match self.version {
+            SmdpVersion::V2 => {
+                let req = CPacketSmdp::raw_v2(self.dev_addr, opcode, data);
+                self.smdp_handler.write_once(&req).map_err(Error::Smdp)?;
+                let resp = self.receive(&req)?;
+                self.accept_reply(opcode, &resp, true)?;
+                Ok(resp.data().to_vec())
+            }
+            SmdpVersion::V3Plus => {
+                let srlno = self.increment_srlno();
+                let req = CPacketSmdp::raw_v3(self.dev_addr, opcode, srlno, data);
+                self.smdp_handler.write_once(&req).map_err(Error::Smdp)?;
+                let resp = self.receive(&req)?;
+                self.accept_reply(opcode, &resp, resp.srlno() == srlno)?;
+                Ok(resp.data().to_vec())
+            }
+        }
+    }
+    /// Writes the request without waiting for a reply. Nothing confirms that
+    /// any device received it, so success only means the frame was sent.
+    fn send_broadcast(
+        &mut self,
+        req_type: RequestType,
+        hashval: u16,
+        array_idx: u8,
+    ) -> CResult<Option<u32>> {
+        let mut cpkt = CPacketSmdp::new(BROADCAST_ADDR, None, req_type, hashval, array_idx);
+        match self.version {
+            SmdpVersion::V2 => {
+                let req_smdp: SmdpPacketV2 = cpkt.into();
+                self.smdp_handler.write_once(&req_smdp)
+            }
+            SmdpVersion::V3Plus => {
+                cpkt.set_srlno(self.increment_srlno());
+                let req_smdp: SmdpPacketV3 = cpkt.try_into()?;
+                self.smdp_handler.write_once(&req_smdp)
+            }
+        }
+        .map_err(Error::Smdp)?;
+        Ok(None)
+    }
+    fn exchange(
+        &mut self,
+        req_type: RequestType,
+        hashval: u16,
+        array_idx: u8,
     ) -> CResult<Option<u32>> {
         let is_read = matches!(req_type, RequestType::Read);
         let mut cpkt = CPacketSmdp::new(self.dev_addr, None, req_type, hashval, array_idx);
 
-        // Write and read to/from wire and convert back into CPacketSmdp
-        let resp_cpkt: CPacketSmdp = match self.version {
+        // Write and read to/from wire, then extract data straight from the reply frame
+        match self.version {
             SmdpVersion::V2 => {
                 let req_smdp: SmdpPacketV2 = cpkt.into();
                 self.smdp_handler
                     .write_once(&req_smdp)
-                    .map_err(|e| Error::Smdp(e))?;
-                let resp_smdp: SmdpPacketV2 =
-                    self.smdp_handler.poll_once().map_err(|e| Error::Smdp(e))?;
-                match resp_smdp.rsp().map_err(|e| Error::Smdp(e))? {
-                    ResponseCode::Ok => resp_smdp.into(),
-                    other => return Err(Error::InvalidFormat(format!("RSP not OK: {:?}", other))),
-                }
+                    .map_err(Error::Smdp)?;
+                let resp_smdp = self.receive(&req_smdp)?;
+                self.accept_entry_reply(is_read, &resp_smdp, true)
             }
             SmdpVersion::V3Plus => {
                 cpkt.set_srlno(self.increment_srlno());
-                let req_smdp: SmdpPacketV3 = cpkt.try_into().expect("Just set srlno");
+                let req_smdp: SmdpPacketV3 = cpkt.try_into()?;
                 self.smdp_handler
                     .write_once(&req_smdp)
-                    .map_err(|e| Error::Smdp(e))?;
-                let resp_smdp: SmdpPacketV3 =
-                    self.smdp_handler.poll_once().map_err(|e| Error::Smdp(e))?;
-                if resp_smdp.srlno() != req_smdp.srlno() {
-                    return Err(Error::InvalidFormat("SRLNO mismatch".to_string()));
-                }
-                match resp_smdp.rsp().map_err(|e| Error::Smdp(e))? {
-                    ResponseCode::Ok => resp_smdp.into(),
-                    other => return Err(Error::InvalidFormat(format!("RSP not OK: {:?}", other))),
-                }
+                    .map_err(Error::Smdp)?;
+                let resp_smdp = self.receive(&req_smdp)?;
+                let srlno_matches = resp_smdp.srlno() == req_smdp.srlno();
+                self.accept_entry_reply(is_read, &resp_smdp, srlno_matches)
             }
-        };
-        // Extract data and return (if read-only).
-        if is_read {
-            resp_cpkt.extract_data().map(Some)
-        } else {
-            Ok(None)
         }
     }
 }
 
+/// True if a received frame is our own request echoed back by a half-duplex
+/// adapter. Replies always carry a response code in CMD_RSP, requests never do.
+fn is_echo(req: (u8, &[u8]), resp: (u8, &[u8])) -> bool {
+    req == resp
+}
+
 /* READ-ONLY METHODS */
 impl CryomechApiSmdp<Box<dyn SerialPort>> {
-    /// Firmware checksum
-    pub fn fw_checksum(&mut self) -> CResult<u32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x2B0D, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data)
-    }
-    /// True if nonvolatile memory was lost
-    pub fn mem_loss(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x801A, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
-    }
-    /// CPU temperature (°C)
-    pub fn cpu_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x3574, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// True if clock battery OK
-    pub fn clock_batt_ok(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0xA37A, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
-    }
-    /// True if clock battery low
-    pub fn clock_batt_low(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x0B8B, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
-    }
-    /// Elapsed compressor minutes
-    pub fn comp_minutes(&mut self) -> CResult<u32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x454C, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data)
-    }
-    /// Compressor motor current draw, in Amps
-    pub fn motor_current_amps(&mut self) -> CResult<u32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x638B, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data)
-    }
-    /// In °C
-    pub fn input_water_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x0D8F, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn output_water_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x0D8F, 0x01)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn helium_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x0D8F, 0x02)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn oil_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x0D8F, 0x03)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn min_input_water_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x6E58, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn min_output_water_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x6E58, 0x01)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn min_helium_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x6E58, 0x02)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn min_oil_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x6E58, 0x03)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn max_input_water_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x8A1C, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn max_output_water_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x8A1C, 0x01)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn max_helium_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x8A1C, 0x02)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In °C
-    pub fn max_oil_temp(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x8A1C, 0x03)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// True if a temperature sensor has failed
-    pub fn temp_sensor_fail(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x6E2D, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
-    }
-    /// True if a pressure sensor has failed
-    pub fn pressure_sensor_fail(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0xF82B, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
-    }
-    /// In PSI Absolute
-    pub fn high_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0xAA50, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn low_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0xAA50, 0x01)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn max_high_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x7A62, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn max_low_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x7A62, 0x01)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn min_high_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x5E0B, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn min_low_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x5E0B, 0x01)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn avg_high_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x7E90, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// In PSI Absolute
-    pub fn avg_low_side_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0xBB94, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// Also known as "bounce". In PSI Absolute
-    pub fn high_side_pressure_deriv(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x66FA, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// Average difference in High/Low side pressures in PSI Absolute.
-    pub fn avg_delta_pressure(&mut self) -> CResult<f32> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x319C, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data as f32 * 0.1)
-    }
-    /// True if the compressor is actively running
-    pub fn comp_on(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x5F95, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
-    }
-    /// True indicates one or more active errors or warnings.
-    pub fn err_code_status(&mut self) -> CResult<bool> {
-        let data =
-            self.comm_handler(RequestType::Read, 0x65A4, 0x00)?
-                .ok_or(Error::InvalidFormat(
-                    "Expected data in response, got none.".to_string(),
-                ))?;
-        Ok(data == 1)
+    /// Reads several array indices of one dictionary entry (e.g. the four
+    /// temperatures under 0x0D8F). Values are returned in the order of
+    /// `indices`. The requests are only batched with pipelining on SMDP V3+.
+    /// Otherwise each index is its own request/reply turnaround, exactly as if
+    /// it were read on its own, since the panel answers one request at a time.
+    pub fn read_indices(&mut self, hashval: u16, indices: &[u8]) -> CResult<Vec<u32>> {
+        if self.pipelining && self.version == SmdpVersion::V3Plus {
+            return self.pipelined_reads(hashval, indices);
+        }
+        let mut values = Vec::with_capacity(indices.len());
+        for &idx in indices {
+            let data = self
+                .comm_handler(RequestType::Read, hashval, idx)?
+                .ok_or(Error::InvalidFormat("Expected data in response, got none."))?;
+            values.push(data);
+        }
+        Ok(values)
+    }
+    /// Reads a register by name, scaled as by its dedicated getter. Flags read
+    /// as 0.0 or 1.0.
+    pub fn read(&mut self, register: Register) -> CResult<Real> {
+        let addr = register
+            .smdp()
+            .ok_or(Error::UnsupportedRegister(register))?;
+        let data = self
+            .comm_handler(RequestType::Read, addr.hashval, addr.array_idx)?
+            .ok_or(Error::InvalidFormat("Expected data in response, got none."))?;
+        Ok(data as Real * addr.scale)
+    }
+    /// True if the unit has the register or feature. Registers are probed with
+    /// one read the first time they are asked about; a NAK means unsupported.
+    /// Features depend on the backend.
+    pub fn supports(&mut self, capability: impl Into<Capability>) -> CResult<bool> {
+        match Backend::Smdp.supports(capability.into(), &self.probed) {
+            Ok(supported) => Ok(supported),
+            Err(register) => {
+                let read = self.read(register);
+                capability::record_probe(&mut self.probed, register, read)
+            }
+        }
+    }
+    /// Reads the given dictionary entries, each hash value as one group, and
+    /// returns the raw values with their scales by getter name.
+    fn read_entries(
+        &mut self,
+        entries: &[&'static DictionaryEntry],
+    ) -> CResult<HashMap<&'static str, (u32, Real)>> {
+        let mut raw = HashMap::with_capacity(entries.len());
+        for entry in entries {
+            if raw.contains_key(entry.name) {
+                continue;
+            }
+            let group: Vec<_> = entries
+                .iter()
+                .filter(|e| e.hashval == entry.hashval)
+                .collect();
+            let indices: Vec<u8> = group.iter().map(|e| e.array_idx).collect();
+            let start = self.clock.now();
+            let values = self.read_indices(entry.hashval, &indices)?;
+            self.note_field_latency(entry.hashval, start);
+            if values.len() != group.len() {
+                return Err(Error::InvalidFormat("Unexpected number of array values"));
+            }
+            raw.extend(
+                group
+                    .iter()
+                    .zip(values)
+                    .map(|(e, v)| (e.name, (v, e.scale))),
+            );
+        }
+        Ok(raw)
+    }
+    fn note_field_latency(&mut self, hashval: u16, start: Instant) {
+        let latency = self.elapsed(start);
+        if let Some(field_latency) = self.field_latency.as_mut() {
+            field_latency.insert(hashval, latency);
+        }
+    }
+    /// Reads one element of a dictionary entry, used by the generated getters.
+    pub(crate) fn read_entry(&mut self, hashval: u16, array_idx: u8) -> CResult<u32> {
+        self.comm_handler(RequestType::Read, hashval, array_idx)?
+            .ok_or(Error::InvalidFormat("Expected data in response, got none."))
+    }
+    /// Reads every read-only value in one pass, array entries as groups. With
+    /// pipelining on V3+ each group takes a single turnaround; otherwise this
+    /// makes the same transactions as calling each getter in turn.
+    pub fn read_all(&mut self) -> CResult<CompressorStatus> {
+        let entries: Vec<_> = dictionary()
+            .iter()
+            .filter(|e| e.hashval != FW_CHECKSUM_HASH)
+            .collect();
+        let raw = self.read_entries(&entries)?;
+        // Each field is filled from the dictionary entry of the same name
+        macro_rules! status {
+            ($($field:ident),* $(,)?) => {
+                CompressorStatus {$(
+                    $field: {
+                        let (value, scale) = raw
+                            .get(stringify!($field))
+                            .copied()
+                            .ok_or(Error::InvalidFormat("Status field missing from dictionary"))?;
+                        FromRaw::from_raw(value, scale)
+                    },
+                )*}
+            };
+        }
+        Ok(status! {
+            comp_on,
+            err_code_status,
+            mem_loss,
+            clock_batt_ok,
+            clock_batt_low,
+            temp_sensor_fail,
+            pressure_sensor_fail,
+            comp_minutes,
+            motor_current_amps,
+            cpu_temp,
+            input_water_temp,
+            output_water_temp,
+            helium_temp,
+            oil_temp,
+            min_input_water_temp,
+            min_output_water_temp,
+            min_helium_temp,
+            min_oil_temp,
+            max_input_water_temp,
+            max_output_water_temp,
+            max_helium_temp,
+            max_oil_temp,
+            high_side_pressure,
+            low_side_pressure,
+            max_high_side_pressure,
+            max_low_side_pressure,
+            min_high_side_pressure,
+            min_low_side_pressure,
+            avg_high_side_pressure,
+            avg_low_side_pressure,
+            high_side_pressure_deriv,
+            avg_delta_pressure,
+        })
+    }
+    /// Like `read_all()`, also returning where and how the snapshot was read.
+    /// The firmware checksum is read once per session; if the unit doesn't
+    /// answer it, it is retried with the next snapshot. Pass it to a poller,
+    /// e.g. `builder.spawn(api, |api| api.read_all_with_provenance())`, to
+    /// keep the record with every cached reading and sink snapshot.
+    pub fn read_all_with_provenance(&mut self) -> CResult<(CompressorStatus, Provenance)> {
+        let read_at = self.clock.system_time();
+        if self.firmware_checksum.is_none() {
+            self.firmware_checksum = self.read_entry(FW_CHECKSUM_HASH, 0x00).ok();
+        }
+        self.field_latency = Some(BTreeMap::new());
+        let status = self.read_all();
+        let field_latency = self.field_latency.take().unwrap_or_default();
+        let provenance = Provenance {
+            read_at,
+            labels: self.labels_arc(),
+            firmware_checksum: self.firmware_checksum,
+            model: self.model,
+            port: self.com_port.clone(),
+            dev_addr: self.dev_addr,
+            protocol: self.version.clone(),
+            crate_version: CRATE_VERSION,
+            field_latency,
+        };
+        Ok((status?, provenance))
+    }
+    /// Reads snapshots every `poll_interval` until `condition` holds, e.g.
+    /// `|s| s.helium_temp < 30.0` in a cooldown script. Failed reads are
+    /// skipped. Errs with `WaitTimeout` if the condition isn't met in time.
+    pub fn wait_until(
+        &mut self,
+        condition: impl Fn(&CompressorStatus) -> bool,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> CResult<CompressorStatus> {
+        let start = self.clock.now();
+        loop {
+            if let Ok(status) = self.read_all()
+                && condition(&status)
+            {
+                return Ok(status);
+            }
+            if self.elapsed(start) >= timeout {
+                return Err(Error::WaitTimeout(timeout));
+            }
+            self.clock
+                .sleep(poll_interval.min(timeout.saturating_sub(self.elapsed(start))));
+        }
     }
 }
 
@@ -426,20 +1318,332 @@ impl CryomechApiSmdp<Box<dyn SerialPort>> {
 impl CryomechApiSmdp<Box<dyn SerialPort>> {
     /// Clears the min/max values for both pressure and temp
     pub fn clear_press_temp_min_max(&mut self) -> CResult<()> {
-        let _ = self.comm_handler(RequestType::Write(0x0001), 0xD3DB, 0x00)?;
+        let _ = self.comm_handler(RequestType::Write(0x0001), CLEAR_MIN_MAX_HASH, 0x00)?;
+        Ok(())
+    }
+    /// Writes `value` to element `array_idx` of a dictionary entry, for
+    /// writable entries without a dedicated method (e.g. per-channel
+    /// settings). Write gates, auditing and cache invalidation apply as for
+    /// the built-in writes. Use index 0 for scalar entries.
+    pub fn write_register(&mut self, hashval: u16, array_idx: u8, value: u32) -> CResult<()> {
+        let _ = self.comm_handler(RequestType::Write(value), hashval, array_idx)?;
         Ok(())
     }
-    /// Activates the compressor. Returns true if verification successful.
-    pub fn start_compressor(&mut self) -> CResult<bool> {
-        let _ = self.comm_handler(RequestType::Write(0x0001), 0xD501, 0x00)?;
-        std::thread::sleep(Duration::from_secs(1));
-        self.comp_on()
+    /// Sends a write to every device on the bus at once, e.g. 0xD3DB to clear
+    /// the min/max values of all units. Write gates and auditing apply as for
+    /// `write_register()`. Devices don't acknowledge broadcasts, so an `Ok`
+    /// only means the frame went out; read back per device if it matters.
+    /// Refused with `fault_lockout`, which needs a fault flag read.
+    pub fn broadcast_write(&mut self, hashval: u16, array_idx: u8, value: u32) -> CResult<()> {
+        let addr = std::mem::replace(&mut self.dev_addr, BROADCAST_ADDR);
+        let result = self.write_register(hashval, array_idx, value);
+        self.dev_addr = addr;
+        result
+    }
+    /// Like `write_register()`, then confirms the write with the verifier
+    /// set for `hashval`, following the verify policy. Start and stop have
+    /// verifiers by default; other entries are `Unchecked` until one is set.
+    pub fn write_register_verified(
+        &mut self,
+        hashval: u16,
+        array_idx: u8,
+        value: u32,
+    ) -> CResult<ControlOutcome> {
+        self.write_register(hashval, array_idx, value)?;
+        let Some(verifier) = self.write_verifiers.get(hashval).cloned() else {
+            return Ok(ControlOutcome::Unchecked);
+        };
+        let (read_hash, read_idx) = verifier.read_target(array_idx);
+        self.verify_write(|api| {
+            let read = api.read_entry(read_hash, read_idx)?;
+            Ok(verifier.accepts(value, read))
+        })
+    }
+    /// Sets how writes to `hashval` are confirmed by
+    /// `write_register_verified()`, replacing any earlier verifier.
+    pub fn set_write_verifier(&mut self, hashval: u16, verifier: WriteVerifier) {
+        self.write_verifiers.insert(hashval, verifier);
+    }
+    /// Clears the min/max values and reads a fresh snapshot, so the returned
+    /// min/max fields start the new session.
+    pub fn reset_session_stats(&mut self) -> CResult<CompressorStatus> {
+        self.clear_press_temp_min_max()?;
+        self.read_all()
     }
-    /// Deactivates the compressor. Returns true if verification successful.
-    pub fn stop_compressor(&mut self) -> CResult<bool> {
-        let _ = self.comm_handler(RequestType::Write(0x0000), 0xC598, 0x00)?;
-        std::thread::sleep(Duration::from_secs(1));
-        self.comp_on().map(|b| !b)
+    /// Clears the min/max values, then starts the compressor through the
+    /// interlock check. Nothing is started if clearing fails.
+    pub fn prepare_for_run(&mut self) -> CResult<ControlOutcome> {
+        self.clear_press_temp_min_max()?;
+        self.start_compressor_checked()
+    }
+    /// Activates the compressor. Skips the write if it is already running.
+    pub fn start_compressor(&mut self) -> CResult<ControlOutcome> {
+        self.set_running(true)
+    }
+    /// Like `start_compressor()`, but first checks static pressure, water
+    /// temperature and sensor fault flags against the configured interlock
+    /// limits. Fails with `Error::Interlock` listing every failed check.
+    pub fn start_compressor_checked(&mut self) -> CResult<ControlOutcome> {
+        if self.comp_on()? {
+            return Ok(ControlOutcome::AlreadyInState);
+        }
+        let readings = InterlockReadings {
+            high_side_pressure: self.high_side_pressure()?,
+            low_side_pressure: self.low_side_pressure()?,
+            input_water_temp: self.input_water_temp()?,
+            temp_sensor_fail: self.temp_sensor_fail()?,
+            pressure_sensor_fail: self.pressure_sensor_fail()?,
+        };
+        let check = self.interlock.check(&readings);
+        match &check {
+            Ok(()) => tracing::info!(
+                device = self.device_name(),
+                high_side_pressure = readings.high_side_pressure,
+                low_side_pressure = readings.low_side_pressure,
+                input_water_temp = readings.input_water_temp,
+                "interlock passed"
+            ),
+            Err(violation) => tracing::warn!(
+                device = self.device_name(),
+                high_side_pressure = readings.high_side_pressure,
+                low_side_pressure = readings.low_side_pressure,
+                input_water_temp = readings.input_water_temp,
+                temp_sensor_fail = readings.temp_sensor_fail,
+                pressure_sensor_fail = readings.pressure_sensor_fail,
+                %violation,
+                "interlock refused start"
+            ),
+        }
+        check.map_err(Error::Interlock)?;
+        self.set_running(true)
+    }
+    /// Deactivates the compressor. Skips the write if it is already stopped.
+    pub fn stop_compressor(&mut self) -> CResult<ControlOutcome> {
+        self.set_running(false)
+    }
+    /// Sends the stop write several times back-to-back and returns without
+    /// checking the run state or verifying, for E-stop chains where latency
+    /// matters. Write arming and control tokens don't apply. Succeeds if any
+    /// write was acknowledged, otherwise returns the last error.
+    pub fn emergency_stop(&mut self) -> CResult<()> {
+        tracing::warn!(device = self.device_name(), "emergency stop");
+        let mut acked = false;
+        let mut last_err = None;
+        for _ in 0..EMERGENCY_STOP_REPEATS {
+            match self.send_stop() {
+                Ok(()) => acked = true,
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match last_err {
+            Some(e) if !acked => Err(e),
+            _ => Ok(()),
+        }
+    }
+    /// Stops the compressor, then watches for the pressures to equalize and the
+    /// motor current to decay for up to `check.window`. A report is returned
+    /// either way; `completed` tells whether the shutdown looked normal.
+    pub fn stop_and_verify(&mut self, check: &ShutdownCheck) -> CResult<ShutdownReport> {
+        let outcome = self.stop_compressor()?;
+        let start = self.clock.now();
+        loop {
+            let delta_pressure = self.high_side_pressure()? - self.low_side_pressure()?;
+            let motor_current = self.motor_current_amps()?;
+            let completed = check.settled(delta_pressure, motor_current);
+            if completed || self.elapsed(start) >= check.window {
+                tracing::info!(
+                    device = self.device_name(),
+                    completed,
+                    delta_pressure,
+                    motor_current,
+                    "shutdown verification finished"
+                );
+                return Ok(ShutdownReport {
+                    outcome,
+                    completed,
+                    delta_pressure,
+                    motor_current,
+                    elapsed: self.elapsed(start),
+                });
+            }
+            self.clock.sleep(
+                check
+                    .interval
+                    .min(check.window.saturating_sub(self.elapsed(start))),
+            );
+        }
+    }
+    /// Starts the compressor and waits up to `check.timeout` for it to reach
+    /// its run ranges. If it doesn't, a stop is sent and the report lists the
+    /// criteria that were not met. A compressor that was already running is
+    /// checked but never stopped. A failed read counts as a failed start: the
+    /// stop is sent and the read error returned.
+    pub fn start_transaction(&mut self, check: &StartCheck) -> CResult<StartReport> {
+        let outcome = self.start_compressor()?;
+        let start = self.clock.now();
+        loop {
+            let (running, delta_pressure, motor_current) = match self.start_readings() {
+                Ok(readings) => readings,
+                Err(e) => {
+                    if outcome != ControlOutcome::AlreadyInState {
+                        tracing::warn!(
+                            device = self.device_name(),
+                            error = %e,
+                            "stopping after failed start verification read"
+                        );
+                        let _ = self.send_stop();
+                    }
+                    return Err(e);
+                }
+            };
+            let failures = check.failures(running, delta_pressure, motor_current);
+            if failures.is_empty() || self.elapsed(start) >= check.timeout {
+                let rolled_back = !failures.is_empty() && outcome != ControlOutcome::AlreadyInState;
+                tracing::info!(
+                    device = self.device_name(),
+                    running,
+                    delta_pressure,
+                    motor_current,
+                    failures = ?failures,
+                    rolled_back,
+                    "start verification finished"
+                );
+                if rolled_back {
+                    tracing::warn!(device = self.device_name(), "stopping after failed start");
+                    self.send_stop()?;
+                }
+                return Ok(StartReport {
+                    outcome,
+                    failures,
+                    rolled_back,
+                    elapsed: self.elapsed(start),
+                });
+            }
+            self.clock.sleep(
+                check
+                    .interval
+                    .min(check.timeout.saturating_sub(self.elapsed(start))),
+            );
+        }
+    }
+    /// Run state, high/low side pressure difference and motor current, as
+    /// checked by `start_transaction()`.
+    fn start_readings(&mut self) -> CResult<(bool, Real, u32)> {
+        let running = self.comp_on()?;
+        let delta_pressure = self.high_side_pressure()? - self.low_side_pressure()?;
+        Ok((running, delta_pressure, self.motor_current_amps()?))
+    }
+    /// Sends a non-dictionary command with the given opcode (upper nibble of
+    /// CMD_RSP, e.g. 0x90) and raw payload, returning the raw reply payload.
+    /// Bypasses the read cache and register-level checks. Since the effect of
+    /// the command is unknown it is treated as a write: gated, audited and
+    /// followed by dropping all cached values.
+    pub fn raw_command(&mut self, opcode: u8, data: &[u8]) -> CResult<Vec<u8>> {
+        CPacketSmdp::check_raw_opcode(opcode)?;
+        let action = AuditAction::RawCommand {
+            opcode,
+            data: data.to_vec(),
+        };
+        self.dispatch(Some(action), 0, 0, |api| {
+            api.retry_exchange(None, |api| api.exchange_raw(opcode, data))
+        })
+    }
+    /// Reads a dictionary entry whose reply carries more than one word, or
+    /// ASCII data if `text` is set. Not cached.
+    pub fn read_response(
+        &mut self,
+        hashval: u16,
+        array_idx: u8,
+        text: bool,
+    ) -> CResult<ResponseData> {
+        let (opcode, data) = CPacketSmdp::read_request(hashval, array_idx);
+        self.dispatch(None, hashval, array_idx, |api| {
+            api.retry_exchange(Some(hashval), |api| api.exchange_raw(opcode, &data))
+                .and_then(|reply| CPacketSmdp::extract_response(&reply, text))
+        })
+    }
+    /// Reads an ASCII dictionary entry that may be split across successive
+    /// array indices, stopping at an empty segment, at the first index the
+    /// panel rejects, or after `max_segments`.
+    pub fn read_string_register(&mut self, hashval: u16, max_segments: u8) -> CResult<String> {
+        let mut text = String::new();
+        for idx in 0..max_segments {
+            let segment = match self.read_response(hashval, idx, true) {
+                Ok(segment) => segment,
+                // Past the last index of the entry
+                Err(e) if e.is_nak() && idx > 0 => break,
+                Err(e) => return Err(e),
+            };
+            match segment.text() {
+                Some(s) if !s.is_empty() => text.push_str(s),
+                _ => break,
+            }
+        }
+        Ok(text)
+    }
+    /// Runs `f` with the write gates (arming, control token, fault lockout)
+    /// lifted, for stops the crate sends on its own behalf.
+    fn ungated<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.bypass_write_gates = true;
+        let result = f(self);
+        self.bypass_write_gates = false;
+        result
+    }
+    /// Sends the stop write once, with no run state check, verification or
+    /// write gates.
+    pub(crate) fn send_stop(&mut self) -> CResult<()> {
+        self.ungated(|api| api.comm_handler(RequestType::Write(0x0000), STOP_HASH, 0x00))
+            .map(|_| ())
+    }
+    /// Like `stop_compressor()`, without write gates, for scheduled stops.
+    pub(crate) fn stop_compressor_ungated(&mut self) -> CResult<ControlOutcome> {
+        self.ungated(Self::stop_compressor)
+    }
+    /// Reads the run state first and only writes the start/stop register if
+    /// the compressor isn't already in the requested state.
+    fn set_running(&mut self, on: bool) -> CResult<ControlOutcome> {
+        // Refused before the state read, so an unarmed call fails whether or
+        // not the compressor is already in the requested state
+        if let Err(e) = self.access_gate() {
+            let (hashval, value) = if on { (START_HASH, 1) } else { (STOP_HASH, 0) };
+            let action = AuditAction::Write {
+                hashval,
+                array_idx: 0,
+                value,
+            };
+            let result = Err(e);
+            self.audit(action, &result);
+            return result;
+        }
+        if self.comp_on()? == on {
+            tracing::debug!(
+                device = self.device_name(),
+                on,
+                "already in requested state"
+            );
+            return Ok(ControlOutcome::AlreadyInState);
+        }
+        if on {
+            let _ = self.comm_handler(RequestType::Write(0x0001), START_HASH, 0x00)?;
+        } else {
+            let _ = self.comm_handler(RequestType::Write(0x0000), STOP_HASH, 0x00)?;
+        }
+        let outcome = self.verify_write(|api| api.comp_on().map(|b| b == on))?;
+        if outcome == ControlOutcome::NotVerified {
+            tracing::warn!(
+                device = self.device_name(),
+                on,
+                "run state change not verified"
+            );
+        } else {
+            tracing::info!(
+                device = self.device_name(),
+                on,
+                ?outcome,
+                "run state changed"
+            );
+        }
+        Ok(outcome)
     }
 }
 
@@ -451,8 +1655,36 @@ pub struct CryomechApiSmdpBuilder {
     dev_addr: u8,
     max_framesize: usize,
     version: SmdpVersion,
+    reconnect_attempts: u32,
+    reconnect_delay: Duration,
+    validate_response: bool,
+    suppress_echo: bool,
+    verify_policy: VerifyPolicy,
+    capture_frames: bool,
+    extra_frames: ExtraFramePolicy,
+    read_cache: Option<ReadCache>,
+    pipelining: bool,
+    rx_polling: RxPolling,
+    interlock: InterlockLimits,
+    require_arming: bool,
+    audit: Option<Audit>,
+    on_drop: OnDrop,
+    require_token: bool,
+    fault_lockout: bool,
+    model: Option<CompressorModel>,
+    clock: Arc<dyn Clock>,
+    labels: DeviceLabels,
+    auto_timeout: Option<AutoTimeout>,
+    link_dead_after: Option<u32>,
+    corrupt_frame_retries: u32,
+    write_verifiers: WriteVerifiers,
+    reboot_detection: bool,
 }
 impl CryomechApiSmdpBuilder {
+    /// Builder for the port whose USB adapter matches `usb`, see `discovery`.
+    pub fn from_usb(usb: &UsbMatch) -> CResult<Self> {
+        Ok(Self::new(&discovery::find_port(usb)?))
+    }
     pub fn new(com_port: &str) -> Self {
         Self {
             read_timeout: 80,
@@ -461,8 +1693,38 @@ impl CryomechApiSmdpBuilder {
             dev_addr: 0x10,
             max_framesize: 64,
             version: SmdpVersion::V2,
+            reconnect_attempts: 3,
+            reconnect_delay: Duration::from_millis(500),
+            validate_response: true,
+            suppress_echo: false,
+            verify_policy: VerifyPolicy::default(),
+            capture_frames: false,
+            extra_frames: ExtraFramePolicy::default(),
+            read_cache: None,
+            pipelining: false,
+            rx_polling: RxPolling::default(),
+            interlock: InterlockLimits::default(),
+            require_arming: false,
+            audit: None,
+            on_drop: OnDrop::default(),
+            require_token: false,
+            fault_lockout: false,
+            model: None,
+            clock: Arc::new(SystemClock),
+            labels: DeviceLabels::default(),
+            auto_timeout: None,
+            link_dead_after: None,
+            corrupt_frame_retries: 2,
+            write_verifiers: WriteVerifiers::default(),
+            reboot_detection: true,
         }
     }
+    /// Records the compressor model, reported by `model()` and in snapshot
+    /// provenance. It doesn't change any settings of the handle.
+    pub fn for_model(mut self, model: CompressorModel) -> Self {
+        self.model = Some(model);
+        self
+    }
     pub fn read_timeout_ms(mut self, timeout: usize) -> Self {
         self.read_timeout = timeout;
         self
@@ -483,14 +1745,292 @@ impl CryomechApiSmdpBuilder {
         self.max_framesize = size;
         self
     }
+    /// Number of times to try reopening the port after the device disappears.
+    /// Zero disables automatic reconnection.
+    pub fn reconnect_attempts(mut self, attempts: u32) -> Self {
+        self.reconnect_attempts = attempts;
+        self
+    }
+    /// Delay before each reconnection attempt.
+    pub fn reconnect_delay_ms(mut self, delay: u64) -> Self {
+        self.reconnect_delay = Duration::from_millis(delay);
+        self
+    }
+    /// Whether replies must carry the device address and Cryomech opcode.
+    /// Disable for adapters that rewrite frame headers.
+    pub fn validate_response(mut self, validate: bool) -> Self {
+        self.validate_response = validate;
+        self
+    }
+    /// Discards our own transmitted frame when an RS-485 adapter echoes it back
+    /// ahead of the device reply.
+    pub fn echo_suppression(mut self, suppress: bool) -> Self {
+        self.suppress_echo = suppress;
+        self
+    }
+    /// How start/stop and other verified writes confirm the new state.
+    pub fn verify_policy(mut self, policy: VerifyPolicy) -> Self {
+        self.verify_policy = policy;
+        self
+    }
+    /// Attach a hex dump of the raw request/response bytes to protocol errors.
+    pub fn capture_frames(mut self, capture: bool) -> Self {
+        self.capture_frames = capture;
+        self
+    }
+    /// Handling of duplicate/unexpected frames following a reply.
+    pub fn extra_frames(mut self, policy: ExtraFramePolicy) -> Self {
+        self.extra_frames = policy;
+        self
+    }
+    /// Serve slowly-changing registers from a cache. By default only the firmware
+    /// checksum (forever) and compressor minutes (30 s) are cached.
+    pub fn read_cache(mut self, enable: bool) -> Self {
+        self.read_cache = enable.then(ReadCache::default);
+        self
+    }
+    /// Sets the cache lifetime for a dictionary entry, enabling the cache.
+    pub fn cache_ttl(mut self, hashval: u16, ttl: CacheTtl) -> Self {
+        self.read_cache
+            .get_or_insert_with(ReadCache::default)
+            .set_ttl(hashval, ttl);
+        self
+    }
+    /// Send all requests of a batched read before reading the replies. Only
+    /// used with SMDP V3+, where replies can be matched by SRLNO.
+    pub fn pipelining(mut self, enable: bool) -> Self {
+        self.pipelining = enable;
+        self
+    }
+    /// How reads wait on the serial port. `Spin` trades CPU for the lowest reply
+    /// latency, which matters most with short read timeouts.
+    pub fn rx_polling(mut self, polling: RxPolling) -> Self {
+        self.rx_polling = polling;
+        self
+    }
+    /// Limits checked by `start_compressor_checked()`.
+    pub fn interlock_limits(mut self, limits: InterlockLimits) -> Self {
+        self.interlock = limits;
+        self
+    }
+    /// Refuse all writes unless `arm_writes()` was called shortly before.
+    /// Guards interactive sessions against accidental start/stop calls.
+    pub fn armed_writes(mut self, require: bool) -> Self {
+        self.require_arming = require;
+        self
+    }
+    /// Record every write, including refused and failed ones, to `sink`.
+    pub fn audit_sink(mut self, sink: impl AuditSink + 'static) -> Self {
+        self.audit = Some(Audit(Box::new(sink)));
+        self
+    }
+    /// Require writes to go through `control()` with the handle's
+    /// `ControlToken`, so read access can be shared without control access.
+    pub fn control_token(mut self, require: bool) -> Self {
+        self.require_token = require;
+        self
+    }
+    /// Refuse every write except stop while `err_code_status()` reports an
+    /// active fault, so automation doesn't fight the panel's own protection.
+    /// Adds a status read before each write.
+    pub fn fault_lockout(mut self, enable: bool) -> Self {
+        self.fault_lockout = enable;
+        self
+    }
+    /// Time source for waits and timeouts, e.g. a `MockClock` in tests.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Metadata identifying the compressor in audit entries and alerts.
+    pub fn labels(mut self, labels: DeviceLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+    /// Tune the read timeout from observed response times within `bounds`,
+    /// starting from `read_timeout_ms`. Lets one setting cover both panels
+    /// that answer in a few ms and slow serial servers.
+    pub fn auto_timeout(mut self, bounds: AutoTimeout) -> Self {
+        self.auto_timeout = Some(bounds);
+        self
+    }
+    /// Report `Error::LinkDead` once `transactions` in a row have failed
+    /// without receiving a single byte, so an unplugged cable or dead adapter
+    /// can be told apart from a compressor that is merely off or faulted.
+    pub fn link_dead_after(mut self, transactions: u32) -> Self {
+        self.link_dead_after = Some(transactions.max(1));
+        self
+    }
+    /// How many times a request is resent when its reply arrives but is
+    /// rejected by the smdp layer, e.g. on a checksum failure. Defaults to 2.
+    pub fn corrupt_frame_retries(mut self, retries: u32) -> Self {
+        self.corrupt_frame_retries = retries;
+        self
+    }
+    /// Confirms writes to `hashval` in `write_register_verified()`, one
+    /// verifier per entry. Start and stop are verified by default.
+    pub fn write_verifier(mut self, hashval: u16, verifier: WriteVerifier) -> Self {
+        self.write_verifiers.insert(hashval, verifier);
+        self
+    }
+    /// Watch for the signature of a panel reboot (SRLNO mismatches, NAKs for
+    /// entries that were answered before) and restart the session when it
+    /// shows, see `last_reboot()`. On by default.
+    pub fn reboot_detection(mut self, detect: bool) -> Self {
+        self.reboot_detection = detect;
+        self
+    }
+    /// Action taken when a handle from `build_guarded()` is dropped.
+    pub fn on_drop(mut self, action: OnDrop) -> Self {
+        self.on_drop = action;
+        self
+    }
+    /// Builds the handle wrapped in a guard that applies the `on_drop` action.
+    pub fn build_guarded(self) -> CResult<ApiGuard> {
+        let on_drop = self.on_drop;
+        Ok(ApiGuard::new(self.build()?, on_drop))
+    }
     pub fn build(self) -> CResult<CryomechApiSmdp<Box<dyn SerialPort>>> {
-        CryomechApiSmdp::new(
+        let link = Arc::new(LinkState::default());
+        let io = link::open_port(
             &self.com_port,
             self.baud,
+            self.rx_polling.port_timeout(),
+            &link,
+        )?;
+        Ok(self.build_with(io, link))
+    }
+    /// Builds the handle on a port that is already open, e.g. a simulated one.
+    pub(crate) fn build_on(self, io: Box<dyn SerialPort>) -> CryomechApiSmdp<Box<dyn SerialPort>> {
+        self.build_with(io, Arc::default())
+    }
+    fn build_with(
+        self,
+        io: Box<dyn SerialPort>,
+        link: Arc<LinkState>,
+    ) -> CryomechApiSmdp<Box<dyn SerialPort>> {
+        let mut api = CryomechApiSmdp::on_port(
+            io,
+            link,
+            &self.com_port,
             self.read_timeout,
-            self.dev_addr,
             self.max_framesize,
-            self.version,
-        )
+            self.auto_timeout.is_some(),
+        );
+        api.baud = self.baud;
+        api.dev_addr = self.dev_addr;
+        api.version = self.version.clone();
+        api.rx_polling = self.rx_polling;
+        self.configure(api)
+    }
+    fn configure(
+        self,
+        mut api: CryomechApiSmdp<Box<dyn SerialPort>>,
+    ) -> CryomechApiSmdp<Box<dyn SerialPort>> {
+        api.reconnect_attempts = self.reconnect_attempts;
+        api.reconnect_delay = self.reconnect_delay;
+        api.validate_response = self.validate_response;
+        api.suppress_echo = self.suppress_echo;
+        api.verify_policy = self.verify_policy;
+        api.link.set_capture(self.capture_frames);
+        api.extra_frames = self.extra_frames;
+        api.read_cache = self.read_cache;
+        api.pipelining = self.pipelining;
+        api.interlock = self.interlock;
+        api.require_arming = self.require_arming;
+        api.audit = self.audit;
+        api.require_token = self.require_token;
+        api.fault_lockout = self.fault_lockout;
+        api.model = self.model;
+        api.clock = self.clock;
+        api.labels = Arc::new(self.labels);
+        api.link_dead_after = self.link_dead_after;
+        api.corrupt_frame_retries = self.corrupt_frame_retries;
+        api.write_verifiers = self.write_verifiers;
+        if !self.reboot_detection {
+            api.reboot_detector = None;
+        }
+        if let Some(bounds) = self.auto_timeout {
+            let tuner = TimeoutTuner::new(bounds, Duration::from_millis(api.read_timeout as u64));
+            api.set_handler_timeout(tuner.current());
+            api.timeout_tuner = Some(tuner);
+        }
+        api
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{clock::MockClock, link::test::NullPort};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_cache_ttl_follows_clock() {
+        let clock = Arc::new(MockClock::default());
+        let mut api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .cache_ttl(0xAA50, CacheTtl::For(Duration::from_secs(10)))
+            .clock(Arc::clone(&clock) as Arc<dyn Clock>)
+            .build_on(NullPort::boxed());
+        if let Some(cache) = api.read_cache.as_mut() {
+            cache.insert(0xAA50, 0, 1500, clock.now());
+        }
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(api.high_side_pressure().unwrap(), 150.0);
+        assert_eq!(api.diagnostics().transactions, 0);
+        // Expired on the mock clock, so the read goes to the device
+        clock.advance(Duration::from_secs(1));
+        assert!(api.high_side_pressure().is_err());
+        assert_eq!(api.diagnostics().transactions, 1);
+    }
+    #[test]
+    fn test_spare_port_only_with_auto_timeout() {
+        let builder = || CryomechApiSmdpBuilder::new("sim").read_timeout_ms(1);
+        assert!(builder().build_on(NullPort::boxed()).spare_port.is_none());
+        let api = builder()
+            .auto_timeout(AutoTimeout::default())
+            .build_on(NullPort::boxed());
+        assert!(api.spare_port.is_some());
+    }
+    #[test]
+    fn test_raw_command_is_audited() {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&entries);
+        let mut api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .armed_writes(true)
+            .audit_sink(move |entry: &AuditEntry| sink.lock().unwrap().push(entry.clone()))
+            .build_on(NullPort::boxed());
+        assert!(matches!(
+            api.raw_command(0x90, &[0x01]),
+            Err(Error::WritesNotArmed)
+        ));
+        api.arm_writes(Duration::from_secs(60));
+        assert!(api.raw_command(0x90, &[0x01]).is_err());
+        assert_eq!(api.diagnostics().transactions, 1);
+        let entries = entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.action
+            == AuditAction::RawCommand {
+                opcode: 0x90,
+                data: vec![0x01],
+            }
+            && e.error.is_some()));
+    }
+    #[test]
+    fn test_is_echo() {
+        let request = [0x63, 0x0D, 0x8F, 0x02];
+        assert!(is_echo((0x80, &request), (0x80, &request)));
+        // The reply to a read carries a response code and the value
+        let reply = [0x63, 0x0D, 0x8F, 0x02, 0x00, 0x00, 0x01, 0x2C];
+        assert!(!is_echo((0x80, &request), (0x81, &reply)));
+        // Same opcode and response code as the request, other entry
+        assert!(!is_echo(
+            (0x80, &request),
+            (0x80, &[0x63, 0x0D, 0x8F, 0x03])
+        ));
     }
 }
@@ -0,0 +1,54 @@
+/* Audit trail of writes, raw commands and maintenance periods for operations traceability */
+
+use crate::labels::DeviceLabels;
+use std::{fmt, sync::Arc, time::SystemTime};
+
+/// What an audited call tried to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditAction {
+    /// Write to a dictionary entry.
+    Write {
+        hashval: u16,
+        array_idx: u8,
+        value: u32,
+    },
+    /// Non-dictionary command sent with `raw_command()`.
+    RawCommand { opcode: u8, data: Vec<u8> },
+    /// Alarm maintenance mode switched with `AlarmBoard::maintenance_mode()`.
+    /// The given reason is the entry's `reason`.
+    Maintenance { on: bool },
+}
+
+/// One write or raw command attempted through the API, or a maintenance
+/// mode change on an alarm board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    pub device_addr: u8,
+    pub action: AuditAction,
+    /// Rendered error if the call failed or was refused.
+    pub error: Option<String>,
+    /// Reason given with `audit_reason()` before the write, if any.
+    pub reason: Option<String>,
+    /// Labels of the handle that made the write.
+    pub labels: Arc<DeviceLabels>,
+}
+
+/// Destination for audit entries, e.g. a log file or a database. Closures
+/// taking `&AuditEntry` implement this trait.
+pub trait AuditSink: Send {
+    fn record(&mut self, entry: &AuditEntry);
+}
+impl<F: FnMut(&AuditEntry) + Send> AuditSink for F {
+    fn record(&mut self, entry: &AuditEntry) {
+        self(entry)
+    }
+}
+
+/// Sink holder so the API handle can keep deriving Debug.
+pub(crate) struct Audit(pub(crate) Box<dyn AuditSink>);
+impl fmt::Debug for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Audit(..)")
+    }
+}
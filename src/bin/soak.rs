@@ -0,0 +1,121 @@
+//! Long-running reliability run against a real compressor. Polls `read_all()`
+//! and single registers back to back for hours and prints error rates, reply
+//! latencies and resident memory once a minute.
+//!
+//! `CRYOMECH_PORT=/dev/ttyUSB0 [CRYOMECH_SMDP_V3=1] [SOAK_HOURS=8] [SOAK_WRITES=1]
+//! cargo run --release --features dev-tool --bin soak`
+//!
+//! With SOAK_WRITES set, the min/max registers are cleared every 100 cycles
+//! to exercise the write path. Start/stop is never written.
+
+use cryomech_api::{CryomechApiSmdpBuilder, Register, SmdpVersion};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+const REPORT_EVERY: Duration = Duration::from_secs(60);
+const WRITE_EVERY: u64 = 100;
+const SINGLE_READS: [Register; 4] = [
+    Register::HeliumTemp,
+    Register::HighSidePressure,
+    Register::LowSidePressure,
+    Register::MotorCurrent,
+];
+
+#[derive(Default)]
+struct Tally {
+    operations: u64,
+    failed: u64,
+    errors: BTreeMap<String, u64>,
+}
+impl Tally {
+    fn record<T>(&mut self, result: Result<T, cryomech_api::Error>) {
+        self.operations += 1;
+        if let Err(e) = result {
+            self.failed += 1;
+            *self.errors.entry(e.to_string()).or_default() += 1;
+        }
+    }
+    fn error_rate(&self) -> f64 {
+        if self.operations == 0 {
+            return 0.0;
+        }
+        self.failed as f64 / self.operations as f64
+    }
+}
+
+/// Resident set size in kB, Linux only.
+fn rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn fmt_ms(d: Option<Duration>) -> String {
+    d.map_or("-".into(), |d| format!("{:.1}", d.as_secs_f64() * 1e3))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let port = std::env::var("CRYOMECH_PORT").map_err(|_| "CRYOMECH_PORT not set")?;
+    let version = if std::env::var("CRYOMECH_SMDP_V3").is_ok() {
+        SmdpVersion::V3Plus
+    } else {
+        SmdpVersion::V2
+    };
+    let hours: f64 = match std::env::var("SOAK_HOURS") {
+        Ok(h) => h.parse()?,
+        Err(_) => 8.0,
+    };
+    let writes = std::env::var("SOAK_WRITES").is_ok();
+    let mut api = CryomechApiSmdpBuilder::new(&port)
+        .version(version)
+        .build()?;
+
+    let start = Instant::now();
+    let end = start + Duration::from_secs_f64(hours * 3600.0);
+    let start_rss = rss_kb();
+    let mut tally = Tally::default();
+    let mut cycles: u64 = 0;
+    let mut next_report = start + REPORT_EVERY;
+    println!("elapsed_s,cycles,operations,error_rate,p50_ms,p99_ms,max_ms,rss_kb");
+    while Instant::now() < end {
+        cycles += 1;
+        tally.record(api.read_all());
+        let register = SINGLE_READS[(cycles % SINGLE_READS.len() as u64) as usize];
+        tally.record(api.read(register));
+        if writes && cycles.is_multiple_of(WRITE_EVERY) {
+            tally.record(api.clear_press_temp_min_max());
+        }
+        if Instant::now() >= next_report {
+            let latency = api.link_stats().overall();
+            println!(
+                "{},{},{},{:.5},{},{},{},{}",
+                start.elapsed().as_secs(),
+                cycles,
+                tally.operations,
+                tally.error_rate(),
+                fmt_ms(latency.quantile(0.5)),
+                fmt_ms(latency.quantile(0.99)),
+                fmt_ms(Some(latency.max())),
+                rss_kb().map_or("-".into(), |kb| kb.to_string()),
+            );
+            next_report += REPORT_EVERY;
+        }
+    }
+
+    println!();
+    println!(
+        "{cycles} cycles, {} operations, {} failed ({:.3}%)",
+        tally.operations,
+        tally.failed,
+        tally.error_rate() * 100.0
+    );
+    for (message, count) in &tally.errors {
+        println!("  {count:>8}  {message}");
+    }
+    if let (Some(before), Some(after)) = (start_rss, rss_kb()) {
+        println!("RSS {before} kB -> {after} kB");
+    }
+    Ok(())
+}
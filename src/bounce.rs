@@ -0,0 +1,103 @@
+/* Rolling statistics of the high side pressure bounce, for cold head wear trends */
+
+use crate::{
+    Real,
+    history::{History, HistoryKind},
+};
+use std::{
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+/// Bounce (`high_side_pressure_deriv`) statistics over one window, from
+/// snapshots taken while the compressor was running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BounceWindow {
+    pub start: SystemTime,
+    /// Root mean square of the bounce, in PSI.
+    pub rms: Real,
+    /// Largest absolute bounce, in PSI.
+    pub peak: Real,
+    pub samples: usize,
+}
+
+impl History {
+    /// Bounce statistics of each consecutive `window` in `range`. Windows with
+    /// no running snapshots are skipped.
+    pub fn bounce_stats(&self, range: Range<SystemTime>, window: Duration) -> Vec<BounceWindow> {
+        let mut out: Vec<BounceWindow> = Vec::new();
+        if window.is_zero() {
+            return out;
+        }
+        let mut sum_sq = 0.0;
+        for entry in self
+            .entries()
+            .filter(|e| e.kind == HistoryKind::Poll && e.status.comp_on && range.contains(&e.at))
+        {
+            let offset = entry.at.duration_since(range.start).unwrap_or_default();
+            let start = range.start + window * (offset.as_secs_f64() / window.as_secs_f64()) as u32;
+            let bounce = entry.status.high_side_pressure_deriv;
+            match out.last_mut() {
+                Some(w) if w.start == start => {
+                    w.peak = w.peak.max(bounce.abs());
+                    w.samples += 1;
+                }
+                _ => {
+                    if let Some(prev) = out.last_mut() {
+                        prev.rms = (sum_sq / prev.samples as Real).sqrt();
+                    }
+                    sum_sq = 0.0;
+                    out.push(BounceWindow {
+                        start,
+                        rms: 0.0,
+                        peak: bounce.abs(),
+                        samples: 1,
+                    });
+                }
+            }
+            sum_sq += bounce * bounce;
+        }
+        if let Some(last) = out.last_mut() {
+            last.rms = (sum_sq / last.samples as Real).sqrt();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        history::{HistoryEntry, test::status},
+        status::CompressorStatus,
+    };
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_bounce_windows() {
+        let mut history = History::new(16);
+        for (minute, comp_on, bounce) in [
+            (0, true, 3.0),
+            (1, true, -4.0),
+            (2, false, 50.0),
+            (11, true, 1.0),
+        ] {
+            history.push(HistoryEntry {
+                at: UNIX_EPOCH + Duration::from_secs(minute * 60),
+                kind: HistoryKind::Poll,
+                status: CompressorStatus {
+                    comp_on,
+                    high_side_pressure_deriv: bounce,
+                    ..status(0.0)
+                },
+            });
+        }
+        let range = UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(3600);
+        let windows = history.bounce_stats(range, Duration::from_secs(600));
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].peak, 4.0);
+        assert!((windows[0].rms - (12.5 as Real).sqrt()).abs() < 1e-4);
+        assert_eq!(windows[1].start, UNIX_EPOCH + Duration::from_secs(600));
+        assert_eq!(windows[1].samples, 1);
+    }
+}
@@ -0,0 +1,390 @@
+/* Several devices multi-dropped on one serial line, served by a single worker thread */
+
+use crate::{CResult, ControlOutcome, Error, Real, api::CryomechApiSmdp, core::register::Register};
+use serialport::SerialPort;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+type Job = Box<dyn FnOnce(&mut SmdpApi) + Send>;
+
+// Identical register reads within this window share one transaction
+const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(50);
+
+enum Work {
+    Job(Job),
+    /// Single register read, which can be coalesced with identical ones.
+    Register(Register, Sender<CResult<Real>>),
+}
+
+/// Request classes, served in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lane {
+    /// Start/stop and interlock-checked starts, ahead of everything else.
+    Control,
+    Write,
+    Read,
+}
+impl Lane {
+    const ALL: [Lane; 3] = [Lane::Control, Lane::Write, Lane::Read];
+}
+
+struct Request {
+    addr: u8,
+    lane: Lane,
+    work: Work,
+}
+
+enum Message {
+    Request(Request),
+    /// Serve what is queued, then hand the API handle back.
+    Shutdown,
+}
+
+/// Pending jobs per device address. Control commands are served first, then
+/// writes, then reads, and within each lane devices take turns so a busy
+/// device can't starve the others.
+#[derive(Debug)]
+struct FairQueue<J> {
+    queues: BTreeMap<u8, [VecDeque<J>; 3]>,
+    // Address served last, the next turn goes to the following address
+    last: Option<u8>,
+}
+impl<J> Default for FairQueue<J> {
+    fn default() -> Self {
+        Self {
+            queues: BTreeMap::new(),
+            last: None,
+        }
+    }
+}
+impl<J> FairQueue<J> {
+    fn push(&mut self, addr: u8, lane: Lane, job: J) {
+        self.queues.entry(addr).or_default()[lane as usize].push_back(job);
+    }
+    fn is_empty(&self) -> bool {
+        self.queues.values().flatten().all(VecDeque::is_empty)
+    }
+    /// Removes the queued reads for `addr` that match `pred`.
+    fn take_reads(&mut self, addr: u8, mut pred: impl FnMut(&J) -> bool) -> Vec<J> {
+        let Some(reads) = self
+            .queues
+            .get_mut(&addr)
+            .map(|q| &mut q[Lane::Read as usize])
+        else {
+            return Vec::new();
+        };
+        let mut matched = Vec::new();
+        for job in std::mem::take(reads) {
+            if pred(&job) {
+                matched.push(job);
+            } else {
+                reads.push_back(job);
+            }
+        }
+        matched
+    }
+    fn pop(&mut self) -> Option<J> {
+        let lane = Lane::ALL
+            .into_iter()
+            .find(|lane| self.queues.values().any(|q| !q[*lane as usize].is_empty()))?
+            as usize;
+        let ready: Vec<u8> = self
+            .queues
+            .iter()
+            .filter(|(_, q)| !q[lane].is_empty())
+            .map(|(addr, _)| *addr)
+            .collect();
+        // First device after the one served last, wrapping around
+        let addr = self
+            .last
+            .and_then(|last| ready.iter().find(|a| **a > last))
+            .or(ready.first())
+            .copied()?;
+        self.last = Some(addr);
+        self.queues.get_mut(&addr)?[lane].pop_front()
+    }
+}
+
+/// Shares one API handle between several devices on a multi-drop bus. Requests
+/// from all `BusDevice` handles are executed one at a time by a worker thread,
+/// which exits on `shutdown()` or once the bus and all of its device handles
+/// are dropped.
+pub struct SharedBus {
+    tx: Sender<Message>,
+    thread: JoinHandle<SmdpApi>,
+}
+impl SharedBus {
+    /// Moves the API handle onto the bus worker. The handle's device address is
+    /// replaced per request by the address of the issuing `BusDevice`.
+    pub fn new(api: SmdpApi) -> Self {
+        Self::with_coalesce_window(api, DEFAULT_COALESCE_WINDOW)
+    }
+    /// Like `new()`, with the window in which identical `read_register()`
+    /// calls for a device are answered from one transaction. Queued duplicates
+    /// are always merged; `Duration::ZERO` disables reuse of finished reads.
+    pub fn with_coalesce_window(mut api: SmdpApi, window: Duration) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            Self::run(&mut api, &rx, window);
+            api
+        });
+        Self { tx, thread }
+    }
+    /// Serves the requests already queued, stops the worker and returns the
+    /// API handle. Device handles still around get `Error::BusClosed` from
+    /// then on.
+    pub fn shutdown(self) -> SmdpApi {
+        let _ = self.tx.send(Message::Shutdown);
+        match self.thread.join() {
+            Ok(api) => api,
+            // A job panicked on the worker, pass it on
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+    /// Handle for the device at `addr`. Handles are cheap and can be cloned
+    /// into other threads.
+    pub fn device(&self, addr: u8) -> BusDevice {
+        BusDevice {
+            addr,
+            tx: self.tx.clone(),
+        }
+    }
+    /// Queues a request. True for the shutdown message.
+    fn queue(pending: &mut FairQueue<(u8, Lane, Work)>, msg: Message) -> bool {
+        match msg {
+            Message::Request(req) => {
+                pending.push(req.addr, req.lane, (req.addr, req.lane, req.work));
+                false
+            }
+            Message::Shutdown => true,
+        }
+    }
+    fn run(api: &mut SmdpApi, rx: &Receiver<Message>, window: Duration) {
+        let mut pending = FairQueue::default();
+        let clock = api.clock();
+        let mut recent: HashMap<(u8, Register), (Real, Instant)> = HashMap::new();
+        // Set by shutdown(), after which nothing new is taken from the channel
+        let mut closing = false;
+        loop {
+            // Block only when there is nothing left to do
+            if pending.is_empty() {
+                if closing {
+                    return;
+                }
+                match rx.recv() {
+                    Ok(msg) => closing = Self::queue(&mut pending, msg),
+                    Err(_) => return,
+                }
+            }
+            while !closing && let Ok(msg) = rx.try_recv() {
+                closing = Self::queue(&mut pending, msg);
+            }
+            match pending.pop() {
+                Some((addr, lane, Work::Job(job))) => {
+                    if lane != Lane::Read {
+                        // The write may have changed any value of the device
+                        recent.retain(|(a, _), _| *a != addr);
+                    }
+                    api.set_device_addr(addr);
+                    job(api);
+                }
+                Some((addr, _, Work::Register(register, reply))) => {
+                    if let Some((value, at)) = recent.get(&(addr, register))
+                        && clock.now().saturating_duration_since(*at) < window
+                    {
+                        let _ = reply.send(Ok(*value));
+                        continue;
+                    }
+                    api.set_device_addr(addr);
+                    match api.read(register) {
+                        Ok(value) => {
+                            recent.insert((addr, register), (value, clock.now()));
+                            let _ = reply.send(Ok(value));
+                            let same = pending.take_reads(addr, |(_, _, work)| {
+                                matches!(work, Work::Register(r, _) if *r == register)
+                            });
+                            for (_, _, work) in same {
+                                if let Work::Register(_, reply) = work {
+                                    let _ = reply.send(Ok(value));
+                                }
+                            }
+                        }
+                        // Errors aren't shared, queued duplicates make their own attempt
+                        Err(e) => {
+                            let _ = reply.send(Err(e));
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// One device on a `SharedBus`.
+#[derive(Clone)]
+pub struct BusDevice {
+    addr: u8,
+    tx: Sender<Message>,
+}
+impl BusDevice {
+    pub fn addr(&self) -> u8 {
+        self.addr
+    }
+    /// Runs `f` against this device when its turn comes up, blocking until done.
+    pub fn read<T, F>(&self, f: F) -> CResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SmdpApi) -> CResult<T> + Send + 'static,
+    {
+        self.submit(Lane::Read, f)
+    }
+    /// Like `read`, but queued ahead of all pending reads on the bus.
+    pub fn write<T, F>(&self, f: F) -> CResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SmdpApi) -> CResult<T> + Send + 'static,
+    {
+        self.submit(Lane::Write, f)
+    }
+    /// Like `write`, but queued ahead of all pending reads and writes. At
+    /// most the transaction already in progress is waited for, so keep `f`
+    /// to the safety-relevant command itself.
+    pub fn control<T, F>(&self, f: F) -> CResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SmdpApi) -> CResult<T> + Send + 'static,
+    {
+        self.submit(Lane::Control, f)
+    }
+    pub fn start_compressor(&self) -> CResult<ControlOutcome> {
+        self.control(|api| api.start_compressor())
+    }
+    /// `start_compressor_checked()` on the control lane, so the interlock
+    /// readings and the start aren't interleaved with other traffic.
+    pub fn start_compressor_checked(&self) -> CResult<ControlOutcome> {
+        self.control(|api| api.start_compressor_checked())
+    }
+    pub fn stop_compressor(&self) -> CResult<ControlOutcome> {
+        self.control(|api| api.stop_compressor())
+    }
+    pub fn emergency_stop(&self) -> CResult<()> {
+        self.control(|api| api.emergency_stop())
+    }
+    /// Reads one register. Identical reads of this device from other threads
+    /// that are queued at the same time, or arrive within the bus's coalesce
+    /// window, are answered by the same transaction.
+    pub fn read_register(&self, register: Register) -> CResult<Real> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.send(Lane::Read, Work::Register(register, reply_tx))?;
+        reply_rx.recv().map_err(|_| Error::BusClosed)?
+    }
+    fn submit<T, F>(&self, lane: Lane, f: F) -> CResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SmdpApi) -> CResult<T> + Send + 'static,
+    {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let job: Job = Box::new(move |api| {
+            let _ = reply_tx.send(f(api));
+        });
+        self.send(lane, Work::Job(job))?;
+        reply_rx.recv().map_err(|_| Error::BusClosed)?
+    }
+    fn send(&self, lane: Lane, work: Work) -> CResult<()> {
+        self.tx
+            .send(Message::Request(Request {
+                addr: self.addr,
+                lane,
+                work,
+            }))
+            .map_err(|_| Error::BusClosed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api::CryomechApiSmdpBuilder, link::test::NullPort};
+
+    #[test]
+    fn test_shutdown_returns_handle() {
+        let api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .build_on(NullPort::boxed());
+        let bus = SharedBus::new(api);
+        let device = bus.device(0x11);
+        assert!(device.read_register(Register::HeliumTemp).is_err());
+        let api = bus.shutdown();
+        assert_eq!(api.diagnostics().transactions, 1);
+        assert!(matches!(
+            device.read_register(Register::HeliumTemp),
+            Err(Error::BusClosed)
+        ));
+    }
+
+    #[test]
+    fn test_round_robin_reads() {
+        let mut q = FairQueue::default();
+        q.push(0x10, Lane::Read, "a1");
+        q.push(0x10, Lane::Read, "a2");
+        q.push(0x10, Lane::Read, "a3");
+        q.push(0x11, Lane::Read, "b1");
+        q.push(0x12, Lane::Read, "c1");
+        let order: Vec<_> = std::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(order, ["a1", "b1", "c1", "a2", "a3"]);
+    }
+    #[test]
+    fn test_writes_first() {
+        let mut q = FairQueue::default();
+        q.push(0x10, Lane::Read, "a1");
+        q.push(0x10, Lane::Read, "a2");
+        q.push(0x11, Lane::Write, "b-start");
+        q.push(0xFF, Lane::Write, "c-stop");
+        assert_eq!(q.pop(), Some("b-start"));
+        assert_eq!(q.pop(), Some("c-stop"));
+        assert_eq!(q.pop(), Some("a1"));
+        q.push(0x11, Lane::Write, "b-stop");
+        assert_eq!(q.pop(), Some("b-stop"));
+        assert_eq!(q.pop(), Some("a2"));
+        assert!(q.is_empty());
+    }
+    #[test]
+    fn test_control_lane_first() {
+        let mut q = FairQueue::default();
+        q.push(0x10, Lane::Read, "a-read");
+        q.push(0x10, Lane::Write, "a-write");
+        q.push(0x11, Lane::Read, "b-read");
+        q.push(0x11, Lane::Control, "b-stop");
+        q.push(0x12, Lane::Control, "c-start");
+        assert_eq!(q.pop(), Some("b-stop"));
+        assert_eq!(q.pop(), Some("c-start"));
+        assert_eq!(q.pop(), Some("a-write"));
+        q.push(0x10, Lane::Control, "a-stop");
+        assert_eq!(q.pop(), Some("a-stop"));
+        assert_eq!(q.pop(), Some("b-read"));
+        assert_eq!(q.pop(), Some("a-read"));
+        assert!(q.is_empty());
+    }
+    #[test]
+    fn test_take_reads() {
+        let mut q = FairQueue::default();
+        q.push(0x10, Lane::Read, "he");
+        q.push(0x10, Lane::Read, "oil");
+        q.push(0x10, Lane::Read, "he");
+        q.push(0x11, Lane::Read, "he");
+        q.push(0x10, Lane::Write, "he");
+        assert_eq!(q.take_reads(0x10, |j| *j == "he"), ["he", "he"]);
+        // The write and the other device's read are left in place
+        assert_eq!(q.pop(), Some("he"));
+        assert_eq!(q.pop(), Some("he"));
+        assert_eq!(q.pop(), Some("oil"));
+        assert!(q.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+/* Opt-in cache for slowly-changing registers, keyed by dictionary hash/index */
+
+use crate::dictionary::{COMP_MINUTES_HASH, FW_CHECKSUM_HASH};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// How long a register value may be served from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheTtl {
+    /// Always read from the device.
+    Never,
+    /// Reuse the value for the given duration.
+    For(Duration),
+    /// Read once per handle (identity/firmware values).
+    Forever,
+}
+
+#[derive(Debug)]
+pub(crate) struct ReadCache {
+    ttls: HashMap<u16, CacheTtl>,
+    values: HashMap<(u16, u8), (u32, Instant)>,
+}
+impl Default for ReadCache {
+    /// Cache with TTLs for the known slowly-changing registers. Everything else
+    /// (pressures, temperatures, state) is always read from the device.
+    fn default() -> Self {
+        // The firmware checksum never changes while the handle is open and
+        // compressor minutes only tick once a minute
+        let ttls = HashMap::from([
+            (FW_CHECKSUM_HASH, CacheTtl::Forever),
+            (COMP_MINUTES_HASH, CacheTtl::For(Duration::from_secs(30))),
+        ]);
+        Self {
+            ttls,
+            values: HashMap::new(),
+        }
+    }
+}
+impl ReadCache {
+    pub(crate) fn set_ttl(&mut self, hashval: u16, ttl: CacheTtl) {
+        self.ttls.insert(hashval, ttl);
+    }
+    fn ttl(&self, hashval: u16) -> CacheTtl {
+        self.ttls.get(&hashval).copied().unwrap_or(CacheTtl::Never)
+    }
+    /// Cached value, if present and still within its TTL at `now`.
+    pub(crate) fn get(&self, hashval: u16, array_idx: u8, now: Instant) -> Option<u32> {
+        let (value, at) = self.values.get(&(hashval, array_idx))?;
+        match self.ttl(hashval) {
+            CacheTtl::Never => None,
+            CacheTtl::For(ttl) => (now.saturating_duration_since(*at) < ttl).then_some(*value),
+            CacheTtl::Forever => Some(*value),
+        }
+    }
+    /// Stores a value read at `now`.
+    pub(crate) fn insert(&mut self, hashval: u16, array_idx: u8, value: u32, now: Instant) {
+        if self.ttl(hashval) != CacheTtl::Never {
+            self.values.insert((hashval, array_idx), (value, now));
+        }
+    }
+    /// Drops all cached values, e.g. after a write that may have changed them.
+    pub(crate) fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_ttls() {
+        let mut cache = ReadCache::default();
+        let t0 = Instant::now();
+        cache.insert(FW_CHECKSUM_HASH, 0, 0xDEAD, t0);
+        cache.insert(COMP_MINUTES_HASH, 0, 100, t0);
+        cache.insert(0xAA50, 0, 2000, t0);
+        assert_eq!(cache.get(FW_CHECKSUM_HASH, 0, t0), Some(0xDEAD));
+        assert_eq!(cache.get(COMP_MINUTES_HASH, 0, t0), Some(100));
+        assert_eq!(cache.get(0xAA50, 0, t0), None);
+        // Compressor minutes expire, the checksum doesn't
+        let later = t0 + Duration::from_secs(30);
+        assert_eq!(cache.get(COMP_MINUTES_HASH, 0, later), None);
+        assert_eq!(cache.get(FW_CHECKSUM_HASH, 0, later), Some(0xDEAD));
+        cache.clear();
+        assert_eq!(cache.get(FW_CHECKSUM_HASH, 0, later), None);
+    }
+    #[test]
+    fn test_expired_entry() {
+        let mut cache = ReadCache::default();
+        let t0 = Instant::now();
+        cache.set_ttl(0xAA50, CacheTtl::For(Duration::ZERO));
+        cache.insert(0xAA50, 1, 2000, t0);
+        assert_eq!(cache.get(0xAA50, 1, t0), None);
+    }
+}
@@ -0,0 +1,111 @@
+/* Capability queries, so callers can hide controls a unit doesn't have */
+
+use crate::{CResult, core::register::Register};
+use std::collections::HashMap;
+
+/// Optional functions that depend on the backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Starting and stopping the compressor remotely.
+    RemoteStartStop,
+    /// Clearing the stored pressure/temperature minimums and maximums.
+    ClearMinMax,
+}
+
+/// Anything that can be passed to `supports()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Register(Register),
+    Feature(Feature),
+}
+impl From<Register> for Capability {
+    fn from(register: Register) -> Self {
+        Self::Register(register)
+    }
+}
+impl From<Feature> for Capability {
+    fn from(feature: Feature) -> Self {
+        Self::Feature(feature)
+    }
+}
+
+/// Which backend a feature is being checked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    Smdp,
+    Modbus,
+}
+impl Backend {
+    /// Answers `supports()` without I/O where possible: features, registers
+    /// the backend has no address for, and registers probed before. Otherwise
+    /// returns the register to probe with one read.
+    pub(crate) fn supports(
+        self,
+        capability: Capability,
+        probed: &ProbeCache,
+    ) -> Result<bool, Register> {
+        let register = match capability {
+            Capability::Feature(Feature::RemoteStartStop) => return Ok(true),
+            Capability::Feature(Feature::ClearMinMax) => return Ok(self == Self::Smdp),
+            Capability::Register(register) => register,
+        };
+        let mapped = match self {
+            Self::Smdp => register.smdp().is_some(),
+            Self::Modbus => register.modbus().is_some(),
+        };
+        match probed.get(&register) {
+            _ if !mapped => Ok(false),
+            Some(&supported) => Ok(supported),
+            None => Err(register),
+        }
+    }
+}
+
+/// Results of register probes, kept for the life of the handle.
+pub(crate) type ProbeCache = HashMap<Register, bool>;
+
+/// Records the probe read of `register`. A rejection means unsupported; other
+/// errors are returned and the register stays unprobed.
+pub(crate) fn record_probe<T>(
+    probed: &mut ProbeCache,
+    register: Register,
+    read: CResult<T>,
+) -> CResult<bool> {
+    let supported = match read {
+        Ok(_) => true,
+        Err(e) if e.is_nak() => false,
+        Err(e) => return Err(e),
+    };
+    probed.insert(register, supported);
+    Ok(supported)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_supports() {
+        let mut probed = ProbeCache::new();
+        let clear: Capability = Feature::ClearMinMax.into();
+        assert_eq!(Backend::Smdp.supports(clear, &probed), Ok(true));
+        assert_eq!(Backend::Modbus.supports(clear, &probed), Ok(false));
+        // Unmapped registers are never probed
+        let cpu: Capability = Register::CpuTemp.into();
+        assert_eq!(Backend::Modbus.supports(cpu, &probed), Ok(false));
+        assert_eq!(Backend::Smdp.supports(cpu, &probed), Err(Register::CpuTemp));
+        assert!(
+            record_probe(
+                &mut probed,
+                Register::CpuTemp,
+                Err::<(), _>(Error::LinkDead(3))
+            )
+            .is_err()
+        );
+        assert_eq!(Backend::Smdp.supports(cpu, &probed), Err(Register::CpuTemp));
+        let nak = Err::<(), _>(Error::ModbusException(0x02));
+        assert!(!record_probe(&mut probed, Register::CpuTemp, nak).unwrap());
+        assert_eq!(Backend::Smdp.supports(cpu, &probed), Ok(false));
+    }
+}
@@ -0,0 +1,164 @@
+/* Session capture of all wire traffic to a file, as text or pcapng */
+
+use crate::{CResult, link::Direction};
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// pcapng block types
+const SHB_TYPE: u32 = 0x0A0D_0D0A;
+const IDB_TYPE: u32 = 0x0000_0001;
+const EPB_TYPE: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+// LINKTYPE_USER0, reserved for private use; frames are raw SMDP bytes
+const LINKTYPE_SMDP: u16 = 147;
+// epb_flags option, direction in the two low bits
+const OPT_EPB_FLAGS: u16 = 2;
+const EPB_INBOUND: u32 = 1;
+const EPB_OUTBOUND: u32 = 2;
+
+/// File format of a session capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// One line per chunk: `<unix time µs> <TX|RX> <hex bytes>`.
+    Text,
+    /// pcapng with link type USER0 (147), one packet per chunk. Direction is
+    /// stored in the packet flags.
+    Pcapng,
+}
+
+/// Writes every chunk of wire traffic to a file with a timestamp. Install on a
+/// handle with `CryomechApiSmdp::capture_session()`.
+#[derive(Debug)]
+pub struct SessionCapture {
+    file: Mutex<File>,
+    format: CaptureFormat,
+}
+impl SessionCapture {
+    /// Creates (or truncates) the capture file and writes the file header.
+    pub fn create(path: impl AsRef<Path>, format: CaptureFormat) -> CResult<Self> {
+        let mut file = File::create(path)?;
+        if format == CaptureFormat::Pcapng {
+            file.write_all(&pcapng_header())?;
+        }
+        Ok(Self {
+            file: Mutex::new(file),
+            format,
+        })
+    }
+    /// Appends one chunk. Write errors are dropped, a capture must never
+    /// disturb the comm path.
+    pub fn record(&self, dir: &Direction, bytes: &[u8]) {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+        let entry = match self.format {
+            CaptureFormat::Text => text_line(micros, dir, bytes).into_bytes(),
+            CaptureFormat::Pcapng => pcapng_packet(micros, dir, bytes),
+        };
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = file.write_all(&entry);
+    }
+}
+
+fn text_line(micros: u64, dir: &Direction, bytes: &[u8]) -> String {
+    let mut line = format!(
+        "{micros} {}",
+        match dir {
+            Direction::Tx => "TX",
+            Direction::Rx => "RX",
+        }
+    );
+    for b in bytes {
+        let _ = write!(line, " {b:02X}");
+    }
+    line.push('\n');
+    line
+}
+
+/// Section header and interface description blocks.
+fn pcapng_header() -> Vec<u8> {
+    let mut out = Vec::with_capacity(48);
+    // Section header block, no options, section length unspecified
+    out.extend(SHB_TYPE.to_le_bytes());
+    out.extend(28u32.to_le_bytes());
+    out.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend((-1i64).to_le_bytes());
+    out.extend(28u32.to_le_bytes());
+    // Interface description block, default µs timestamp resolution
+    out.extend(IDB_TYPE.to_le_bytes());
+    out.extend(20u32.to_le_bytes());
+    out.extend(LINKTYPE_SMDP.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(20u32.to_le_bytes());
+    out
+}
+
+/// Enhanced packet block carrying one chunk.
+fn pcapng_packet(micros: u64, dir: &Direction, bytes: &[u8]) -> Vec<u8> {
+    let padded = bytes.len().div_ceil(4) * 4;
+    // Fixed fields + data + epb_flags option + end of options
+    let total = (32 + padded + 8 + 4) as u32;
+    let flags = match dir {
+        Direction::Tx => EPB_OUTBOUND,
+        Direction::Rx => EPB_INBOUND,
+    };
+    let mut out = Vec::with_capacity(total as usize);
+    out.extend(EPB_TYPE.to_le_bytes());
+    out.extend(total.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(((micros >> 32) as u32).to_le_bytes());
+    out.extend((micros as u32).to_le_bytes());
+    out.extend((bytes.len() as u32).to_le_bytes());
+    out.extend((bytes.len() as u32).to_le_bytes());
+    out.extend(bytes);
+    out.resize(out.len() + padded - bytes.len(), 0);
+    out.extend(OPT_EPB_FLAGS.to_le_bytes());
+    out.extend(4u16.to_le_bytes());
+    out.extend(flags.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(total.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_text_line() {
+        assert_eq!(
+            text_line(1_700_000_000_000_000, &Direction::Tx, &[0x02, 0x10, 0x80]),
+            "1700000000000000 TX 02 10 80\n"
+        );
+    }
+    #[test]
+    fn test_pcapng_blocks() {
+        let header = pcapng_header();
+        assert_eq!(header.len(), 48);
+        assert_eq!(header[8..12], BYTE_ORDER_MAGIC.to_le_bytes());
+        let pkt = pcapng_packet(
+            0x1_0000_0002,
+            &Direction::Rx,
+            &[0x02, 0x10, 0x80, 0x0D, 0x0D],
+        );
+        // 5 data bytes padded to 8
+        assert_eq!(pkt.len(), 52);
+        assert_eq!(pkt[4..8], 52u32.to_le_bytes());
+        assert_eq!(pkt[pkt.len() - 4..], 52u32.to_le_bytes());
+        assert_eq!(pkt[12..16], 1u32.to_le_bytes());
+        assert_eq!(pkt[16..20], 2u32.to_le_bytes());
+        assert_eq!(pkt[20..24], 5u32.to_le_bytes());
+        assert_eq!(pkt[28..36], [0x02, 0x10, 0x80, 0x0D, 0x0D, 0, 0, 0]);
+        assert_eq!(pkt[36..40], [2, 0, 4, 0]);
+        assert_eq!(pkt[40..44], EPB_INBOUND.to_le_bytes());
+    }
+}
@@ -0,0 +1,92 @@
+/* Time source used for sleeps and timeouts, replaceable in tests */
+
+use std::{
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Source of time for waits, timeouts and timestamps. The API handle and the
+/// poller use `SystemClock` unless another clock is configured.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    /// Wall clock time, for timestamps and schedules.
+    fn system_time(&self) -> SystemTime;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Clock that only moves when slept on or advanced, so time-dependent logic
+/// can be tested without waiting.
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    start_wall: SystemTime,
+    offset: Mutex<Duration>,
+}
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(SystemTime::now())
+    }
+}
+impl MockClock {
+    /// Mock clock whose wall time starts at `wall`.
+    pub fn new(wall: SystemTime) -> Self {
+        Self {
+            start: Instant::now(),
+            start_wall: wall,
+            offset: Mutex::new(Duration::ZERO),
+        }
+    }
+    pub fn advance(&self, duration: Duration) {
+        *self.offset.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+    /// Total time slept or advanced so far.
+    pub fn elapsed(&self) -> Duration {
+        *self.offset.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+    fn system_time(&self) -> SystemTime {
+        self.start_wall + self.elapsed()
+    }
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::default();
+        let t0 = clock.now();
+        let w0 = clock.system_time();
+        clock.sleep(Duration::from_secs(5));
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.now() - t0, Duration::from_millis(5250));
+        assert_eq!(
+            clock.system_time().duration_since(w0).unwrap(),
+            Duration::from_millis(5250)
+        );
+    }
+}
@@ -0,0 +1,163 @@
+/* Capability token that gates writes on a handle */
+
+use crate::api::CryomechApiSmdp;
+use serialport::SerialPort;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+
+static NEXT_HANDLE_ID: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn next_handle_id() -> u64 {
+    NEXT_HANDLE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Proof of write permission for one handle. Issued once per handle by
+/// `take_control_token()` and can't be cloned, so only code that was handed
+/// the token can start, stop or clear values when tokens are required.
+#[derive(Debug)]
+pub struct ControlToken {
+    pub(crate) handle_id: u64,
+}
+
+/// Write access to a handle, obtained with `control()`. Writes through it are
+/// allowed until it is dropped.
+#[derive(Debug)]
+pub struct Control<'a> {
+    api: &'a mut SmdpApi,
+}
+impl<'a> Control<'a> {
+    pub(crate) fn new(api: &'a mut SmdpApi) -> Self {
+        api.set_token_active(true);
+        Self { api }
+    }
+}
+impl Deref for Control<'_> {
+    type Target = SmdpApi;
+    fn deref(&self) -> &SmdpApi {
+        self.api
+    }
+}
+impl DerefMut for Control<'_> {
+    fn deref_mut(&mut self) -> &mut SmdpApi {
+        self.api
+    }
+}
+impl Drop for Control<'_> {
+    fn drop(&mut self) {
+        self.api.set_token_active(false);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Error,
+        api::CryomechApiSmdpBuilder,
+        clock::MockClock,
+        dictionary::{START_HASH, STOP_HASH},
+        link::test::NullPort,
+    };
+    use std::{sync::Arc, time::Duration};
+
+    // Handle requiring both arming and the token, on a port that never answers
+    fn gated(clock: Arc<MockClock>) -> SmdpApi {
+        CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .armed_writes(true)
+            .control_token(true)
+            .clock(clock)
+            .build_on(NullPort::boxed())
+    }
+
+    #[test]
+    fn test_take_control_token() {
+        let mut api = gated(Arc::default());
+        let token = api.take_control_token().unwrap();
+        assert!(api.take_control_token().is_none());
+        assert!(api.control(&token).is_ok());
+        // A token only opens the handle it was issued by
+        let mut other = gated(Arc::default());
+        assert!(matches!(
+            other.control(&token),
+            Err(Error::ControlTokenRequired)
+        ));
+    }
+
+    #[test]
+    fn test_control_drop() {
+        let mut api = gated(Arc::default());
+        api.arm_writes(Duration::from_secs(60));
+        let token = api.take_control_token().unwrap();
+        {
+            let mut control = api.control(&token).unwrap();
+            // Past the gates, so the write goes out and times out
+            assert!(control.write_register(START_HASH, 0, 1).is_err());
+            assert_eq!(control.diagnostics().transactions, 1);
+        }
+        assert!(matches!(
+            api.write_register(START_HASH, 0, 1),
+            Err(Error::ControlTokenRequired)
+        ));
+        assert_eq!(api.diagnostics().transactions, 1);
+    }
+
+    #[test]
+    fn test_gate_order() {
+        let clock = Arc::new(MockClock::default());
+        let mut api = gated(Arc::clone(&clock));
+        let token = api.take_control_token().unwrap();
+        // Arming is checked before the token
+        assert!(matches!(
+            api.write_register(START_HASH, 0, 1),
+            Err(Error::WritesNotArmed)
+        ));
+        api.arm_writes(Duration::from_secs(10));
+        assert!(matches!(
+            api.write_register(START_HASH, 0, 1),
+            Err(Error::ControlTokenRequired)
+        ));
+        clock.advance(Duration::from_secs(10));
+        assert!(matches!(
+            api.control(&token)
+                .unwrap()
+                .write_register(START_HASH, 0, 1),
+            Err(Error::WritesNotArmed)
+        ));
+        // Stops are gated like any other write
+        assert!(matches!(
+            api.write_register(STOP_HASH, 0, 0),
+            Err(Error::WritesNotArmed)
+        ));
+        assert_eq!(api.diagnostics().transactions, 0);
+    }
+
+    #[test]
+    fn test_stop_needs_arming() {
+        let mut api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .armed_writes(true)
+            .build_on(NullPort::boxed());
+        assert!(matches!(api.stop_compressor(), Err(Error::WritesNotArmed)));
+        // Refused before the run state read
+        assert_eq!(api.diagnostics().transactions, 0);
+    }
+
+    #[test]
+    fn test_emergency_stop_ignores_arming() {
+        let mut api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .armed_writes(true)
+            .build_on(NullPort::boxed());
+        // No device answers, but every stop got past the gate onto the wire
+        assert!(!matches!(api.emergency_stop(), Err(Error::WritesNotArmed)));
+        assert_eq!(api.diagnostics().transactions, 3);
+    }
+}
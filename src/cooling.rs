@@ -0,0 +1,116 @@
+/* Cooling water diagnosis from water and oil temperatures */
+
+use crate::{
+    Real,
+    history::{History, HistoryKind},
+    status::CompressorStatus,
+};
+use std::{ops::Range, time::SystemTime};
+
+/// Thresholds for `CoolingLimits::diagnose()`. The defaults are examples; set
+/// them from the model's cooling water specification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoolingLimits {
+    /// Highest acceptable supply (input) water temperature, °C.
+    pub max_supply_temp: Real,
+    /// Largest acceptable rise from input to output water, °C. A larger rise
+    /// means too little water is carrying the heat away.
+    pub max_water_rise: Real,
+    /// Oil temperature above which heat is not being removed, °C.
+    pub max_oil_temp: Real,
+}
+impl Default for CoolingLimits {
+    fn default() -> Self {
+        Self {
+            max_supply_temp: 27.0,
+            max_water_rise: 15.0,
+            max_oil_temp: 46.0,
+        }
+    }
+}
+
+/// Likely state of the cooling water supply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoolingDiagnosis {
+    Normal,
+    /// Supply water is warmer than the limit.
+    WarmSupply,
+    /// Water rise is too large, or the oil runs hot with cool supply water.
+    InsufficientFlow,
+    WarmSupplyAndInsufficientFlow,
+    /// Compressor is off; water temperatures say nothing about the cooling.
+    NotRunning,
+}
+
+impl CoolingLimits {
+    /// Diagnosis from one snapshot.
+    pub fn diagnose(&self, status: &CompressorStatus) -> CoolingDiagnosis {
+        if !status.comp_on {
+            return CoolingDiagnosis::NotRunning;
+        }
+        self.classify(
+            status.input_water_temp,
+            status.output_water_temp,
+            status.oil_temp,
+        )
+    }
+    /// Diagnosis from the running snapshots in `range`, averaged so a single
+    /// transient reading doesn't flag a problem. None if the compressor did not
+    /// run in `range`.
+    pub fn diagnose_history(
+        &self,
+        history: &History,
+        range: Range<SystemTime>,
+    ) -> Option<CoolingDiagnosis> {
+        let (mut input, mut output, mut oil, mut n) = (0.0, 0.0, 0.0, 0);
+        for entry in history
+            .entries()
+            .filter(|e| e.kind == HistoryKind::Poll && e.status.comp_on && range.contains(&e.at))
+        {
+            input += entry.status.input_water_temp;
+            output += entry.status.output_water_temp;
+            oil += entry.status.oil_temp;
+            n += 1;
+        }
+        let n = n as Real;
+        (n > 0.0).then(|| self.classify(input / n, output / n, oil / n))
+    }
+    fn classify(&self, input: Real, output: Real, oil: Real) -> CoolingDiagnosis {
+        let warm = input > self.max_supply_temp;
+        // Hot oil with a warm supply is explained by the supply alone
+        let low_flow = output - input > self.max_water_rise || (!warm && oil > self.max_oil_temp);
+        match (warm, low_flow) {
+            (false, false) => CoolingDiagnosis::Normal,
+            (true, false) => CoolingDiagnosis::WarmSupply,
+            (false, true) => CoolingDiagnosis::InsufficientFlow,
+            (true, true) => CoolingDiagnosis::WarmSupplyAndInsufficientFlow,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let limits = CoolingLimits::default();
+        assert_eq!(limits.classify(20.0, 30.0, 40.0), CoolingDiagnosis::Normal);
+        assert_eq!(
+            limits.classify(30.0, 40.0, 50.0),
+            CoolingDiagnosis::WarmSupply
+        );
+        assert_eq!(
+            limits.classify(20.0, 40.0, 40.0),
+            CoolingDiagnosis::InsufficientFlow
+        );
+        assert_eq!(
+            limits.classify(20.0, 30.0, 50.0),
+            CoolingDiagnosis::InsufficientFlow
+        );
+        assert_eq!(
+            limits.classify(30.0, 50.0, 50.0),
+            CoolingDiagnosis::WarmSupplyAndInsufficientFlow
+        );
+    }
+}
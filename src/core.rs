@@ -0,0 +1,13 @@
+/* Protocol core: frame encoding/decoding and the register map, without I/O */
+// Only core and alloc items, so this can be lifted into a no_std build (embedded
+// bridges, WASM). Transports in api/link/modbus are layered on top; `codec` is
+// the entry point for users that bring their own transport.
+#![deny(
+    clippy::std_instead_of_core,
+    clippy::std_instead_of_alloc,
+    clippy::alloc_instead_of_core
+)]
+
+pub mod codec;
+pub(crate) mod packet;
+pub(crate) mod register;
@@ -0,0 +1,63 @@
+/* Public frame codec for callers that move the bytes themselves (browser UIs, bridges) */
+
+use super::{
+    packet::{CPacketSmdp, RequestType},
+    register::Register,
+};
+use crate::{CResult, Error, Real};
+use alloc::vec::Vec;
+use smdp::{SmdpPacketV2, SmdpPacketV3};
+
+/// SMDP V2 read request for `register` to the device at `addr`.
+pub fn read_request_v2(addr: u8, register: Register) -> CResult<SmdpPacketV2> {
+    let reg = register
+        .smdp()
+        .ok_or(Error::UnsupportedRegister(register))?;
+    Ok(CPacketSmdp::new(addr, None, RequestType::Read, reg.hashval, reg.array_idx).into())
+}
+
+/// SMDP V3+ read request for `register` to the device at `addr`.
+pub fn read_request_v3(addr: u8, srlno: u8, register: Register) -> CResult<SmdpPacketV3> {
+    let reg = register
+        .smdp()
+        .ok_or(Error::UnsupportedRegister(register))?;
+    CPacketSmdp::new(
+        addr,
+        Some(srlno),
+        RequestType::Read,
+        reg.hashval,
+        reg.array_idx,
+    )
+    .try_into()
+}
+
+/// Data field of a read request for `register`, for callers doing their own
+/// SMDP framing.
+pub fn read_request_data(register: Register) -> CResult<Vec<u8>> {
+    Ok(read_request_v2(0, register)?.data().to_vec())
+}
+
+/// Scaled value carried in the data field of a read reply for `register`.
+pub fn decode_reading(register: Register, data: &[u8]) -> CResult<Real> {
+    let reg = register
+        .smdp()
+        .ok_or(Error::UnsupportedRegister(register))?;
+    Ok(CPacketSmdp::extract_data(data)? as Real * reg.scale)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_helium_temp_round_trip() {
+        assert_eq!(
+            read_request_data(Register::HeliumTemp).unwrap(),
+            [0x63, 0x0D, 0x8F, 0x02]
+        );
+        let reply = [0x63, 0x0D, 0x8F, 0x02, 0x00, 0x00, 0x01, 0x2C];
+        let temp = decode_reading(Register::HeliumTemp, &reply).unwrap();
+        assert!((temp - 30.0).abs() < 1e-4);
+        assert!(decode_reading(Register::OperatingState, &reply).is_err());
+    }
+}
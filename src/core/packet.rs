@@ -0,0 +1,364 @@
+/* Defines an abstraction over the link protocols that handles specifics related to the Cryomech API */
+// The comm path must never panic on device input
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::indexing_slicing
+    )
+)]
+
+use smdp::{Packet, SmdpPacketV2, SmdpPacketV3, format::ResponseCode};
+
+use crate::{CResult, Error};
+use alloc::{string::String, vec::Vec};
+
+pub(crate) const SMDP_OPCODE: u8 = 0x80;
+// Upper nibble of CMD_RSP carries the command, lower nibble the response code.
+const OPCODE_MASK: u8 = 0xF0;
+// Low bits of CMD_RSP carry the response code
+const RSP_MASK: u8 = 0x07;
+// Response code returned while the panel temporarily inhibits commands
+const RSP_INHIBITED: u8 = 0x05;
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RequestType {
+    Read,
+    /// Writes to dictionary values need data along with the
+    /// dictionary hash/idx
+    Write(u32),
+}
+
+/// Value carried by a dictionary read reply, after the echoed request header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseData {
+    /// Reply with no data
+    Empty,
+    /// One or more big-endian 32-bit values
+    Words(Vec<u32>),
+    /// ASCII data, e.g. a serial number, with trailing NULs and spaces removed
+    Text(String),
+}
+impl ResponseData {
+    /// The value of a single-word reply.
+    pub fn word(&self) -> Option<u32> {
+        match self {
+            Self::Words(words) if words.len() == 1 => words.first().copied(),
+            _ => None,
+        }
+    }
+    pub fn words(&self) -> &[u32] {
+        match self {
+            Self::Words(words) => words,
+            _ => &[],
+        }
+    }
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Header and data access common to both SMDP frame versions, so replies
+/// are handled by one code path.
+pub(crate) trait SmdpFrame: Packet {
+    fn addr(&self) -> u8;
+    fn cmd_rsp(&self) -> u8;
+    fn data(&self) -> &[u8];
+    fn rsp(&self) -> Result<ResponseCode, smdp::Error>;
+}
+impl SmdpFrame for SmdpPacketV2 {
+    fn addr(&self) -> u8 {
+        SmdpPacketV2::addr(self)
+    }
+    fn cmd_rsp(&self) -> u8 {
+        SmdpPacketV2::cmd_rsp(self)
+    }
+    fn data(&self) -> &[u8] {
+        SmdpPacketV2::data(self)
+    }
+    fn rsp(&self) -> Result<ResponseCode, smdp::Error> {
+        SmdpPacketV2::rsp(self)
+    }
+}
+impl SmdpFrame for SmdpPacketV3 {
+    fn addr(&self) -> u8 {
+        SmdpPacketV3::addr(self)
+    }
+    fn cmd_rsp(&self) -> u8 {
+        SmdpPacketV3::cmd_rsp(self)
+    }
+    fn data(&self) -> &[u8] {
+        SmdpPacketV3::data(self)
+    }
+    fn rsp(&self) -> Result<ResponseCode, smdp::Error> {
+        SmdpPacketV3::rsp(self)
+    }
+}
+
+/// Cryomech specific wrapper for SMDP packet format.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CPacketSmdp {
+    addr: u8,
+    data: Vec<u8>,
+    srlno: Option<u8>,
+}
+impl CPacketSmdp {
+    /// Request for a dictionary read or write. smdp frames take ownership of
+    /// their data, so every request allocates its data field once; it can't
+    /// be handed back for reuse. Replies are not copied, see `extract_data()`.
+    pub(crate) fn new(
+        addr: u8,
+        srlno: Option<u8>,
+        req_type: RequestType,
+        hashval: u16,
+        array_idx: u8,
+    ) -> Self {
+        let (req_type_val, dict_write_data) = match req_type {
+            RequestType::Read => (0x63u8, None),
+            RequestType::Write(d) => (0x61, Some(d)),
+        };
+
+        // Sized for the largest request (write) so it is the only allocation
+        let mut data = Vec::with_capacity(8);
+        data.push(req_type_val);
+        data.extend_from_slice(&hashval.to_be_bytes());
+        data.push(array_idx);
+        if let Some(dict_data) = dict_write_data {
+            data.extend_from_slice(&dict_data.to_be_bytes());
+        }
+        Self { addr, data, srlno }
+    }
+    /// Extracts the data portion of a well-formed reply based on
+    /// the Cryomech data model. Should either be 4 bytes (BE) or
+    /// null. Reads directly from the data field of the received
+    /// frame, no copy into a CPacketSmdp is needed.
+    pub(crate) fn extract_data(data: &[u8]) -> CResult<u32> {
+        // A well-formed response containing data should be 8-bytes
+        if data.len() == 8 {
+            data.get(4..)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u32::from_be_bytes)
+                .ok_or(Error::InvalidFormat("Index into response data invalid."))
+        } else {
+            Err(Error::InvalidFormat(
+                "Response is malformed or is not a response packet.",
+            ))
+        }
+    }
+    /// Extracts the data of a reply of any length. `text` selects ASCII
+    /// decoding, otherwise the data must be a whole number of 32-bit words.
+    pub(crate) fn extract_response(data: &[u8], text: bool) -> CResult<ResponseData> {
+        let payload = data
+            .get(4..)
+            .ok_or(Error::InvalidFormat("Response is shorter than its header."))?;
+        if payload.is_empty() {
+            return Ok(ResponseData::Empty);
+        }
+        if text {
+            let text = core::str::from_utf8(payload)
+                .ok()
+                .filter(|s| s.is_ascii())
+                .ok_or(Error::InvalidFormat("Response data is not ASCII."))?;
+            return Ok(ResponseData::Text(
+                text.trim_end_matches(['\0', ' ']).into(),
+            ));
+        }
+        if payload.len() % 4 != 0 {
+            return Err(Error::InvalidFormat(
+                "Response data is not a whole number of words.",
+            ));
+        }
+        Ok(ResponseData::Words(
+            payload
+                .chunks_exact(4)
+                .filter_map(|w| w.try_into().ok().map(u32::from_be_bytes))
+                .collect(),
+        ))
+    }
+    /// Opcode and data field of a dictionary read, for sending through the
+    /// raw path when the reply isn't a single word.
+    pub(crate) fn read_request(hashval: u16, array_idx: u8) -> (u8, [u8; 4]) {
+        let [hi, lo] = hashval.to_be_bytes();
+        (SMDP_OPCODE, [0x63, hi, lo, array_idx])
+    }
+    /// True if the CMD_RSP byte of a reply answers a request with `opcode`.
+    pub(crate) fn is_reply_to(cmd_rsp: u8, opcode: u8) -> bool {
+        cmd_rsp & OPCODE_MASK == opcode
+    }
+    /// Checks a non-dictionary opcode for use with `raw_v2`/`raw_v3`. Only the
+    /// upper nibble may be set, the lower one carries the response code.
+    pub(crate) fn check_raw_opcode(opcode: u8) -> CResult<()> {
+        if opcode & !OPCODE_MASK != 0 || opcode == 0 {
+            return Err(Error::InvalidFormat(
+                "Opcode must be non-zero with the low nibble clear.",
+            ));
+        }
+        Ok(())
+    }
+    /// Request frame with a caller-supplied opcode and payload.
+    pub(crate) fn raw_v2(addr: u8, opcode: u8, data: &[u8]) -> SmdpPacketV2 {
+        SmdpPacketV2::new(addr, opcode, data.to_vec())
+    }
+    /// Request frame with a caller-supplied opcode and payload.
+    pub(crate) fn raw_v3(addr: u8, opcode: u8, srlno: u8, data: &[u8]) -> SmdpPacketV3 {
+        SmdpPacketV3::new(addr, opcode, srlno, data.to_vec())
+    }
+    /// True if the reply signals the device is busy and the request may be retried.
+    pub(crate) fn is_busy_reply(cmd_rsp: u8) -> bool {
+        cmd_rsp & RSP_MASK == RSP_INHIBITED
+    }
+    /// Sets the SRLNO of a packet. Used with SMDP versions >= 2.
+    pub(crate) fn set_srlno(&mut self, srlno: u8) {
+        self.srlno = Some(srlno)
+    }
+}
+impl From<CPacketSmdp> for SmdpPacketV2 {
+    fn from(cpkt: CPacketSmdp) -> Self {
+        SmdpPacketV2::new(cpkt.addr, SMDP_OPCODE, cpkt.data)
+    }
+}
+impl TryFrom<CPacketSmdp> for SmdpPacketV3 {
+    type Error = Error;
+
+    fn try_from(cpkt: CPacketSmdp) -> Result<Self, Self::Error> {
+        if let Some(srlno) = cpkt.srlno {
+            Ok(SmdpPacketV3::new(cpkt.addr, SMDP_OPCODE, srlno, cpkt.data))
+        } else {
+            Err(Error::InvalidFormat("Packet has no serial number."))
+        }
+    }
+}
+impl From<SmdpPacketV2> for CPacketSmdp {
+    fn from(pkt: SmdpPacketV2) -> Self {
+        Self {
+            addr: pkt.addr(),
+            data: Vec::from(pkt.data()),
+            srlno: None,
+        }
+    }
+}
+impl From<SmdpPacketV3> for CPacketSmdp {
+    fn from(pkt: SmdpPacketV3) -> Self {
+        Self {
+            addr: pkt.addr(),
+            data: Vec::from(pkt.data()),
+            srlno: Some(pkt.srlno()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cpkt_into_smdpv1() {
+        let cpkt = CPacketSmdp {
+            addr: 0x10,
+            srlno: None,
+            data: vec![1, 2, 3],
+        };
+        let smdpv1_pkt: SmdpPacketV2 = cpkt.clone().into();
+        assert_eq!(smdpv1_pkt.data(), cpkt.data);
+        assert_eq!(smdpv1_pkt.addr(), cpkt.addr);
+        assert_eq!(smdpv1_pkt.cmd_rsp(), SMDP_OPCODE);
+    }
+    #[test]
+    fn test_cpkt_into_smdpv2_ok() {
+        let cpkt = CPacketSmdp {
+            addr: 0x10,
+            srlno: Some(0x17),
+            data: vec![1, 2, 3],
+        };
+        let smdpv2_pkt: SmdpPacketV3 = cpkt.clone().try_into().unwrap();
+        assert_eq!(smdpv2_pkt.data(), cpkt.data);
+        assert_eq!(smdpv2_pkt.addr(), cpkt.addr);
+        assert_eq!(smdpv2_pkt.cmd_rsp(), SMDP_OPCODE);
+        assert_eq!(smdpv2_pkt.srlno(), cpkt.srlno.unwrap());
+    }
+    #[test]
+    fn test_cpkt_into_smdpv2_err() {
+        let cpkt = CPacketSmdp {
+            addr: 0x10,
+            srlno: None,
+            data: vec![1, 2, 3],
+        };
+        let result: Result<SmdpPacketV3, _> = cpkt.try_into();
+        assert!(result.is_err());
+    }
+    #[test]
+    fn test_indexed_write_data() {
+        let cpkt = CPacketSmdp::new(0x10, None, RequestType::Write(0x0102), 0x1234, 3);
+        assert_eq!(cpkt.data, [0x61, 0x12, 0x34, 0x03, 0x00, 0x00, 0x01, 0x02]);
+    }
+    #[test]
+    fn test_extract_response() {
+        let header = [0x63, 0x12, 0x34, 0x00];
+        let words = [&header[..], &[0, 0, 0, 1, 0, 0, 0, 2]].concat();
+        let data = CPacketSmdp::extract_response(&words, false).unwrap();
+        assert_eq!(data.words(), [1, 2]);
+        assert_eq!(data.word(), None);
+        let text = [&header[..], b"CP1234\0\0"].concat();
+        let data = CPacketSmdp::extract_response(&text, true).unwrap();
+        assert_eq!(data.text(), Some("CP1234"));
+        assert!(CPacketSmdp::extract_response(&text[..7], false).is_err());
+        assert_eq!(
+            CPacketSmdp::extract_response(&header, false).unwrap(),
+            ResponseData::Empty
+        );
+    }
+    #[test]
+    fn test_extract_data_any_length() {
+        for len in 0..=32 {
+            let data = vec![0xFF; len];
+            assert_eq!(CPacketSmdp::extract_data(&data).is_ok(), len == 8);
+        }
+    }
+    #[test]
+    fn test_is_reply_to() {
+        assert!(CPacketSmdp::is_reply_to(0x81, SMDP_OPCODE));
+        assert!(CPacketSmdp::is_reply_to(SMDP_OPCODE, SMDP_OPCODE));
+        assert!(!CPacketSmdp::is_reply_to(0x91, SMDP_OPCODE));
+        assert!(!CPacketSmdp::is_reply_to(0x01, SMDP_OPCODE));
+        assert!(CPacketSmdp::is_reply_to(0x91, 0x90));
+    }
+    #[test]
+    fn test_raw_opcode() {
+        assert!(CPacketSmdp::check_raw_opcode(0x90).is_ok());
+        assert!(CPacketSmdp::check_raw_opcode(0x91).is_err());
+        assert!(CPacketSmdp::check_raw_opcode(0x00).is_err());
+        assert!(CPacketSmdp::is_reply_to(0x91, 0x90));
+        assert!(!CPacketSmdp::is_reply_to(0x81, 0x90));
+    }
+    #[test]
+    fn test_is_busy_reply() {
+        assert!(CPacketSmdp::is_busy_reply(0x85));
+        assert!(!CPacketSmdp::is_busy_reply(0x81));
+    }
+    #[test]
+    fn test_smdpv1_into_cpkt() {
+        let addr = 0x20;
+        let data = vec![4, 5, 6];
+        let smdpv1_pkt = SmdpPacketV2::new(addr, SMDP_OPCODE, data.clone());
+        let cpkt: CPacketSmdp = smdpv1_pkt.into();
+        assert_eq!(cpkt.addr, addr);
+        assert_eq!(cpkt.data, data);
+        assert_eq!(cpkt.srlno, None);
+    }
+
+    #[test]
+    fn test_smdpv2_into_cpkt() {
+        let addr = 0x30;
+        let srlno = 0x42;
+        let data = vec![7, 8, 9];
+        let smdpv2_pkt = SmdpPacketV3::new(addr, SMDP_OPCODE, srlno, data.clone());
+        let cpkt: CPacketSmdp = smdpv2_pkt.into();
+        assert_eq!(cpkt.addr, addr);
+        assert_eq!(cpkt.data, data);
+        assert_eq!(cpkt.srlno, Some(srlno));
+    }
+}
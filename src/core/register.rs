@@ -0,0 +1,118 @@
+/* Protocol-agnostic register names with their SMDP and Modbus addresses */
+
+use crate::Real;
+
+/// A readable compressor value, independent of the backend. Not every
+/// register exists on both protocols; see `smdp()` and `modbus()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    CompOn,
+    ErrCodeStatus,
+    InputWaterTemp,
+    OutputWaterTemp,
+    HeliumTemp,
+    OilTemp,
+    HighSidePressure,
+    LowSidePressure,
+    AvgHighSidePressure,
+    AvgLowSidePressure,
+    AvgDeltaPressure,
+    HighSidePressureDeriv,
+    MotorCurrent,
+    /// Total compressor run time, in hours.
+    RunHours,
+    CpuTemp,
+    /// Panel operating state code (Modbus only).
+    OperatingState,
+}
+
+/// Location of a register in the SMDP dictionary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SmdpAddr {
+    pub(crate) hashval: u16,
+    pub(crate) array_idx: u8,
+    /// Factor applied to the raw value.
+    pub(crate) scale: Real,
+}
+
+/// Location of a register in the Modbus input register map (1-based).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ModbusAddr {
+    /// Single 16-bit register holding an integer.
+    Word(u16),
+    /// Two registers holding an IEEE float.
+    Float(u16),
+}
+
+const fn smdp(hashval: u16, array_idx: u8, scale: Real) -> Option<SmdpAddr> {
+    Some(SmdpAddr {
+        hashval,
+        array_idx,
+        scale,
+    })
+}
+
+impl Register {
+    /// Dictionary hash, index and scale on the SMDP backend.
+    pub(crate) fn smdp(self) -> Option<SmdpAddr> {
+        match self {
+            Self::CompOn => smdp(0x5F95, 0, 1.0),
+            Self::ErrCodeStatus => smdp(0x65A4, 0, 1.0),
+            Self::InputWaterTemp => smdp(0x0D8F, 0, 0.1),
+            Self::OutputWaterTemp => smdp(0x0D8F, 1, 0.1),
+            Self::HeliumTemp => smdp(0x0D8F, 2, 0.1),
+            Self::OilTemp => smdp(0x0D8F, 3, 0.1),
+            Self::HighSidePressure => smdp(0xAA50, 0, 0.1),
+            Self::LowSidePressure => smdp(0xAA50, 1, 0.1),
+            Self::AvgHighSidePressure => smdp(0x7E90, 0, 0.1),
+            Self::AvgLowSidePressure => smdp(0xBB94, 0, 0.1),
+            Self::AvgDeltaPressure => smdp(0x319C, 0, 0.1),
+            Self::HighSidePressureDeriv => smdp(0x66FA, 0, 0.1),
+            Self::MotorCurrent => smdp(0x638B, 0, 1.0),
+            // Dictionary counts minutes
+            Self::RunHours => smdp(0x454C, 0, 1.0 / 60.0),
+            Self::CpuTemp => smdp(0x3574, 0, 0.1),
+            Self::OperatingState => None,
+        }
+    }
+    /// Input register on the Modbus backend.
+    pub(crate) fn modbus(self) -> Option<ModbusAddr> {
+        match self {
+            Self::OperatingState => Some(ModbusAddr::Word(1)),
+            Self::CompOn => Some(ModbusAddr::Word(2)),
+            Self::InputWaterTemp => Some(ModbusAddr::Float(7)),
+            Self::OutputWaterTemp => Some(ModbusAddr::Float(9)),
+            Self::OilTemp => Some(ModbusAddr::Float(11)),
+            Self::HeliumTemp => Some(ModbusAddr::Float(13)),
+            Self::LowSidePressure => Some(ModbusAddr::Float(15)),
+            Self::AvgLowSidePressure => Some(ModbusAddr::Float(17)),
+            Self::HighSidePressure => Some(ModbusAddr::Float(19)),
+            Self::AvgHighSidePressure => Some(ModbusAddr::Float(21)),
+            Self::AvgDeltaPressure => Some(ModbusAddr::Float(23)),
+            Self::MotorCurrent => Some(ModbusAddr::Float(25)),
+            Self::RunHours => Some(ModbusAddr::Float(27)),
+            Self::ErrCodeStatus | Self::HighSidePressureDeriv | Self::CpuTemp => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shared_registers_map_on_both() {
+        for reg in [
+            Register::CompOn,
+            Register::OilTemp,
+            Register::HeliumTemp,
+            Register::HighSidePressure,
+            Register::LowSidePressure,
+            Register::RunHours,
+        ] {
+            assert!(reg.smdp().is_some() && reg.modbus().is_some(), "{reg:?}");
+        }
+        assert_eq!(Register::OperatingState.smdp(), None);
+        assert_eq!(Register::CpuTemp.modbus(), None);
+    }
+}
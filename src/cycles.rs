@@ -0,0 +1,151 @@
+/* Start/stop cycle counts and duty cycle from the snapshot history */
+
+use crate::{
+    Real,
+    history::{History, HistoryKind},
+};
+use std::{
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+/// Start/stop cycles seen in the history. Transitions are only as precise as
+/// the poll interval; a stop and restart between two polls is not seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CycleCounts {
+    pub starts: u32,
+    pub stops: u32,
+    /// Starts that followed the previous stop by less than the short-cycle
+    /// threshold.
+    pub short_cycles: u32,
+    /// Compressor minutes in the most recent snapshot, for wear per run hour.
+    pub comp_minutes: u32,
+}
+
+impl History {
+    /// Counts start/stop transitions between polled snapshots. A start within
+    /// `short_cycle` of the preceding stop is also counted as a short cycle.
+    pub fn cycles(&self, short_cycle: Duration) -> CycleCounts {
+        let mut counts = CycleCounts::default();
+        let mut prev_on: Option<bool> = None;
+        let mut last_stop: Option<SystemTime> = None;
+        for entry in self.entries().filter(|e| e.kind == HistoryKind::Poll) {
+            let on = entry.status.comp_on;
+            match (prev_on, on) {
+                (Some(false), true) => {
+                    counts.starts += 1;
+                    if last_stop.is_some_and(|stop| {
+                        entry.at.duration_since(stop).unwrap_or_default() < short_cycle
+                    }) {
+                        counts.short_cycles += 1;
+                    }
+                }
+                (Some(true), false) => {
+                    counts.stops += 1;
+                    last_stop = Some(entry.at);
+                }
+                _ => {}
+            }
+            prev_on = Some(on);
+            counts.comp_minutes = entry.status.comp_minutes;
+        }
+        counts
+    }
+    /// Fraction of `range` the compressor was running, from 0.0 to 1.0. Each
+    /// polled state is taken to hold until the next poll, so time after the
+    /// last poll is not covered. None if no part of `range` is covered.
+    pub fn duty_cycle(&self, range: Range<SystemTime>) -> Option<Real> {
+        let polls: Vec<_> = self
+            .entries()
+            .filter(|e| e.kind == HistoryKind::Poll)
+            .map(|e| (e.at, e.status.comp_on))
+            .collect();
+        let (mut on, mut covered) = (Duration::ZERO, Duration::ZERO);
+        for pair in polls.windows(2) {
+            let [(from, comp_on), (to, _)] = pair else {
+                continue;
+            };
+            let start = (*from).max(range.start);
+            let end = (*to).min(range.end);
+            let Ok(len) = end.duration_since(start) else {
+                continue;
+            };
+            covered += len;
+            if *comp_on {
+                on += len;
+            }
+        }
+        (!covered.is_zero()).then(|| (on.as_secs_f64() / covered.as_secs_f64()) as Real)
+    }
+    /// Duty cycle of each consecutive `window` in `range`, keyed by window start.
+    /// Windows without history are skipped.
+    pub fn duty_cycles(
+        &self,
+        range: Range<SystemTime>,
+        window: Duration,
+    ) -> Vec<(SystemTime, Real)> {
+        let mut out = Vec::new();
+        if window.is_zero() {
+            return out;
+        }
+        let mut start = range.start;
+        while start < range.end {
+            let end = (start + window).min(range.end);
+            if let Some(duty) = self.duty_cycle(start..end) {
+                out.push((start, duty));
+            }
+            start = end;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        history::{HistoryEntry, test::status},
+        status::CompressorStatus,
+    };
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_short_cycles() {
+        let mut history = History::new(16);
+        for (minute, comp_on) in [
+            (0, false),
+            (1, true),
+            (30, false),
+            (33, true),
+            (40, false),
+            (60, true),
+        ] {
+            history.push(HistoryEntry {
+                at: UNIX_EPOCH + Duration::from_secs(minute * 60),
+                kind: HistoryKind::Poll,
+                status: CompressorStatus {
+                    comp_on,
+                    comp_minutes: minute as u32,
+                    ..status(0.0)
+                },
+            });
+        }
+        let counts = history.cycles(Duration::from_secs(5 * 60));
+        // On 1-30, 33-40 of the 60 minutes covered
+        let all = UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(3600);
+        let duty = history.duty_cycle(all.clone()).unwrap();
+        assert!((duty - 36.0 / 60.0).abs() < 1e-4);
+        let halves = history.duty_cycles(all, Duration::from_secs(1800));
+        assert_eq!(halves.len(), 2);
+        assert!((halves[0].1 - 29.0 / 30.0).abs() < 1e-4);
+        assert_eq!(
+            counts,
+            CycleCounts {
+                starts: 3,
+                stops: 2,
+                short_cycles: 1,
+                comp_minutes: 60,
+            }
+        );
+    }
+}
@@ -0,0 +1,45 @@
+/* Failure information retained on the API handle for supervisory code */
+
+use crate::Error;
+use std::time::SystemTime;
+
+/// Transaction that was in progress when an error occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionContext {
+    pub hashval: u16,
+    pub array_idx: u8,
+    /// True for dictionary writes, false for reads
+    pub write: bool,
+    /// SRLNO used for the request (SMDP V3+ only)
+    pub srlno: Option<u8>,
+}
+
+/// Most recent error seen by a handle.
+#[derive(Debug, Clone)]
+pub struct ErrorRecord {
+    /// Rendered error message
+    pub message: String,
+    pub at: SystemTime,
+    pub context: TransactionContext,
+}
+
+/// Running transaction counters and the last error seen by a handle.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub transactions: u64,
+    pub failures: u64,
+    pub last_error: Option<ErrorRecord>,
+}
+impl Diagnostics {
+    pub(crate) fn record<T>(&mut self, result: &Result<T, Error>, context: TransactionContext) {
+        self.transactions += 1;
+        if let Err(e) = result {
+            self.failures += 1;
+            self.last_error = Some(ErrorRecord {
+                message: e.to_string(),
+                at: SystemTime::now(),
+                context,
+            });
+        }
+    }
+}
@@ -0,0 +1,193 @@
+/* SMDP dictionary table, generating the typed getters and their metadata */
+
+use crate::{CResult, Real, api::CryomechApiSmdp};
+use serialport::SerialPort;
+
+/// Unit of a dictionary entry's value after scaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Celsius,
+    /// PSI absolute
+    Psia,
+    /// PSI per second
+    PsiPerSecond,
+    Amps,
+    Minutes,
+    /// 1 for true, 0 for false
+    Flag,
+    /// Unitless value, e.g. a checksum
+    Raw,
+}
+
+// Entries the handle reads or writes on its own, outside the getters
+pub(crate) const FW_CHECKSUM_HASH: u16 = 0x2B0D;
+pub(crate) const COMP_MINUTES_HASH: u16 = 0x454C;
+pub(crate) const COMP_ON_HASH: u16 = 0x5F95;
+// Write-only commands
+pub(crate) const START_HASH: u16 = 0xD501;
+pub(crate) const STOP_HASH: u16 = 0xC598;
+pub(crate) const CLEAR_MIN_MAX_HASH: u16 = 0xD3DB;
+
+/// Read-only dictionary entry with a getter on the SMDP handle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DictionaryEntry {
+    /// Name of the getter
+    pub name: &'static str,
+    pub hashval: u16,
+    pub array_idx: u8,
+    /// Factor applied to the raw value by `Real` getters.
+    pub scale: Real,
+    pub unit: Unit,
+}
+
+/// Converts a raw dictionary value to a getter's return type.
+pub(crate) trait FromRaw {
+    fn from_raw(raw: u32, scale: Real) -> Self;
+}
+impl FromRaw for bool {
+    fn from_raw(raw: u32, _scale: Real) -> Self {
+        raw == 1
+    }
+}
+impl FromRaw for u32 {
+    fn from_raw(raw: u32, _scale: Real) -> Self {
+        raw
+    }
+}
+impl FromRaw for Real {
+    fn from_raw(raw: u32, scale: Real) -> Self {
+        raw as Real * scale
+    }
+}
+
+/// Generates a getter per entry and the `DICTIONARY` table describing them.
+macro_rules! registers {
+    ($(
+        $(#[$doc:meta])*
+        $name:ident -> $ty:ty => {
+            hash: $hash:expr, idx: $idx:expr, scale: $scale:expr, unit: $unit:ident
+        }
+    ),* $(,)?) => {
+        impl CryomechApiSmdp<Box<dyn SerialPort>> {
+            $(
+                $(#[$doc])*
+                pub fn $name(&mut self) -> CResult<$ty> {
+                    Ok(<$ty>::from_raw(self.read_entry($hash, $idx)?, $scale))
+                }
+            )*
+        }
+        const DICTIONARY: &[DictionaryEntry] = &[$(
+            DictionaryEntry {
+                name: stringify!($name),
+                hashval: $hash,
+                array_idx: $idx,
+                scale: $scale,
+                unit: Unit::$unit,
+            },
+        )*];
+    };
+}
+
+registers! {
+    /// Firmware checksum
+    fw_checksum -> u32 => { hash: FW_CHECKSUM_HASH, idx: 0, scale: 1.0, unit: Raw },
+    /// True if nonvolatile memory was lost
+    mem_loss -> bool => { hash: 0x801A, idx: 0, scale: 1.0, unit: Flag },
+    /// CPU temperature (°C)
+    cpu_temp -> Real => { hash: 0x3574, idx: 0, scale: 0.1, unit: Celsius },
+    /// True if clock battery OK
+    clock_batt_ok -> bool => { hash: 0xA37A, idx: 0, scale: 1.0, unit: Flag },
+    /// True if clock battery low
+    clock_batt_low -> bool => { hash: 0x0B8B, idx: 0, scale: 1.0, unit: Flag },
+    /// Elapsed compressor minutes
+    comp_minutes -> u32 => { hash: COMP_MINUTES_HASH, idx: 0, scale: 1.0, unit: Minutes },
+    /// Compressor motor current draw, in Amps
+    motor_current_amps -> u32 => { hash: 0x638B, idx: 0, scale: 1.0, unit: Amps },
+    /// In °C
+    input_water_temp -> Real => { hash: 0x0D8F, idx: 0, scale: 0.1, unit: Celsius },
+    /// In °C
+    output_water_temp -> Real => { hash: 0x0D8F, idx: 1, scale: 0.1, unit: Celsius },
+    /// In °C
+    helium_temp -> Real => { hash: 0x0D8F, idx: 2, scale: 0.1, unit: Celsius },
+    /// In °C
+    oil_temp -> Real => { hash: 0x0D8F, idx: 3, scale: 0.1, unit: Celsius },
+    /// In °C
+    min_input_water_temp -> Real => { hash: 0x6E58, idx: 0, scale: 0.1, unit: Celsius },
+    /// In °C
+    min_output_water_temp -> Real => { hash: 0x6E58, idx: 1, scale: 0.1, unit: Celsius },
+    /// In °C
+    min_helium_temp -> Real => { hash: 0x6E58, idx: 2, scale: 0.1, unit: Celsius },
+    /// In °C
+    min_oil_temp -> Real => { hash: 0x6E58, idx: 3, scale: 0.1, unit: Celsius },
+    /// In °C
+    max_input_water_temp -> Real => { hash: 0x8A1C, idx: 0, scale: 0.1, unit: Celsius },
+    /// In °C
+    max_output_water_temp -> Real => { hash: 0x8A1C, idx: 1, scale: 0.1, unit: Celsius },
+    /// In °C
+    max_helium_temp -> Real => { hash: 0x8A1C, idx: 2, scale: 0.1, unit: Celsius },
+    /// In °C
+    max_oil_temp -> Real => { hash: 0x8A1C, idx: 3, scale: 0.1, unit: Celsius },
+    /// True if a temperature sensor has failed
+    temp_sensor_fail -> bool => { hash: 0x6E2D, idx: 0, scale: 1.0, unit: Flag },
+    /// True if a pressure sensor has failed
+    pressure_sensor_fail -> bool => { hash: 0xF82B, idx: 0, scale: 1.0, unit: Flag },
+    /// In PSI Absolute
+    high_side_pressure -> Real => { hash: 0xAA50, idx: 0, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    low_side_pressure -> Real => { hash: 0xAA50, idx: 1, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    max_high_side_pressure -> Real => { hash: 0x7A62, idx: 0, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    max_low_side_pressure -> Real => { hash: 0x7A62, idx: 1, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    min_high_side_pressure -> Real => { hash: 0x5E0B, idx: 0, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    min_low_side_pressure -> Real => { hash: 0x5E0B, idx: 1, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    avg_high_side_pressure -> Real => { hash: 0x7E90, idx: 0, scale: 0.1, unit: Psia },
+    /// In PSI Absolute
+    avg_low_side_pressure -> Real => { hash: 0xBB94, idx: 0, scale: 0.1, unit: Psia },
+    /// Also known as "bounce". In PSI per second
+    high_side_pressure_deriv -> Real => { hash: 0x66FA, idx: 0, scale: 0.1, unit: PsiPerSecond },
+    /// Average difference in High/Low side pressures in PSI Absolute.
+    avg_delta_pressure -> Real => { hash: 0x319C, idx: 0, scale: 0.1, unit: Psia },
+    /// True if the compressor is actively running
+    comp_on -> bool => { hash: COMP_ON_HASH, idx: 0, scale: 1.0, unit: Flag },
+    /// True indicates one or more active errors or warnings.
+    err_code_status -> bool => { hash: 0x65A4, idx: 0, scale: 1.0, unit: Flag },
+}
+
+/// All read-only dictionary entries that have a getter on the SMDP handle.
+pub fn dictionary() -> &'static [DictionaryEntry] {
+    DICTIONARY
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::register::Register;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_entries_unique() {
+        let addrs: HashSet<_> = DICTIONARY
+            .iter()
+            .map(|e| (e.hashval, e.array_idx))
+            .collect();
+        assert_eq!(addrs.len(), DICTIONARY.len());
+    }
+    #[test]
+    fn test_matches_register_map() {
+        let entry = |name| DICTIONARY.iter().find(|e| e.name == name).unwrap();
+        for (name, register) in [
+            ("helium_temp", Register::HeliumTemp),
+            ("low_side_pressure", Register::LowSidePressure),
+            ("comp_on", Register::CompOn),
+        ] {
+            let smdp = register.smdp().unwrap();
+            let e = entry(name);
+            assert_eq!((e.hashval, e.array_idx), (smdp.hashval, smdp.array_idx));
+            assert_eq!(e.scale, smdp.scale);
+        }
+    }
+}
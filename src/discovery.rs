@@ -0,0 +1,127 @@
+/* Serial port lookup by USB adapter identity, independent of device numbering */
+
+use crate::{CResult, Error};
+use serialport::{SerialPortType, UsbPortInfo};
+
+/// USB identity of a serial adapter. Unset fields match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbMatch {
+    vid: Option<u16>,
+    pid: Option<u16>,
+    serial_number: Option<String>,
+}
+impl UsbMatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// USB vendor/product ID of the adapter, e.g. 0x0403/0x6001 for an FTDI FT232R.
+    pub fn vid_pid(mut self, vid: u16, pid: u16) -> Self {
+        self.vid = Some(vid);
+        self.pid = Some(pid);
+        self
+    }
+    /// Serial number reported by the adapter. Unique per adapter for most
+    /// FTDI parts, which makes it the most stable way to pick a port.
+    pub fn serial_number(mut self, serial: &str) -> Self {
+        self.serial_number = Some(serial.into());
+        self
+    }
+    fn matches_ids(&self, vid: u16, pid: u16, serial: Option<&str>) -> bool {
+        self.vid.is_none_or(|v| v == vid)
+            && self.pid.is_none_or(|p| p == pid)
+            && self
+                .serial_number
+                .as_deref()
+                .is_none_or(|s| serial == Some(s))
+    }
+    fn matches(&self, info: &UsbPortInfo) -> bool {
+        self.matches_ids(info.vid, info.pid, info.serial_number.as_deref())
+    }
+}
+
+// USB vendor IDs of the adapters commonly used with compressor panels
+const VID_FTDI: u16 = 0x0403;
+const VID_PROLIFIC: u16 = 0x067B;
+
+/// USB serial port that is likely a compressor adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidatePort {
+    pub port_name: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+/// USB serial ports behind FTDI or Prolific adapters, sorted by name, with
+/// their USB metadata for display in setup tools.
+pub fn list_candidate_ports() -> CResult<Vec<CandidatePort>> {
+    let mut ports: Vec<CandidatePort> = serialport::available_ports()?
+        .into_iter()
+        .filter_map(|p| match p.port_type {
+            SerialPortType::UsbPort(info) if matches!(info.vid, VID_FTDI | VID_PROLIFIC) => {
+                Some(CandidatePort {
+                    port_name: p.port_name,
+                    vid: info.vid,
+                    pid: info.pid,
+                    serial_number: info.serial_number,
+                    manufacturer: info.manufacturer,
+                    product: info.product,
+                })
+            }
+            _ => None,
+        })
+        .collect();
+    ports.sort_by(|a, b| a.port_name.cmp(&b.port_name));
+    Ok(ports)
+}
+
+/// Names of all USB serial ports matching `usb`, sorted by name.
+pub fn find_ports(usb: &UsbMatch) -> CResult<Vec<String>> {
+    let mut ports: Vec<String> = serialport::available_ports()?
+        .into_iter()
+        .filter(|p| matches!(&p.port_type, SerialPortType::UsbPort(info) if usb.matches(info)))
+        .map(|p| p.port_name)
+        .collect();
+    ports.sort();
+    Ok(ports)
+}
+
+/// Serial number of the USB adapter behind `port`, if it is a USB port and
+/// reports one.
+pub(crate) fn usb_serial(port: &str) -> CResult<Option<String>> {
+    Ok(serialport::available_ports()?
+        .into_iter()
+        .find(|p| p.port_name == port)
+        .and_then(|p| match p.port_type {
+            SerialPortType::UsbPort(info) => info.serial_number,
+            _ => None,
+        }))
+}
+
+/// Name of the port matching `usb`. If several match, the first by name is
+/// returned; narrow the match with a serial number to avoid that.
+pub fn find_port(usb: &UsbMatch) -> CResult<String> {
+    find_ports(usb)?
+        .into_iter()
+        .next()
+        .ok_or(Error::PortNotFound)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_usb_match() {
+        assert!(UsbMatch::new().matches_ids(0x0403, 0x6001, None));
+        let ftdi = UsbMatch::new().vid_pid(0x0403, 0x6001);
+        assert!(ftdi.matches_ids(0x0403, 0x6001, Some("A10K5XYZ")));
+        assert!(!ftdi.matches_ids(0x067B, 0x2303, None));
+        let unit = ftdi.serial_number("A10K5XYZ");
+        assert!(unit.matches_ids(0x0403, 0x6001, Some("A10K5XYZ")));
+        assert!(!unit.matches_ids(0x0403, 0x6001, Some("A10K5ABC")));
+        assert!(!unit.matches_ids(0x0403, 0x6001, None));
+    }
+}
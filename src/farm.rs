@@ -0,0 +1,207 @@
+/* Group of compressors, each on its own serial port, operated together */
+
+use crate::{CResult, ControlOutcome, Error, Real, api::CryomechApiSmdp, status::CompressorStatus};
+use serialport::SerialPort;
+use std::time::Duration;
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+
+/// Status of every unit in a farm plus fleet-wide rollups.
+#[derive(Debug, Clone)]
+pub struct FarmSnapshot {
+    /// Per unit, in farm order. Rendered error if the unit could not be read.
+    pub units: Vec<(String, Result<CompressorStatus, String>)>,
+    /// True if any unit reports an error or a failed sensor.
+    pub any_faulted: bool,
+    /// Units that could not be read.
+    pub unreachable: usize,
+    /// Units whose compressor is running.
+    pub running: usize,
+    /// Unit with the highest output water temperature, and that temperature.
+    pub worst_water_temp: Option<(String, Real)>,
+}
+impl FarmSnapshot {
+    fn from_units(units: Vec<(String, Result<CompressorStatus, String>)>) -> Self {
+        let read = || {
+            units
+                .iter()
+                .filter_map(|(n, s)| Some((n, s.as_ref().ok()?)))
+        };
+        let any_faulted =
+            read().any(|(_, s)| s.err_code_status || s.temp_sensor_fail || s.pressure_sensor_fail);
+        let running = read().filter(|(_, s)| s.comp_on).count();
+        let worst_water_temp = read()
+            .max_by(|(_, a), (_, b)| a.output_water_temp.total_cmp(&b.output_water_temp))
+            .map(|(n, s)| (n.clone(), s.output_water_temp));
+        let unreachable = units.iter().filter(|(_, s)| s.is_err()).count();
+        Self {
+            units,
+            any_faulted,
+            unreachable,
+            running,
+            worst_water_temp,
+        }
+    }
+}
+
+/// Outcome of `CompressorFarm::stop_all()` for one unit.
+#[derive(Debug)]
+pub struct UnitStop {
+    /// Run state read before the stop. A failed read doesn't hold the stop
+    /// back.
+    pub was_running: CResult<bool>,
+    /// The stop writes, as sent by `emergency_stop()`.
+    pub stop: CResult<()>,
+}
+
+/// Named set of API handles, one per compressor.
+#[derive(Debug, Default)]
+pub struct CompressorFarm {
+    units: Vec<(String, SmdpApi)>,
+}
+impl CompressorFarm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a compressor. Units are operated in the order they were added.
+    /// The unit name becomes the handle's name label unless it already has one.
+    pub fn add(&mut self, name: &str, mut api: SmdpApi) {
+        if api.labels().name.is_none() {
+            api.set_labels(api.labels().clone().name(name));
+        }
+        self.units.push((name.into(), api));
+    }
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+    /// Handle of the named unit.
+    pub fn unit_mut(&mut self, name: &str) -> Option<&mut SmdpApi> {
+        self.units
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, api)| api)
+    }
+    /// Reads every unit with `read_all()`. A unit that fails to read is
+    /// reported as such and left out of the rollups.
+    pub fn snapshot(&mut self) -> FarmSnapshot {
+        let units = self
+            .units
+            .iter_mut()
+            .map(|(name, api)| (name.clone(), api.read_all().map_err(|e| e.to_string())))
+            .collect();
+        FarmSnapshot::from_units(units)
+    }
+    /// Stops every unit for a facility shutdown. The stop goes out like
+    /// `emergency_stop()`, whatever the run state read returns and without
+    /// write gates. A failure on one unit doesn't stop the others from being
+    /// stopped.
+    pub fn stop_all(&mut self) -> Vec<(String, UnitStop)> {
+        self.units
+            .iter_mut()
+            .map(|(name, api)| {
+                let was_running = api.comp_on();
+                let stop = api.emergency_stop();
+                (name.clone(), UnitStop { was_running, stop })
+            })
+            .collect()
+    }
+    /// Starts the named units in the given order with
+    /// `start_compressor_checked()`. Each gets its own result; unknown names
+    /// fail with `Error::UnknownUnit`.
+    pub fn start_group(&mut self, names: &[&str]) -> Vec<(String, CResult<ControlOutcome>)> {
+        self.for_group(names, SmdpApi::start_compressor_checked)
+    }
+    /// Stops the named units in the given order, like `start_group()`.
+    pub fn stop_group(&mut self, names: &[&str]) -> Vec<(String, CResult<ControlOutcome>)> {
+        self.for_group(names, SmdpApi::stop_compressor)
+    }
+    fn for_group(
+        &mut self,
+        names: &[&str],
+        op: fn(&mut SmdpApi) -> CResult<ControlOutcome>,
+    ) -> Vec<(String, CResult<ControlOutcome>)> {
+        names
+            .iter()
+            .map(|&name| {
+                let result = match self.unit_mut(name) {
+                    Some(api) => op(api),
+                    None => Err(Error::UnknownUnit(name.into())),
+                };
+                (name.into(), result)
+            })
+            .collect()
+    }
+    /// Starts the units one after another with `start_compressor_checked()`,
+    /// waiting `delay` after each unit that was actually started so their
+    /// inrush currents don't overlap. Units that fail, are refused by their
+    /// interlock or are already running don't hold up the rest.
+    pub fn start_all_staggered(
+        &mut self,
+        delay: Duration,
+    ) -> Vec<(String, CResult<ControlOutcome>)> {
+        let mut results = Vec::with_capacity(self.units.len());
+        let count = self.units.len();
+        for (i, (name, api)) in self.units.iter_mut().enumerate() {
+            let result = api.start_compressor_checked();
+            let started = matches!(result, Ok(o) if o != ControlOutcome::AlreadyInState);
+            results.push((name.clone(), result));
+            if started && i + 1 < count {
+                api.clock().sleep(delay);
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api::CryomechApiSmdpBuilder, history::test::status, link::test::NullPort};
+
+    #[test]
+    fn test_rollups() {
+        let warm = CompressorStatus {
+            output_water_temp: 35.0,
+            ..status(0.0)
+        };
+        let faulted = CompressorStatus {
+            comp_on: false,
+            temp_sensor_fail: true,
+            output_water_temp: 30.0,
+            ..status(0.0)
+        };
+        let snap = FarmSnapshot::from_units(vec![
+            ("a".into(), Ok(warm)),
+            ("b".into(), Ok(faulted)),
+            ("c".into(), Err("timeout".into())),
+        ]);
+        assert!(snap.any_faulted);
+        assert_eq!((snap.running, snap.unreachable), (1, 1));
+        assert_eq!(snap.worst_water_temp, Some(("a".into(), 35.0)));
+    }
+    #[test]
+    fn test_group_unknown_unit() {
+        let results = CompressorFarm::new().start_group(&["cryo-a"]);
+        assert!(matches!(&results[..], [(name, Err(Error::UnknownUnit(_)))] if name == "cryo-a"));
+    }
+    #[test]
+    fn test_stop_all_after_failed_read() {
+        let mut farm = CompressorFarm::new();
+        let api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .build_on(NullPort::boxed());
+        farm.add("cryo-a", api);
+        let results = farm.stop_all();
+        let [(_, unit)] = &results[..] else {
+            panic!("one result per unit");
+        };
+        assert!(unit.was_running.is_err());
+        // The stop writes were still sent after the read timed out
+        let api = farm.unit_mut("cryo-a").unwrap();
+        assert_eq!(api.diagnostics().transactions, 4);
+    }
+}
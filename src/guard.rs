@@ -0,0 +1,61 @@
+/* Handle wrapper that leaves the compressor in a safe state when dropped */
+
+use crate::api::CryomechApiSmdp;
+use serialport::SerialPort;
+use std::ops::{Deref, DerefMut};
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+
+/// What an `ApiGuard` does with the compressor when it is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnDrop {
+    /// Leave the compressor as it is.
+    #[default]
+    Nothing,
+    /// Send a single stop command, e.g. for unattended test rigs. Skips the
+    /// run state check, verification, write arming and control tokens.
+    StopCompressor,
+}
+
+/// API handle that applies an `OnDrop` action when it goes out of scope,
+/// including during a panic unwind. Derefs to the wrapped handle.
+#[derive(Debug)]
+pub struct ApiGuard {
+    api: SmdpApi,
+    on_drop: OnDrop,
+}
+impl ApiGuard {
+    pub fn new(api: SmdpApi, on_drop: OnDrop) -> Self {
+        Self { api, on_drop }
+    }
+    /// Leaves the compressor alone on drop from now on.
+    pub fn disarm(&mut self) {
+        self.on_drop = OnDrop::Nothing;
+    }
+}
+impl Deref for ApiGuard {
+    type Target = SmdpApi;
+    fn deref(&self) -> &SmdpApi {
+        &self.api
+    }
+}
+impl DerefMut for ApiGuard {
+    fn deref_mut(&mut self) -> &mut SmdpApi {
+        &mut self.api
+    }
+}
+impl Drop for ApiGuard {
+    fn drop(&mut self) {
+        match self.on_drop {
+            OnDrop::Nothing => {}
+            // Nowhere to report a failure from here
+            OnDrop::StopCompressor => {
+                tracing::warn!(
+                    device = self.api.device_name(),
+                    "stopping compressor on drop"
+                );
+                let _ = self.api.send_stop();
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+/* In-memory history of compressor snapshots */
+
+use crate::{
+    Real,
+    clock::{Clock, SystemClock},
+    core::register::Register,
+    poller::ReadingSink,
+    status::CompressorStatus,
+};
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Why a snapshot was stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryKind {
+    /// Regular reading from the poller.
+    Poll,
+    /// Values read just before a scheduled min/max reset.
+    MinMaxArchive,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub at: SystemTime,
+    pub kind: HistoryKind,
+    pub status: CompressorStatus,
+}
+
+/// Bounded history of snapshots, oldest first. Once full, the oldest entry is
+/// dropped for each new one.
+#[derive(Debug)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    clock: Arc<dyn Clock>,
+}
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity.min(4096)),
+            capacity,
+            clock: Arc::new(SystemClock),
+        }
+    }
+    /// Timestamps entries from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Stores a snapshot, timestamped now.
+    pub fn record(&mut self, kind: HistoryKind, status: CompressorStatus) {
+        let at = self.clock.system_time();
+        self.push(HistoryEntry { at, kind, status });
+    }
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+    /// Empties the history and returns its entries, oldest first. The
+    /// capacity and clock are kept.
+    pub fn reset(&mut self) -> Vec<HistoryEntry> {
+        self.entries.drain(..).collect()
+    }
+    /// Polled values of `register` within `range`, reduced to at most
+    /// `max_points` for plotting. When reduced, the range is split into equal
+    /// time buckets and each non-empty bucket becomes its mean time and value.
+    pub fn series(
+        &self,
+        register: Register,
+        range: Range<SystemTime>,
+        max_points: usize,
+    ) -> Vec<(SystemTime, Real)> {
+        let points: Vec<_> = self
+            .entries()
+            .filter(|e| e.kind == HistoryKind::Poll && range.contains(&e.at))
+            .filter_map(|e| Some((e.at, e.status.value(register)?)))
+            .collect();
+        if points.len() <= max_points {
+            return points;
+        }
+        let span = range
+            .end
+            .duration_since(range.start)
+            .unwrap_or_default()
+            .as_secs_f64();
+        // (sum of offsets in s, sum of values, count) per bucket
+        let mut buckets = vec![(0.0f64, 0.0 as Real, 0u32); max_points];
+        for (at, value) in points {
+            let offset = at
+                .duration_since(range.start)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let idx = ((offset / span * max_points as f64) as usize).min(max_points - 1);
+            if let Some(bucket) = buckets.get_mut(idx) {
+                bucket.0 += offset;
+                bucket.1 += value;
+                bucket.2 += 1;
+            }
+        }
+        buckets
+            .into_iter()
+            .filter(|(_, _, n)| *n > 0)
+            .map(|(t, v, n)| {
+                let at = range.start + Duration::from_secs_f64(t / f64::from(n));
+                (at, v / n as Real)
+            })
+            .collect()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// History shared between the poller thread and the application.
+pub type SharedHistory = Arc<Mutex<History>>;
+
+/// Records each poller reading as a `HistoryKind::Poll` entry.
+impl ReadingSink<CompressorStatus> for SharedHistory {
+    fn on_snapshot(&mut self, value: &CompressorStatus) {
+        self.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(HistoryKind::Poll, value.clone());
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    pub(crate) fn status(helium_temp: f32) -> CompressorStatus {
+        CompressorStatus {
+            comp_on: true,
+            err_code_status: false,
+            mem_loss: false,
+            clock_batt_ok: true,
+            clock_batt_low: false,
+            temp_sensor_fail: false,
+            pressure_sensor_fail: false,
+            comp_minutes: 0,
+            motor_current_amps: 0,
+            cpu_temp: 0.0,
+            input_water_temp: 0.0,
+            output_water_temp: 0.0,
+            helium_temp: helium_temp as _,
+            oil_temp: 0.0,
+            min_input_water_temp: 0.0,
+            min_output_water_temp: 0.0,
+            min_helium_temp: 0.0,
+            min_oil_temp: 0.0,
+            max_input_water_temp: 0.0,
+            max_output_water_temp: 0.0,
+            max_helium_temp: 0.0,
+            max_oil_temp: 0.0,
+            high_side_pressure: 0.0,
+            low_side_pressure: 0.0,
+            max_high_side_pressure: 0.0,
+            max_low_side_pressure: 0.0,
+            min_high_side_pressure: 0.0,
+            min_low_side_pressure: 0.0,
+            avg_high_side_pressure: 0.0,
+            avg_low_side_pressure: 0.0,
+            high_side_pressure_deriv: 0.0,
+            avg_delta_pressure: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_series_downsampling() {
+        let mut history = History::new(100);
+        for i in 0..10u64 {
+            history.push(HistoryEntry {
+                at: UNIX_EPOCH + Duration::from_secs(i),
+                kind: HistoryKind::Poll,
+                status: status(i as f32),
+            });
+        }
+        let range = UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(10);
+        assert_eq!(
+            history
+                .series(Register::HeliumTemp, range.clone(), 20)
+                .len(),
+            10
+        );
+        let reduced = history.series(Register::HeliumTemp, range, 2);
+        assert_eq!(reduced.len(), 2);
+        assert_eq!(reduced[0], (UNIX_EPOCH + Duration::from_secs(2), 2.0));
+        assert_eq!(reduced[1].1, 7.0);
+        let narrow = UNIX_EPOCH + Duration::from_secs(8)..UNIX_EPOCH + Duration::from_secs(20);
+        assert_eq!(history.series(Register::HeliumTemp, narrow, 5).len(), 2);
+        assert!(
+            history
+                .series(
+                    Register::OperatingState,
+                    UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(10),
+                    5
+                )
+                .is_empty()
+        );
+    }
+    #[test]
+    fn test_bounded_history() {
+        let clock = Arc::new(MockClock::new(UNIX_EPOCH));
+        let mut history = History::new(2).with_clock(clock.clone());
+        history.record(HistoryKind::Poll, status(1.0));
+        clock.advance(Duration::from_secs(60));
+        history.record(HistoryKind::MinMaxArchive, status(2.0));
+        history.record(HistoryKind::Poll, status(3.0));
+        let temps: Vec<_> = history.entries().map(|e| e.status.helium_temp).collect();
+        assert_eq!(temps, [2.0, 3.0]);
+        assert_eq!(
+            history.entries().next().unwrap().at,
+            UNIX_EPOCH + Duration::from_secs(60)
+        );
+        assert_eq!(history.reset().len(), 2);
+        assert!(history.is_empty());
+        history.record(HistoryKind::Poll, status(4.0));
+        assert_eq!(history.len(), 1);
+    }
+}
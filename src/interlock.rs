@@ -0,0 +1,198 @@
+/* Pre-start safety checks against configurable limits */
+
+#[cfg(feature = "policy")]
+use crate::CResult;
+use crate::Real;
+use std::{fmt, ops::RangeInclusive};
+
+/// Limits checked by `start_compressor_checked()` before the compressor is
+/// started. The defaults are deliberately wide; tighten them from the
+/// compressor's datasheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterlockLimits {
+    /// Allowed static (stopped) helium pressure on both sides, PSI absolute.
+    pub static_pressure: RangeInclusive<Real>,
+    /// Allowed cooling water inlet temperature, °C.
+    pub input_water_temp: RangeInclusive<Real>,
+    /// Refuse to start while a temperature or pressure sensor reports a fault.
+    pub sensor_flags: bool,
+}
+impl Default for InterlockLimits {
+    fn default() -> Self {
+        Self {
+            static_pressure: 150.0..=350.0,
+            input_water_temp: 5.0..=30.0,
+            sensor_flags: true,
+        }
+    }
+}
+
+/// Values read from the compressor for the interlock check.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InterlockReadings {
+    pub(crate) high_side_pressure: Real,
+    pub(crate) low_side_pressure: Real,
+    pub(crate) input_water_temp: Real,
+    pub(crate) temp_sensor_fail: bool,
+    pub(crate) pressure_sensor_fail: bool,
+}
+
+/// A single failed interlock check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterlockFailure {
+    HighSidePressure(Real),
+    LowSidePressure(Real),
+    InputWaterTemp(Real),
+    TempSensorFail,
+    PressureSensorFail,
+}
+impl fmt::Display for InterlockFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HighSidePressure(p) => write!(f, "high side pressure {p} PSIA out of range"),
+            Self::LowSidePressure(p) => write!(f, "low side pressure {p} PSIA out of range"),
+            Self::InputWaterTemp(t) => write!(f, "input water temperature {t} °C out of range"),
+            Self::TempSensorFail => f.write_str("temperature sensor fault"),
+            Self::PressureSensorFail => f.write_str("pressure sensor fault"),
+        }
+    }
+}
+
+/// All interlock checks that failed, in check order. Never empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterlockViolation(pub Vec<InterlockFailure>);
+impl fmt::Display for InterlockViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, failure) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{failure}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Interlock section of a site policy file. Missing keys keep their defaults.
+///
+/// ```toml
+/// min_static_pressure = 180.0
+/// max_static_pressure = 300.0
+/// max_water_in_temp = 27.0
+/// ```
+#[cfg(feature = "policy")]
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    min_static_pressure: Option<Real>,
+    max_static_pressure: Option<Real>,
+    min_water_in_temp: Option<Real>,
+    max_water_in_temp: Option<Real>,
+    sensor_flags: Option<bool>,
+}
+
+#[cfg(feature = "policy")]
+impl InterlockLimits {
+    /// Limits from a TOML policy, see `from_file()`.
+    pub fn from_toml(policy: &str) -> CResult<Self> {
+        let file: PolicyFile = toml::from_str(policy)?;
+        let d = Self::default();
+        Ok(Self {
+            static_pressure: file
+                .min_static_pressure
+                .unwrap_or(*d.static_pressure.start())
+                ..=file.max_static_pressure.unwrap_or(*d.static_pressure.end()),
+            input_water_temp: file
+                .min_water_in_temp
+                .unwrap_or(*d.input_water_temp.start())
+                ..=file.max_water_in_temp.unwrap_or(*d.input_water_temp.end()),
+            sensor_flags: file.sensor_flags.unwrap_or(d.sensor_flags),
+        })
+    }
+    /// Limits from a TOML policy file with the keys `min_static_pressure`,
+    /// `max_static_pressure` (PSI absolute), `min_water_in_temp`,
+    /// `max_water_in_temp` (°C) and `sensor_flags`. Missing keys keep their
+    /// default value, unknown keys are an error.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> CResult<Self> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+}
+
+impl InterlockLimits {
+    pub(crate) fn check(&self, r: &InterlockReadings) -> Result<(), InterlockViolation> {
+        let mut failures = Vec::new();
+        if !self.static_pressure.contains(&r.high_side_pressure) {
+            failures.push(InterlockFailure::HighSidePressure(r.high_side_pressure));
+        }
+        if !self.static_pressure.contains(&r.low_side_pressure) {
+            failures.push(InterlockFailure::LowSidePressure(r.low_side_pressure));
+        }
+        if !self.input_water_temp.contains(&r.input_water_temp) {
+            failures.push(InterlockFailure::InputWaterTemp(r.input_water_temp));
+        }
+        if self.sensor_flags && r.temp_sensor_fail {
+            failures.push(InterlockFailure::TempSensorFail);
+        }
+        if self.sensor_flags && r.pressure_sensor_fail {
+            failures.push(InterlockFailure::PressureSensorFail);
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(InterlockViolation(failures))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn readings() -> InterlockReadings {
+        InterlockReadings {
+            high_side_pressure: 250.0,
+            low_side_pressure: 249.0,
+            input_water_temp: 18.0,
+            temp_sensor_fail: false,
+            pressure_sensor_fail: false,
+        }
+    }
+
+    #[test]
+    fn test_interlock_ok() {
+        assert_eq!(InterlockLimits::default().check(&readings()), Ok(()));
+    }
+    #[test]
+    fn test_interlock_violations() {
+        let r = InterlockReadings {
+            low_side_pressure: 90.0,
+            input_water_temp: 35.0,
+            pressure_sensor_fail: true,
+            ..readings()
+        };
+        assert_eq!(
+            InterlockLimits::default().check(&r),
+            Err(InterlockViolation(vec![
+                InterlockFailure::LowSidePressure(90.0),
+                InterlockFailure::InputWaterTemp(35.0),
+                InterlockFailure::PressureSensorFail,
+            ]))
+        );
+        let limits = InterlockLimits {
+            sensor_flags: false,
+            ..Default::default()
+        };
+        assert!(limits.check(&r).unwrap_err().0.len() == 2);
+    }
+    #[cfg(feature = "policy")]
+    #[test]
+    fn test_policy_from_toml() {
+        let limits =
+            InterlockLimits::from_toml("max_water_in_temp = 27.0\nmin_static_pressure = 180.0\n")
+                .unwrap();
+        assert_eq!(limits.input_water_temp, 5.0..=27.0);
+        assert_eq!(limits.static_pressure, 180.0..=350.0);
+        assert!(limits.sensor_flags);
+        assert!(InterlockLimits::from_toml("max_water_temp = 27.0").is_err());
+    }
+}
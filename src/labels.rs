@@ -0,0 +1,71 @@
+/* User metadata attached to a device handle */
+
+use std::collections::BTreeMap;
+
+/// Describes which compressor a handle talks to. Carried into audit entries,
+/// watchdog events and poller output so logs and alerts identify the unit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceLabels {
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub cryostat: Option<String>,
+    pub owner: Option<String>,
+    /// Any further key/value metadata.
+    pub extra: BTreeMap<String, String>,
+}
+impl DeviceLabels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+    pub fn location(mut self, location: &str) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+    pub fn cryostat(mut self, cryostat: &str) -> Self {
+        self.cryostat = Some(cryostat.into());
+        self
+    }
+    pub fn owner(mut self, owner: &str) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.extra.insert(key.into(), value.into());
+        self
+    }
+    /// All set labels as key/value pairs, e.g. for metrics tags. The named
+    /// fields come first, then `extra` in key order.
+    pub fn tags(&self) -> Vec<(&str, &str)> {
+        [
+            ("name", &self.name),
+            ("location", &self.location),
+            ("cryostat", &self.cryostat),
+            ("owner", &self.owner),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| Some((k, v.as_deref()?)))
+        .chain(self.extra.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let labels = DeviceLabels::new()
+            .name("cryo-a")
+            .owner("lab 2")
+            .tag("rack", "R4");
+        assert_eq!(
+            labels.tags(),
+            [("name", "cryo-a"), ("owner", "lab 2"), ("rack", "R4")]
+        );
+    }
+}
@@ -1,19 +1,238 @@
-pub mod api;
-mod packet;
+// For the core module, which avoids std paths
+extern crate alloc;
 
-pub use api::{CryomechApiSmdpBuilder, SmdpVersion};
-use smdp;
+mod alarm;
+pub mod api;
+mod audit;
+mod bounce;
+pub mod bus;
+mod cache;
+mod capability;
+mod capture;
+mod clock;
+mod control;
+mod cooling;
+mod core;
+mod cycles;
+pub mod diagnostics;
+mod dictionary;
+pub mod discovery;
+mod farm;
+mod guard;
+mod history;
+mod interlock;
+mod labels;
+mod link;
+pub mod modbus;
+mod model;
+mod plan;
+pub mod poller;
+mod power;
+#[cfg(feature = "proto")]
+pub mod proto;
+mod provenance;
+mod reboot;
+mod registry;
+mod report;
+mod schedule;
+mod shutdown;
+mod sink;
+mod smoothing;
+mod startup;
+mod stats;
+mod status;
+mod units;
+mod verify;
+mod watchdog;
 
-use serialport;
+pub use crate::core::{codec, packet::ResponseData, register::Register};
+pub use alarm::{
+    Alarm, AlarmBoard, AlarmKind, AlarmSink, MaintenanceEntry, Severity, SharedAlarms,
+};
+pub use api::{
+    BROADCAST_ADDR, ControlOutcome, CryomechApiSmdpBuilder, ExtraFramePolicy, RxPolling,
+    SmdpVersion, VerifyPolicy,
+};
+pub use audit::{AuditAction, AuditEntry, AuditSink};
+pub use bounce::BounceWindow;
+pub use bus::SharedBus;
+pub use cache::CacheTtl;
+pub use capability::{Capability, Feature};
+pub use capture::{CaptureFormat, SessionCapture};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use control::{Control, ControlToken};
+pub use cooling::{CoolingDiagnosis, CoolingLimits};
+pub use cycles::CycleCounts;
+pub use dictionary::{DictionaryEntry, Unit, dictionary};
+pub use discovery::{CandidatePort, UsbMatch};
+pub use farm::{CompressorFarm, FarmSnapshot, UnitStop};
+pub use guard::{ApiGuard, OnDrop};
+pub use history::{History, HistoryEntry, HistoryKind, SharedHistory};
+pub use interlock::{InterlockFailure, InterlockLimits, InterlockViolation};
+pub use labels::DeviceLabels;
+pub use link::{Direction, FrameDump, LinkDeadEvent};
+pub use modbus::{CryomechApiModbusTcpBuilder, WordOrder};
+pub use model::CompressorModel;
+pub use plan::{PollPlan, Priority};
+pub use poller::{AdaptiveRate, PollerBuilder, PollerEvent, ReadingSink, Steady};
+pub use power::{Phases, PowerEstimator};
+pub use provenance::{CRATE_VERSION, Provenance};
+pub use reboot::{DeviceRebooted, RebootSignature};
+pub use registry::{PortRecord, PortRegistry};
+pub use report::{DailySummary, MinMax, ReportFormat};
+pub use schedule::{Schedule, ScheduleEntry, ScheduledAction, ScheduledRun, Weekday};
+pub use shutdown::{ShutdownCheck, ShutdownReport};
+pub use sink::Backpressure;
+pub use smoothing::{Filter, RegisterValues, Smoothed, Smoothing};
+pub use startup::{StartCheck, StartFailure, StartReport};
+pub use stats::{AutoTimeout, LatencyHistogram, LinkStats};
+pub use status::{CompressorStatus, Tolerances};
 use thiserror::Error;
+pub use units::{PSI_TO_BAR, PSI_TO_KPA, Psia, Psig};
+pub use verify::WriteVerifier;
+pub use watchdog::{TempWatchdog, WatchdogEvent};
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("{0}")]
-    InvalidFormat(String),
+    InvalidFormat(&'static str),
+    #[error("Response address {got:#04X} does not match device address {expected:#04X}")]
+    AddressMismatch { expected: u8, got: u8 },
+    #[error("Unexpected opcode in response: {0:#04X}")]
+    UnexpectedOpcode(u8),
+    #[error("RSP not OK: {0:?}")]
+    ResponseNotOk(smdp::format::ResponseCode),
     #[error(transparent)]
     Smdp(#[from] smdp::Error),
     #[error(transparent)]
     Serial(#[from] serialport::Error),
+    #[error("Device busy, command inhibited")]
+    DeviceBusy,
+    #[error("Received {0} unexpected extra frame(s) after the reply")]
+    ExtraFrames(usize),
+    #[error("{source}; frames: {dump}")]
+    WithFrames { source: Box<Error>, dump: FrameDump },
+    #[error("Start refused by interlock: {0}")]
+    Interlock(InterlockViolation),
+    #[error("Writes are not armed, call arm_writes() first")]
+    WritesNotArmed,
+    #[error("Write requires this handle's control token")]
+    ControlTokenRequired,
+    #[error("Write refused while the compressor reports an active fault")]
+    ActiveFault,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "policy")]
+    #[error("Invalid policy file: {0}")]
+    Policy(#[from] toml::de::Error),
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    #[error("Snapshot encoding: {0}")]
+    Encoding(String),
+    #[error("No serial port matches the requested USB identity")]
+    PortNotFound,
+    #[error("{0:?} is not available on this backend")]
+    UnsupportedRegister(Register),
+    #[error("Modbus exception code {0:#04X}")]
+    ModbusException(u8),
+    #[error("No unit named {0:?} in the farm")]
+    UnknownUnit(String),
+    #[error("Bus worker has stopped")]
+    BusClosed,
+    #[error("No bytes received in {0} consecutive transactions, link is dead")]
+    LinkDead(u32),
+    #[error("Condition not met within {0:?}")]
+    WaitTimeout(std::time::Duration),
+    #[error("Devices don't reply to the broadcast address, it can only be written")]
+    BroadcastRead,
+    /// A V3+ reply carried another SRLNO than its request, typically because
+    /// the panel restarted its sequence after a reboot.
+    #[error("Reply SRLNO does not match the request")]
+    SrlnoMismatch,
+}
+impl Error {
+    /// True for errors caused by what was (or wasn't) on the wire, as opposed
+    /// to local serial port failures.
+    pub(crate) fn is_protocol(&self) -> bool {
+        matches!(
+            self,
+            Self::InvalidFormat(_)
+                | Self::AddressMismatch { .. }
+                | Self::UnexpectedOpcode(_)
+                | Self::ResponseNotOk(_)
+                | Self::Smdp(_)
+                | Self::SrlnoMismatch
+        )
+    }
+    /// The error itself, or the one wrapped with its frames.
+    pub(crate) fn inner(&self) -> &Self {
+        match self {
+            Self::WithFrames { source, .. } => source.inner(),
+            _ => self,
+        }
+    }
+    /// True if the device rejected the request, as it does for dictionary
+    /// entries or registers it doesn't have.
+    pub(crate) fn is_nak(&self) -> bool {
+        match self {
+            Self::ResponseNotOk(_) | Self::ModbusException(_) => true,
+            Self::WithFrames { source, .. } => source.is_nak(),
+            _ => false,
+        }
+    }
 }
 pub(crate) type CResult<T> = Result<T, Error>;
+
+/// Floating point type of scaled readings (temperatures, pressures): `f32`, or
+/// `f64` with the `f64` feature.
+#[cfg(not(feature = "f64"))]
+pub type Real = f32;
+#[cfg(feature = "f64")]
+pub type Real = f64;
+
+/// Not part of the public API. Exposes the packet encode/decode path to the
+/// benchmarks in `benches/` and the fuzz targets in `fuzz/`.
+#[doc(hidden)]
+pub mod internals {
+    use crate::{
+        CResult,
+        api::{CryomechApiSmdpBuilder, SmdpVersion},
+        core::packet::{CPacketSmdp, RequestType},
+        dictionary::FW_CHECKSUM_HASH,
+        link::ReplayPort,
+    };
+    use smdp::{SmdpPacketV2, SmdpPacketV3};
+
+    pub fn encode_read_v2(addr: u8, hashval: u16, array_idx: u8) -> SmdpPacketV2 {
+        CPacketSmdp::new(addr, None, RequestType::Read, hashval, array_idx).into()
+    }
+    pub fn encode_read_v3(
+        addr: u8,
+        srlno: u8,
+        hashval: u16,
+        array_idx: u8,
+    ) -> CResult<SmdpPacketV3> {
+        CPacketSmdp::new(addr, Some(srlno), RequestType::Read, hashval, array_idx).try_into()
+    }
+    pub fn decode_reply_data(data: &[u8]) -> CResult<u32> {
+        CPacketSmdp::extract_data(data)
+    }
+    /// Reads the firmware checksum from a handle whose port plays back `rx`,
+    /// through the receive path and reply checks of a device read. With
+    /// `text`, it is read as a string entry through the raw path instead.
+    pub fn read_replayed(rx: &[u8], version: SmdpVersion, text: bool) -> CResult<()> {
+        let mut api = CryomechApiSmdpBuilder::new("replay")
+            .version(version)
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .build_on(ReplayPort::boxed(rx));
+        if text {
+            api.read_string_register(FW_CHECKSUM_HASH, 4).map(|_| ())
+        } else {
+            api.fw_checksum().map(|_| ())
+        }
+    }
+    /// Decodes a buffered Modbus read reply to transaction 1 from unit 1.
+    pub fn decode_modbus_reply(frame: &[u8], count: u16) -> CResult<Vec<u16>> {
+        crate::modbus::decode_read_reply(frame, 1, 1, count)
+    }
+}
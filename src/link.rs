@@ -0,0 +1,481 @@
+/* Serial port wrapper that lets the API layer observe the health of the underlying link */
+// The comm path must never panic on device input
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::indexing_slicing
+    )
+)]
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Read, Write},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::{CResult, labels::DeviceLabels};
+
+// errno values reported on Unix when a USB serial adapter disappears
+const EIO: i32 = 5;
+const ENXIO: i32 = 6;
+const ENODEV: i32 = 19;
+// Upper bound on captured bytes per direction, keeps error payloads small
+const MAX_CAPTURE_BYTES: usize = 128;
+
+/// Raw bytes sent and received during a failed transaction.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameDump {
+    pub tx: Vec<u8>,
+    pub rx: Vec<u8>,
+    /// True if either direction exceeded the capture limit and was cut short.
+    pub truncated: bool,
+}
+impl FrameDump {
+    fn push(buf: &mut Vec<u8>, bytes: &[u8]) -> bool {
+        let room = MAX_CAPTURE_BYTES.saturating_sub(buf.len());
+        buf.extend(bytes.iter().take(room));
+        bytes.len() > room
+    }
+}
+impl fmt::Display for FrameDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+            for (i, b) in bytes.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:02X}", b)?;
+            }
+            Ok(())
+        }
+        write!(f, "tx=[")?;
+        hex(f, &self.tx)?;
+        write!(f, "] rx=[")?;
+        hex(f, &self.rx)?;
+        write!(f, "]")?;
+        if self.truncated {
+            write!(f, " (truncated)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Raised once when a handle's link goes dead, see
+/// `CryomechApiSmdpBuilder::link_dead_after()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkDeadEvent {
+    pub at: SystemTime,
+    /// Consecutive transactions that received no bytes at all.
+    pub transactions: u32,
+    pub labels: Arc<DeviceLabels>,
+}
+
+/// Direction of bytes passed to a wire hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent to the device
+    Tx,
+    /// Received from the device
+    Rx,
+}
+
+type WireHook = Arc<dyn Fn(&Direction, &[u8]) + Send + Sync>;
+
+#[derive(Default)]
+struct HookSlot(Mutex<Option<WireHook>>);
+impl fmt::Debug for HookSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HookSlot(..)")
+    }
+}
+
+/// Link state shared between the API handle and the port owned by the SMDP handler.
+#[derive(Debug, Default)]
+pub(crate) struct LinkState {
+    device_gone: AtomicBool,
+    rx_bytes: AtomicU64,
+    capture_enabled: AtomicBool,
+    capture: Mutex<FrameDump>,
+    hook: HookSlot,
+}
+impl LinkState {
+    /// True if an I/O error since the last (re)open indicates the device is gone.
+    pub(crate) fn device_gone(&self) -> bool {
+        self.device_gone.load(Ordering::Relaxed)
+    }
+    /// Total bytes received since the link was created.
+    pub(crate) fn rx_bytes(&self) -> u64 {
+        self.rx_bytes.load(Ordering::Relaxed)
+    }
+    /// Resets the state after the port has been reopened.
+    pub(crate) fn reset(&self) {
+        self.device_gone.store(false, Ordering::Relaxed);
+    }
+    pub(crate) fn set_capture(&self, enabled: bool) {
+        self.capture_enabled.store(enabled, Ordering::Relaxed);
+    }
+    pub(crate) fn capture_enabled(&self) -> bool {
+        self.capture_enabled.load(Ordering::Relaxed)
+    }
+    /// Starts a fresh capture for the next transaction.
+    pub(crate) fn clear_capture(&self) {
+        if self.capture_enabled() {
+            *self.capture.lock().unwrap_or_else(|e| e.into_inner()) = FrameDump::default();
+        }
+    }
+    /// Bytes captured since the last call to `clear_capture`.
+    pub(crate) fn take_capture(&self) -> FrameDump {
+        std::mem::take(&mut *self.capture.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+    pub(crate) fn set_hook(&self, hook: Option<WireHook>) {
+        *self.hook.0.lock().unwrap_or_else(|e| e.into_inner()) = hook;
+    }
+    fn record(&self, dir: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        if dir == Direction::Rx {
+            self.rx_bytes
+                .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        }
+        // Call outside the lock so a slow hook doesn't block set_hook
+        let hook = self
+            .hook
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(hook) = hook {
+            hook(&dir, bytes);
+        }
+        if !self.capture_enabled() {
+            return;
+        }
+        let mut capture = self.capture.lock().unwrap_or_else(|e| e.into_inner());
+        let truncated = match dir {
+            Direction::Tx => FrameDump::push(&mut capture.tx, bytes),
+            Direction::Rx => FrameDump::push(&mut capture.rx, bytes),
+        };
+        capture.truncated |= truncated;
+    }
+    fn observe_err(&self, err: &io::Error) {
+        if is_device_gone(err) {
+            self.device_gone.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// True if the error signals that the device path no longer refers to a live device
+/// (e.g. USB adapter unplugged or reset), as opposed to a timeout or framing issue.
+fn is_device_gone(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::BrokenPipe | io::ErrorKind::NotConnected | io::ErrorKind::NotFound => true,
+        _ => cfg!(unix) && matches!(err.raw_os_error(), Some(EIO | ENXIO | ENODEV)),
+    }
+}
+
+/// Opens the serial device and wraps it so errors are reported into `state`.
+pub(crate) fn open_port(
+    com_port: &str,
+    baud: u32,
+    rx_timeout: Duration,
+    state: &Arc<LinkState>,
+) -> CResult<Box<dyn SerialPort>> {
+    let port = serialport::new(com_port, baud).timeout(rx_timeout).open()?;
+    Ok(Box::new(MonitoredPort {
+        inner: port,
+        state: Arc::clone(state),
+    }))
+}
+
+/// Serial port that records link failures seen on reads/writes. All other
+/// operations are passed through to the wrapped port.
+#[derive(Debug)]
+struct MonitoredPort {
+    inner: Box<dyn SerialPort>,
+    state: Arc<LinkState>,
+}
+impl MonitoredPort {
+    fn observe<T>(&self, res: io::Result<T>) -> io::Result<T> {
+        if let Err(e) = &res {
+            self.state.observe_err(e);
+        }
+        res
+    }
+}
+impl Read for MonitoredPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.inner.read(buf);
+        if let Ok(n) = res {
+            self.state
+                .record(Direction::Rx, buf.get(..n).unwrap_or(buf));
+        }
+        self.observe(res)
+    }
+}
+impl Write for MonitoredPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let res = self.inner.write(buf);
+        if let Ok(n) = res {
+            self.state
+                .record(Direction::Tx, buf.get(..n).unwrap_or(buf));
+        }
+        self.observe(res)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        let res = self.inner.flush();
+        self.observe(res)
+    }
+}
+impl SerialPort for MonitoredPort {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        self.inner.flow_control()
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_write()
+    }
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(MonitoredPort {
+            inner: self.inner.try_clone()?,
+            state: Arc::clone(&self.state),
+        }))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        self.inner.set_break()
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
+
+/// Simulated port that plays back fixed bytes, e.g. for fuzzing the
+/// receive path. Writes are accepted, reads time out once the bytes are
+/// used up.
+#[derive(Debug, Clone)]
+pub(crate) struct ReplayPort(Arc<Mutex<VecDeque<u8>>>);
+impl ReplayPort {
+    pub(crate) fn boxed(rx: &[u8]) -> Box<dyn SerialPort> {
+        Box::new(Self(Arc::new(Mutex::new(rx.iter().copied().collect()))))
+    }
+}
+impl Read for ReplayPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut rx = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if rx.is_empty() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        rx.read(buf)
+    }
+}
+impl Write for ReplayPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl SerialPort for ReplayPort {
+    fn name(&self) -> Option<String> {
+        None
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(115200)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+    fn timeout(&self) -> Duration {
+        Duration::ZERO
+    }
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_flow_control(&mut self, _flow_control: FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_timeout(&mut self, _timeout: Duration) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        let rx = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(u32::try_from(rx.len()).unwrap_or(u32::MAX))
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, _buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Port with no device attached: writes are accepted, reads time out.
+    pub(crate) struct NullPort;
+    impl NullPort {
+        pub(crate) fn boxed() -> Box<dyn SerialPort> {
+            ReplayPort::boxed(&[])
+        }
+    }
+
+    #[test]
+    fn test_device_gone_detection() {
+        #[cfg(unix)]
+        assert!(is_device_gone(&io::Error::from_raw_os_error(EIO)));
+        assert!(is_device_gone(&io::Error::from(io::ErrorKind::BrokenPipe)));
+        assert!(!is_device_gone(&io::Error::from(io::ErrorKind::TimedOut)));
+    }
+    #[test]
+    fn test_link_state_reset() {
+        let state = LinkState::default();
+        state.observe_err(&io::Error::from(io::ErrorKind::NotConnected));
+        assert!(state.device_gone());
+        state.reset();
+        assert!(!state.device_gone());
+    }
+    #[test]
+    fn test_capture_bounded() {
+        let state = LinkState::default();
+        state.set_capture(true);
+        state.record(Direction::Tx, &[0x02, 0x10, 0x80]);
+        state.record(Direction::Rx, &[0xAA; MAX_CAPTURE_BYTES + 10]);
+        let dump = state.take_capture();
+        assert_eq!(dump.tx, vec![0x02, 0x10, 0x80]);
+        assert_eq!(dump.rx.len(), MAX_CAPTURE_BYTES);
+        assert!(dump.truncated);
+        assert!(dump.to_string().starts_with("tx=[02 10 80] rx=[AA AA"));
+    }
+    #[test]
+    fn test_wire_hook() {
+        let state = LinkState::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        state.set_hook(Some(Arc::new(move |dir: &Direction, bytes: &[u8]| {
+            sink.lock().unwrap().push((*dir, bytes.to_vec()));
+        })));
+        state.record(Direction::Tx, &[0x02, 0x10]);
+        state.record(Direction::Rx, &[]);
+        state.set_hook(None);
+        state.record(Direction::Rx, &[0x0D]);
+        assert_eq!(*seen.lock().unwrap(), [(Direction::Tx, vec![0x02, 0x10])]);
+        // Received bytes are counted with or without a hook
+        assert_eq!(state.rx_bytes(), 1);
+    }
+}
@@ -0,0 +1,571 @@
+/* Modbus TCP backend for Cryomech compressors with an Ethernet interface */
+// The comm path must never panic on device input
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::indexing_slicing
+    )
+)]
+
+use crate::{
+    CResult, Error, Real,
+    capability::{self, Backend, Capability, ProbeCache},
+    core::register::{ModbusAddr, Register},
+    model::CompressorModel,
+};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+const DEFAULT_PORT: u16 = 502;
+// Modbus function codes
+const FC_READ_INPUT: u8 = 0x04;
+const FC_WRITE_SINGLE: u8 = 0x06;
+const EXCEPTION_FLAG: u8 = 0x80;
+// MBAP header: transaction id, protocol id, length, unit id
+const MBAP_LEN: usize = 7;
+
+/// Control holding register and unit input registers of the CPA panel. The
+/// other input registers are mapped through `Register::modbus()`.
+mod reg {
+    /// 0x0001 starts, 0x00FF stops the compressor.
+    pub(super) const CONTROL: u16 = 1;
+    pub(super) const CONTROL_START: u16 = 0x0001;
+    pub(super) const CONTROL_STOP: u16 = 0x00FF;
+    /// Pressure unit of the panel (0 PSI, 1 bar, 2 kPa), followed by the
+    /// temperature unit (0 °F, 1 °C, 2 K).
+    pub(super) const UNITS: u16 = 29;
+}
+
+/// Units the panel reports pressures and temperatures in, as set on its
+/// display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PanelUnits {
+    pressure: u16,
+    temperature: u16,
+}
+impl PanelUnits {
+    /// Converts a pressure to PSI.
+    fn psi(self, value: Real) -> CResult<Real> {
+        match self.pressure {
+            0 => Ok(value),
+            1 => Ok(value * 14.503_774),
+            2 => Ok(value * 0.145_037_74),
+            _ => Err(Error::InvalidFormat("Unknown panel pressure unit.")),
+        }
+    }
+    /// Converts a temperature to °C.
+    fn celsius(self, value: Real) -> CResult<Real> {
+        match self.temperature {
+            0 => Ok((value - 32.0) * 5.0 / 9.0),
+            1 => Ok(value),
+            2 => Ok(value - 273.15),
+            _ => Err(Error::InvalidFormat("Unknown panel temperature unit.")),
+        }
+    }
+}
+
+/// Order of the two 16-bit registers that hold a 32-bit value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WordOrder {
+    /// Low word in the first register, as sent by CPA panels.
+    #[default]
+    LowFirst,
+    HighFirst,
+}
+impl WordOrder {
+    fn join(self, first: u16, second: u16) -> u32 {
+        let (hi, lo) = match self {
+            Self::LowFirst => (second, first),
+            Self::HighFirst => (first, second),
+        };
+        (u32::from(hi) << 16) | u32::from(lo)
+    }
+}
+
+/// Modbus TCP API to Cryomech compressors. The panel reports values in the
+/// units set on its display; they are converted to °C and PSI, the units of
+/// the SMDP backend.
+#[derive(Debug)]
+pub struct CryomechApiModbusTcp {
+    stream: TcpStream,
+    addr: SocketAddr,
+    timeout: Duration,
+    /// Set when a reply wasn't consumed cleanly, so leftover bytes would be
+    /// taken for the next reply. The connection is replaced first.
+    desynced: bool,
+    /// Read with the first converted value, and again after a reconnect.
+    units: Option<PanelUnits>,
+    host: String,
+    unit_id: u8,
+    transaction_id: u16,
+    word_order: WordOrder,
+    model: Option<CompressorModel>,
+    probed: ProbeCache,
+}
+impl CryomechApiModbusTcp {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+    /// Model given to the builder with `for_model()`.
+    pub fn model(&self) -> Option<CompressorModel> {
+        self.model
+    }
+    /// Reads `count` input registers starting at the 1-based register `first`.
+    pub fn read_input_registers(&mut self, first: u16, count: u16) -> CResult<Vec<u16>> {
+        let mut pdu = vec![FC_READ_INPUT];
+        pdu.extend(first.to_be_bytes());
+        pdu.extend(count.to_be_bytes());
+        self.transact(&pdu, |frame, transaction_id, unit_id| {
+            decode_read_reply(frame, transaction_id, unit_id, count)
+        })
+    }
+    /// Writes one holding register, 1-based.
+    pub fn write_holding_register(&mut self, register: u16, value: u16) -> CResult<()> {
+        let mut pdu = vec![FC_WRITE_SINGLE];
+        pdu.extend(register.to_be_bytes());
+        pdu.extend(value.to_be_bytes());
+        self.transact(&pdu, |frame, transaction_id, unit_id| {
+            // The reply echoes the request
+            if decode_reply(frame, transaction_id, unit_id, FC_WRITE_SINGLE)? != pdu {
+                return Err(Error::InvalidFormat(
+                    "Write reply does not echo the request.",
+                ));
+            }
+            Ok(())
+        })
+    }
+    /// Sends a request and decodes the whole reply frame, header included,
+    /// with `decode`. After a timeout or a reply that fails to decode other
+    /// than with an exception, leftover bytes could be taken for the next
+    /// reply, so the connection is replaced before the next request.
+    fn transact<T>(
+        &mut self,
+        pdu: &[u8],
+        decode: impl FnOnce(&[u8], u16, u8) -> CResult<T>,
+    ) -> CResult<T> {
+        if self.desynced {
+            self.stream = connect(&self.addr, self.timeout)?;
+            self.desynced = false;
+            self.units = None;
+        }
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let request = encode_frame(self.transaction_id, self.unit_id, pdu);
+        let result = self
+            .exchange(&request)
+            .and_then(|frame| decode(&frame, self.transaction_id, self.unit_id));
+        if let Err(e) = &result
+            && !e.is_nak()
+        {
+            self.desynced = true;
+        }
+        result
+    }
+    /// Writes a request frame and reads one reply frame, as long as its
+    /// header says.
+    fn exchange(&mut self, request: &[u8]) -> CResult<Vec<u8>> {
+        self.stream.write_all(request)?;
+        let mut header = [0u8; MBAP_LEN];
+        self.stream.read_exact(&mut header)?;
+        let mut frame = vec![0u8; MBAP_LEN + frame_len(&header)?];
+        let (head, body) = frame.split_at_mut(MBAP_LEN);
+        head.copy_from_slice(&header);
+        self.stream.read_exact(body)?;
+        Ok(frame)
+    }
+    fn read_u16(&mut self, register: u16) -> CResult<u16> {
+        self.read_input_registers(register, 1)?
+            .first()
+            .copied()
+            .ok_or(Error::InvalidFormat("Expected data in response, got none."))
+    }
+    /// True if the unit has the register or feature. Registers are probed with
+    /// one read the first time they are asked about; an exception reply means
+    /// unsupported. Features depend on the backend.
+    pub fn supports(&mut self, capability: impl Into<Capability>) -> CResult<bool> {
+        match Backend::Modbus.supports(capability.into(), &self.probed) {
+            Ok(supported) => Ok(supported),
+            Err(register) => {
+                let read = self.read(register);
+                capability::record_probe(&mut self.probed, register, read)
+            }
+        }
+    }
+    fn read_u16_register(&mut self, register: Register) -> CResult<u16> {
+        match register.modbus() {
+            Some(ModbusAddr::Word(reg)) => self.read_u16(reg),
+            _ => Err(Error::UnsupportedRegister(register)),
+        }
+    }
+    fn read_u32(&mut self, register: u16) -> CResult<u32> {
+        match self.read_input_registers(register, 2)?.as_slice() {
+            [first, second] => Ok(self.word_order.join(*first, *second)),
+            _ => Err(Error::InvalidFormat("Expected two registers in response.")),
+        }
+    }
+    fn read_float(&mut self, register: u16) -> CResult<Real> {
+        Ok(f32::from_bits(self.read_u32(register)?) as Real)
+    }
+}
+
+/* READ METHODS */
+impl CryomechApiModbusTcp {
+    /// Reads a register by name. Flags and state codes are returned as numbers.
+    pub fn read(&mut self, register: Register) -> CResult<Real> {
+        match register
+            .modbus()
+            .ok_or(Error::UnsupportedRegister(register))?
+        {
+            ModbusAddr::Word(reg) => Ok(Real::from(self.read_u16(reg)?)),
+            ModbusAddr::Float(reg) => {
+                let value = self.read_float(reg)?;
+                match register {
+                    Register::InputWaterTemp
+                    | Register::OutputWaterTemp
+                    | Register::OilTemp
+                    | Register::HeliumTemp => self.panel_units()?.celsius(value),
+                    Register::LowSidePressure
+                    | Register::AvgLowSidePressure
+                    | Register::HighSidePressure
+                    | Register::AvgHighSidePressure
+                    | Register::AvgDeltaPressure => self.panel_units()?.psi(value),
+                    _ => Ok(value),
+                }
+            }
+        }
+    }
+    fn panel_units(&mut self) -> CResult<PanelUnits> {
+        if let Some(units) = self.units {
+            return Ok(units);
+        }
+        let units = match self.read_input_registers(reg::UNITS, 2)?.as_slice() {
+            [pressure, temperature] => PanelUnits {
+                pressure: *pressure,
+                temperature: *temperature,
+            },
+            _ => return Err(Error::InvalidFormat("Expected two registers in response.")),
+        };
+        self.units = Some(units);
+        Ok(units)
+    }
+    /// Panel operating state code, see the panel manual.
+    pub fn operating_state(&mut self) -> CResult<u16> {
+        self.read_u16_register(Register::OperatingState)
+    }
+    pub fn comp_on(&mut self) -> CResult<bool> {
+        Ok(self.read(Register::CompOn)? == 1.0)
+    }
+    pub fn input_water_temp(&mut self) -> CResult<Real> {
+        self.read(Register::InputWaterTemp)
+    }
+    pub fn output_water_temp(&mut self) -> CResult<Real> {
+        self.read(Register::OutputWaterTemp)
+    }
+    pub fn oil_temp(&mut self) -> CResult<Real> {
+        self.read(Register::OilTemp)
+    }
+    pub fn helium_temp(&mut self) -> CResult<Real> {
+        self.read(Register::HeliumTemp)
+    }
+    pub fn low_side_pressure(&mut self) -> CResult<Real> {
+        self.read(Register::LowSidePressure)
+    }
+    pub fn avg_low_side_pressure(&mut self) -> CResult<Real> {
+        self.read(Register::AvgLowSidePressure)
+    }
+    pub fn high_side_pressure(&mut self) -> CResult<Real> {
+        self.read(Register::HighSidePressure)
+    }
+    pub fn avg_high_side_pressure(&mut self) -> CResult<Real> {
+        self.read(Register::AvgHighSidePressure)
+    }
+    pub fn avg_delta_pressure(&mut self) -> CResult<Real> {
+        self.read(Register::AvgDeltaPressure)
+    }
+    pub fn motor_current_amps(&mut self) -> CResult<Real> {
+        self.read(Register::MotorCurrent)
+    }
+    pub fn comp_hours(&mut self) -> CResult<Real> {
+        self.read(Register::RunHours)
+    }
+}
+
+/* WRITE METHODS */
+impl CryomechApiModbusTcp {
+    pub fn start_compressor(&mut self) -> CResult<()> {
+        self.write_holding_register(reg::CONTROL, reg::CONTROL_START)
+    }
+    pub fn stop_compressor(&mut self) -> CResult<()> {
+        self.write_holding_register(reg::CONTROL, reg::CONTROL_STOP)
+    }
+}
+
+fn connect(addr: &SocketAddr, timeout: Duration) -> CResult<TcpStream> {
+    let stream = TcpStream::connect_timeout(addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+fn encode_frame(transaction_id: u16, unit_id: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(MBAP_LEN + pdu.len());
+    frame.extend(transaction_id.to_be_bytes());
+    frame.extend(0u16.to_be_bytes());
+    frame.extend(((pdu.len() + 1) as u16).to_be_bytes());
+    frame.push(unit_id);
+    frame.extend(pdu);
+    frame
+}
+
+/// Validates a reply MBAP header and returns the PDU length that follows.
+fn check_header(header: &[u8; MBAP_LEN], transaction_id: u16, unit_id: u8) -> CResult<usize> {
+    let [t0, t1, p0, p1, _, _, unit] = *header;
+    if u16::from_be_bytes([t0, t1]) != transaction_id {
+        return Err(Error::InvalidFormat("Modbus transaction id mismatch."));
+    }
+    if u16::from_be_bytes([p0, p1]) != 0 {
+        return Err(Error::InvalidFormat("Not a Modbus TCP reply."));
+    }
+    if unit != unit_id {
+        return Err(Error::AddressMismatch {
+            expected: unit_id,
+            got: unit,
+        });
+    }
+    frame_len(header)
+}
+
+/// PDU length announced by a reply MBAP header.
+fn frame_len(header: &[u8; MBAP_LEN]) -> CResult<usize> {
+    let [_, _, _, _, l0, l1, _] = *header;
+    // Length counts the unit id; cap it at the largest valid Modbus PDU
+    match usize::from(u16::from_be_bytes([l0, l1])) {
+        len @ 2..=254 => Ok(len - 1),
+        _ => Err(Error::InvalidFormat("Modbus reply length out of range.")),
+    }
+}
+
+/// Checks the function code of a reply PDU, turning exceptions into errors.
+fn check_pdu(pdu: &[u8], function: u8) -> CResult<()> {
+    match pdu {
+        [fc, code, ..] if *fc == function | EXCEPTION_FLAG => Err(Error::ModbusException(*code)),
+        [fc, ..] if *fc == function => Ok(()),
+        [fc, ..] => Err(Error::UnexpectedOpcode(*fc)),
+        [] => Err(Error::InvalidFormat("Empty Modbus reply.")),
+    }
+}
+
+/// Checks a reply frame (MBAP header and PDU) to a request with `function`
+/// and returns its PDU.
+fn decode_reply(frame: &[u8], transaction_id: u16, unit_id: u8, function: u8) -> CResult<&[u8]> {
+    let (header, pdu) = frame
+        .split_first_chunk::<MBAP_LEN>()
+        .ok_or(Error::InvalidFormat(
+            "Modbus reply shorter than its header.",
+        ))?;
+    if check_header(header, transaction_id, unit_id)? != pdu.len() {
+        return Err(Error::InvalidFormat(
+            "Modbus reply length does not match header.",
+        ));
+    }
+    check_pdu(pdu, function)?;
+    Ok(pdu)
+}
+
+/// Decodes a reply frame to an input register read of `count` registers.
+pub(crate) fn decode_read_reply(
+    frame: &[u8],
+    transaction_id: u16,
+    unit_id: u8,
+    count: u16,
+) -> CResult<Vec<u16>> {
+    decode_registers(
+        decode_reply(frame, transaction_id, unit_id, FC_READ_INPUT)?,
+        count,
+    )
+}
+
+fn decode_registers(pdu: &[u8], count: u16) -> CResult<Vec<u16>> {
+    match pdu {
+        [_, byte_count, data @ ..]
+            if usize::from(*byte_count) == data.len() && data.len() == usize::from(count) * 2 =>
+        {
+            Ok(data
+                .chunks_exact(2)
+                .filter_map(|w| w.try_into().ok().map(u16::from_be_bytes))
+                .collect())
+        }
+        _ => Err(Error::InvalidFormat(
+            "Register count in reply does not match request.",
+        )),
+    }
+}
+
+/// Builder for the Modbus TCP API type, mirroring `CryomechApiSmdpBuilder`.
+pub struct CryomechApiModbusTcpBuilder {
+    host: String,
+    port: u16,
+    unit_id: u8,
+    timeout: Duration,
+    word_order: Option<WordOrder>,
+    model: Option<CompressorModel>,
+}
+impl CryomechApiModbusTcpBuilder {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.into(),
+            port: DEFAULT_PORT,
+            unit_id: 1,
+            timeout: Duration::from_millis(1000),
+            word_order: None,
+            model: None,
+        }
+    }
+    /// Records the compressor model, reported by `model()`.
+    pub fn for_model(mut self, model: CompressorModel) -> Self {
+        self.model = Some(model);
+        self
+    }
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+    pub fn unit_id(mut self, unit_id: u8) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+    /// Connect, read and write timeout.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Duration::from_millis(timeout);
+        self
+    }
+    pub fn word_order(mut self, order: WordOrder) -> Self {
+        self.word_order = Some(order);
+        self
+    }
+    pub fn build(self) -> CResult<CryomechApiModbusTcp> {
+        let addr = std::net::ToSocketAddrs::to_socket_addrs(&(self.host.as_str(), self.port))?
+            .next()
+            .ok_or(Error::InvalidFormat("Host did not resolve to an address."))?;
+        Ok(CryomechApiModbusTcp {
+            stream: connect(&addr, self.timeout)?,
+            addr,
+            timeout: self.timeout,
+            desynced: false,
+            units: None,
+            host: self.host,
+            unit_id: self.unit_id,
+            transaction_id: 0,
+            word_order: self.word_order.unwrap_or_default(),
+            model: self.model,
+            probed: ProbeCache::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_read_request() {
+        let pdu = [FC_READ_INPUT, 0x00, 0x07, 0x00, 0x02];
+        assert_eq!(
+            encode_frame(0x0102, 1, &pdu),
+            [
+                0x01, 0x02, 0x00, 0x00, 0x00, 0x06, 0x01, 0x04, 0x00, 0x07, 0x00, 0x02
+            ]
+        );
+    }
+    #[test]
+    fn test_decode_reply() {
+        let header = [0x01, 0x02, 0x00, 0x00, 0x00, 0x07, 0x01];
+        assert_eq!(check_header(&header, 0x0102, 1).unwrap(), 6);
+        assert!(check_header(&header, 0x0103, 1).is_err());
+        assert!(matches!(
+            check_header(&header, 0x0102, 2),
+            Err(Error::AddressMismatch { .. })
+        ));
+        let pdu = [FC_READ_INPUT, 0x04, 0x00, 0x00, 0x42, 0x20];
+        assert!(check_pdu(&pdu, FC_READ_INPUT).is_ok());
+        let regs = decode_registers(&pdu, 2).unwrap();
+        assert_eq!(regs, [0x0000, 0x4220]);
+        // 40.0 as an IEEE float, low word first
+        assert_eq!(
+            f32::from_bits(WordOrder::LowFirst.join(regs[0], regs[1])),
+            40.0
+        );
+        assert!(decode_registers(&pdu, 3).is_err());
+        assert!(matches!(
+            check_pdu(&[0x84, 0x02], FC_READ_INPUT),
+            Err(Error::ModbusException(0x02))
+        ));
+    }
+    #[test]
+    fn test_decode_read_reply() {
+        let frame = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x01, 0x04, 0x02, 0x00, 0x03,
+        ];
+        assert_eq!(decode_read_reply(&frame, 1, 1, 1).unwrap(), [3]);
+        // Header claims more bytes than were received
+        assert!(decode_read_reply(&frame[..10], 1, 1, 1).is_err());
+        assert!(decode_read_reply(&frame[..3], 1, 1, 1).is_err());
+    }
+    #[test]
+    fn test_panel_units() {
+        let display = PanelUnits {
+            pressure: 1,
+            temperature: 0,
+        };
+        assert!((display.celsius(212.0).unwrap() - 100.0).abs() < 1e-3);
+        assert!((display.psi(10.0).unwrap() - 145.037_74).abs() < 1e-3);
+        let si = PanelUnits {
+            pressure: 2,
+            temperature: 2,
+        };
+        assert!((si.celsius(300.0).unwrap() - 26.85).abs() < 1e-3);
+        assert!((si.psi(100.0).unwrap() - 14.503_774).abs() < 1e-3);
+        let unknown = PanelUnits {
+            pressure: 3,
+            temperature: 3,
+        };
+        assert!(unknown.psi(1.0).is_err() && unknown.celsius(1.0).is_err());
+    }
+    #[test]
+    fn test_reconnect_after_bad_reply() {
+        use std::{net::TcpListener, thread};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut request = [0u8; 12];
+            // Reply to another transaction, then a stray byte
+            let (mut first, _) = listener.accept().unwrap();
+            first.read_exact(&mut request).unwrap();
+            first
+                .write_all(&[
+                    0x00, 0x09, 0x00, 0x00, 0x00, 0x05, 0x01, 0x04, 0x02, 0x00, 0x03, 0xFF,
+                ])
+                .unwrap();
+            let (mut second, _) = listener.accept().unwrap();
+            second.read_exact(&mut request).unwrap();
+            let [t0, t1, ..] = request;
+            second
+                .write_all(&[t0, t1, 0x00, 0x00, 0x00, 0x05, 0x01, 0x04, 0x02, 0x00, 0x07])
+                .unwrap();
+        });
+        let mut api = CryomechApiModbusTcpBuilder::new("127.0.0.1")
+            .port(port)
+            .build()
+            .unwrap();
+        assert!(api.read_input_registers(1, 1).is_err());
+        assert_eq!(api.read_input_registers(1, 1).unwrap(), [7]);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,12 @@
+/* Compressor models known to the crate */
+
+/// Cryomech compressor models. The model is only recorded (e.g. in
+/// provenance) and doesn't change how the handle talks to the unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressorModel {
+    Cpa286i,
+    Cpa289c,
+    Cpa1110,
+    Cpa1114,
+    Cpa2870,
+}
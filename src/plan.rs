@@ -0,0 +1,120 @@
+/* Register poll plan with priorities, for use as a poller read function */
+
+use crate::{CResult, Real, api::CryomechApiSmdp, core::register::Register};
+use serialport::SerialPort;
+use std::{collections::HashMap, time::Duration};
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+
+/// How urgently a register is read within a poll cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Read first, every cycle, regardless of the cycle budget.
+    Critical,
+    Normal,
+    /// Statistics (averages, min/max) that may lag a few cycles.
+    Bulk,
+}
+
+/// Set of registers read once per poll cycle, critical ones first. With a cycle
+/// budget, lower-priority reads that no longer fit are carried over to the next
+/// cycle, continuing where the previous one stopped.
+///
+/// Pass `move |api| plan.poll(api)` as the poller's read function.
+#[derive(Debug, Clone, Default)]
+pub struct PollPlan {
+    entries: Vec<(Register, Priority)>,
+    budget: Option<Duration>,
+    // Position in the non-critical reads to resume from
+    cursor: usize,
+    values: HashMap<Register, Real>,
+}
+impl PollPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a register, replacing its priority if already present.
+    pub fn register(mut self, register: Register, priority: Priority) -> Self {
+        self.entries.retain(|(r, _)| *r != register);
+        self.entries.push((register, priority));
+        // Stable, so registers keep their insertion order within a priority
+        self.entries.sort_by_key(|(_, p)| *p);
+        self
+    }
+    /// Time after which no further non-critical reads are started in a cycle.
+    pub fn cycle_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+    /// Runs one cycle and returns the latest value of every register read so
+    /// far. Registers deferred this cycle keep their previous value. Errors
+    /// abort the cycle.
+    pub fn poll(&mut self, api: &mut SmdpApi) -> CResult<HashMap<Register, Real>> {
+        let clock = api.clock();
+        let start = clock.now();
+        let (critical, rest) = self.split();
+        for register in critical {
+            let value = api.read(register)?;
+            self.values.insert(register, value);
+        }
+        for (done, register) in rest.iter().enumerate() {
+            if self
+                .budget
+                .is_some_and(|budget| clock.now().saturating_duration_since(start) >= budget)
+            {
+                self.cursor = (self.cursor + done) % rest.len();
+                return Ok(self.values.clone());
+            }
+            let value = api.read(*register)?;
+            self.values.insert(*register, value);
+        }
+        self.cursor = 0;
+        Ok(self.values.clone())
+    }
+    /// Critical registers, then the others in priority order starting from
+    /// where the last cycle stopped.
+    fn split(&self) -> (Vec<Register>, Vec<Register>) {
+        let critical = self.registers(|p| p == Priority::Critical);
+        let mut rest = self.registers(|p| p != Priority::Critical);
+        let len = rest.len();
+        if len > 0 {
+            rest.rotate_left(self.cursor % len);
+        }
+        (critical, rest)
+    }
+    fn registers(&self, filter: impl Fn(Priority) -> bool) -> Vec<Register> {
+        self.entries
+            .iter()
+            .filter(|(_, p)| filter(*p))
+            .map(|(r, _)| *r)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_priority_order_and_resume() {
+        let mut plan = PollPlan::new()
+            .register(Register::AvgDeltaPressure, Priority::Bulk)
+            .register(Register::OilTemp, Priority::Normal)
+            .register(Register::CompOn, Priority::Critical)
+            .register(Register::AvgHighSidePressure, Priority::Bulk)
+            .register(Register::ErrCodeStatus, Priority::Critical);
+        let (critical, rest) = plan.split();
+        assert_eq!(critical, [Register::CompOn, Register::ErrCodeStatus]);
+        assert_eq!(
+            rest,
+            [
+                Register::OilTemp,
+                Register::AvgDeltaPressure,
+                Register::AvgHighSidePressure
+            ]
+        );
+        plan.cursor = 2;
+        let (_, rest) = plan.split();
+        assert_eq!(rest[0], Register::AvgHighSidePressure);
+    }
+}
@@ -0,0 +1,680 @@
+/* Background polling of a Cryomech device with a cache of the most recent reading */
+
+use crate::{
+    CResult, Error, Real,
+    api::{ControlOutcome, CryomechApiSmdp},
+    clock::Clock,
+    core::register::Register,
+    discovery::{self, UsbMatch},
+    history::{HistoryEntry, HistoryKind, SharedHistory},
+    interlock::InterlockLimits,
+    labels::DeviceLabels,
+    link::LinkDeadEvent,
+    provenance::Provenance,
+    reboot::DeviceRebooted,
+    schedule::{Schedule, ScheduledAction, ScheduledRun},
+    sink::{Backpressure, SinkDispatch},
+    status::{CompressorStatus, Tolerances},
+    watchdog::{TempWatchdog, WatchdogEvent, WatchdogState},
+};
+use serialport::SerialPort;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+type SteadyFn<T> = fn(&T, &T) -> bool;
+
+/// Most recent reading held by the poller.
+#[derive(Debug, Clone)]
+pub struct Cached<T> {
+    pub value: T,
+    /// Time of the refresh that produced `value`.
+    pub updated: Instant,
+    /// True if the last successful refresh is older than the staleness threshold.
+    pub stale: bool,
+}
+
+/// Polls a device on a background thread, caching the latest successful reading.
+/// The thread exits when the poller is dropped or `shutdown()` is called.
+/// Timestamps, the schedule and the watchdog follow the handle's clock; the
+/// wait between polls is always real time.
+pub struct Poller<T> {
+    cache: Arc<Mutex<Option<(T, Instant)>>>,
+    stale_after: Duration,
+    clock: Arc<dyn Clock>,
+    labels: Arc<DeviceLabels>,
+    last_scheduled: Arc<Mutex<Option<ScheduledRun>>>,
+    watchdog_event: Arc<Mutex<Option<WatchdogEvent>>>,
+    link_dead_event: Arc<Mutex<Option<LinkDeadEvent>>>,
+    reboot_event: Arc<Mutex<Option<DeviceRebooted>>>,
+    history: Option<SharedHistory>,
+    reset_link_stats: Arc<AtomicBool>,
+    sink_dropped: Arc<AtomicU64>,
+    watchdog: Arc<Mutex<Option<TempWatchdog>>>,
+    new_interlock: Arc<Mutex<Option<InterlockLimits>>>,
+    stop_tx: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+}
+impl<T: Clone> Poller<T> {
+    /// Latest cached reading, or None if no refresh has succeeded yet.
+    pub fn latest(&self) -> Option<Cached<T>> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.as_ref().map(|(value, updated)| Cached {
+            value: value.clone(),
+            updated: *updated,
+            stale: self.clock.now().saturating_duration_since(*updated) > self.stale_after,
+        })
+    }
+    /// Blocks until the cached reading satisfies `condition`, checking every
+    /// `poll_interval`. Only readings refreshed after the call are considered.
+    /// Errs with `WaitTimeout` if the condition isn't met in time.
+    pub fn wait_until(
+        &self,
+        condition: impl Fn(&T) -> bool,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> CResult<T> {
+        let start = self.clock.now();
+        loop {
+            if let Some(cached) = self.latest()
+                && cached.updated >= start
+                && condition(&cached.value)
+            {
+                return Ok(cached.value);
+            }
+            let elapsed = self.clock.now().saturating_duration_since(start);
+            if elapsed >= timeout {
+                return Err(Error::WaitTimeout(timeout));
+            }
+            self.clock
+                .sleep(poll_interval.min(timeout.saturating_sub(elapsed)));
+        }
+    }
+    /// Labels of the polled handle, to tag readings taken from the cache.
+    pub fn labels(&self) -> &DeviceLabels {
+        &self.labels
+    }
+    /// Last action carried out for the schedule, if one is configured.
+    pub fn last_scheduled(&self) -> Option<ScheduledRun> {
+        self.last_scheduled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+    /// Stops the poller and waits for its thread to exit. A transaction in
+    /// flight is completed and the sink flushed first. Errs if the thread
+    /// panicked, e.g. in the user's read closure.
+    pub fn shutdown(self) -> thread::Result<()> {
+        let _ = self.stop_tx.send(());
+        self.thread.join()
+    }
+    /// Most recent watchdog stop, if the watchdog is enabled and has tripped.
+    pub fn watchdog_event(&self) -> Option<WatchdogEvent> {
+        self.watchdog_event
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+    /// Set when the handle's link goes dead, cleared once bytes are received
+    /// again. Requires `link_dead_after()` on the handle's builder.
+    pub fn link_dead_event(&self) -> Option<LinkDeadEvent> {
+        self.link_dead_event
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+    /// Empties the history attached with `PollerBuilder::history()` and
+    /// returns its entries, e.g. at a shift boundary. Empty without history.
+    pub fn reset_history(&self) -> Vec<HistoryEntry> {
+        self.history.as_ref().map_or_else(Vec::new, |history| {
+            history.lock().unwrap_or_else(|e| e.into_inner()).reset()
+        })
+    }
+    /// Items the sink missed because its queue was full, with
+    /// `Backpressure::DropOldest`.
+    pub fn sink_dropped(&self) -> u64 {
+        self.sink_dropped.load(Ordering::Relaxed)
+    }
+    /// Replaces the watchdog limits, or disables the watchdog with None,
+    /// from the next poll on. A pending over-limit hold starts over.
+    pub fn set_watchdog(&self, watchdog: Option<TempWatchdog>) {
+        *self.watchdog.lock().unwrap_or_else(|e| e.into_inner()) = watchdog;
+    }
+    /// Replaces the handle's interlock limits before the next poll, without
+    /// stopping the poller or closing the port.
+    pub fn set_interlock_limits(&self, limits: InterlockLimits) {
+        *self.new_interlock.lock().unwrap_or_else(|e| e.into_inner()) = Some(limits);
+    }
+    /// Like `set_interlock_limits()`, reading the limits from a policy file
+    /// on the calling thread. Nothing changes if the file is invalid.
+    #[cfg(feature = "policy")]
+    pub fn reload_interlock_policy(&self, path: impl AsRef<std::path::Path>) -> CResult<()> {
+        self.set_interlock_limits(InterlockLimits::from_file(path)?);
+        Ok(())
+    }
+    /// Resets the handle's link statistics before the next poll.
+    pub fn reset_link_stats(&self) {
+        self.reset_link_stats.store(true, Ordering::Relaxed);
+    }
+    /// Most recent panel reboot seen by the handle, see
+    /// `CryomechApiSmdpBuilder::reboot_detection()`.
+    pub fn reboot_event(&self) -> Option<DeviceRebooted> {
+        self.reboot_event
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Something the poller thread did or noticed besides taking a reading.
+#[derive(Debug, Clone)]
+pub enum PollerEvent {
+    Watchdog(WatchdogEvent),
+    Scheduled(ScheduledRun),
+    LinkDead(LinkDeadEvent),
+    Rebooted(DeviceRebooted),
+}
+
+/// Receives each successful reading on the poller thread, in addition to the
+/// cache, and optionally events and read errors. Implement it to forward
+/// readings to a custom store (e.g. a LIMS) without changing the poller.
+/// Implemented for std channel senders so readings can go straight into an
+/// application's existing channel plumbing.
+pub trait ReadingSink<T>: Send {
+    fn on_snapshot(&mut self, value: &T);
+    fn on_event(&mut self, _event: &PollerEvent) {}
+    /// Called for each failed read; the cache keeps the previous reading.
+    fn on_error(&mut self, _error: &Error) {}
+    /// Called once when the poller thread exits, for sinks that buffer.
+    fn flush(&mut self) {}
+}
+/// Sends every reading. A dropped receiver is ignored and polling continues.
+impl<T: Clone + Send> ReadingSink<T> for mpsc::Sender<T> {
+    fn on_snapshot(&mut self, value: &T) {
+        let _ = self.send(value.clone());
+    }
+}
+/// Drops readings while the channel is full rather than stall the poller.
+impl<T: Clone + Send> ReadingSink<T> for mpsc::SyncSender<T> {
+    fn on_snapshot(&mut self, value: &T) {
+        let _ = self.try_send(value.clone());
+    }
+}
+/// No sink, readings only go to the cache.
+impl<T> ReadingSink<T> for () {
+    fn on_snapshot(&mut self, _value: &T) {}
+}
+
+/// Poll rate limits for `spawn_adaptive()`. The poller runs at the builder's
+/// interval while readings change and doubles the interval, up to `slow`, each
+/// time they have been steady for `settle_cycles` polls in a row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveRate {
+    pub slow: Duration,
+    pub settle_cycles: u32,
+}
+impl Default for AdaptiveRate {
+    fn default() -> Self {
+        Self {
+            slow: Duration::from_secs(60),
+            settle_cycles: 5,
+        }
+    }
+}
+
+/// Readings the adaptive poll rate can compare between cycles.
+pub trait Steady {
+    /// True if nothing meaningful changed since `previous`.
+    fn steady(&self, previous: &Self) -> bool;
+}
+impl Steady for CompressorStatus {
+    fn steady(&self, previous: &Self) -> bool {
+        self.approx_eq(previous, Tolerances::default())
+    }
+}
+/// Output of `read_all_with_provenance()`. Only the readings are compared, the
+/// record differs with every read.
+impl<T: Steady> Steady for (T, Provenance) {
+    fn steady(&self, previous: &Self) -> bool {
+        self.0.steady(&previous.0)
+    }
+}
+/// Output of `PollPlan::poll()`. Registers are compared with the default
+/// `Tolerances`, i.e. within half a display step.
+impl Steady for HashMap<Register, Real> {
+    fn steady(&self, previous: &Self) -> bool {
+        let tolerance = Tolerances::default().temp;
+        self.len() == previous.len()
+            && self.iter().all(|(register, value)| {
+                previous
+                    .get(register)
+                    .is_some_and(|prev| (value - prev).abs() <= tolerance)
+            })
+    }
+}
+
+#[derive(Debug)]
+struct AdaptiveState {
+    fast: Duration,
+    rate: AdaptiveRate,
+    steady_for: u32,
+    interval: Duration,
+}
+impl AdaptiveState {
+    fn new(fast: Duration, rate: AdaptiveRate) -> Self {
+        Self {
+            fast,
+            rate,
+            steady_for: 0,
+            interval: fast,
+        }
+    }
+    /// Interval to wait after a reading that was (or wasn't) steady.
+    fn observe(&mut self, steady: bool) -> Duration {
+        if !steady {
+            self.steady_for = 0;
+            self.interval = self.fast;
+        } else {
+            self.steady_for += 1;
+            if self.steady_for >= self.rate.settle_cycles {
+                self.steady_for = 0;
+                self.interval = (self.interval * 2).min(self.rate.slow.max(self.fast));
+            }
+        }
+        self.interval
+    }
+}
+
+/// Builder for the background poller
+pub struct PollerBuilder {
+    interval: Duration,
+    stale_after: Duration,
+    schedule: Option<Schedule>,
+    watchdog: Option<TempWatchdog>,
+    rebind: Option<UsbMatch>,
+    history: Option<SharedHistory>,
+    backpressure: Backpressure,
+}
+impl PollerBuilder {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            stale_after: interval * 3,
+            schedule: None,
+            watchdog: None,
+            rebind: None,
+            history: None,
+            backpressure: Backpressure::default(),
+        }
+    }
+    /// Age after which the cached reading is reported as stale.
+    pub fn stale_after(mut self, threshold: Duration) -> Self {
+        self.stale_after = threshold;
+        self
+    }
+    /// Start/stop the compressor on a schedule, checked once per interval.
+    /// Scheduled starts go through `start_compressor_checked()` and are skipped
+    /// when an interlock fails.
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+    /// Stop the compressor when oil or helium temperature stays over its
+    /// limit. Adds two reads per interval. The stop is sent even when the
+    /// handle requires write arming or a control token.
+    pub fn watchdog(mut self, watchdog: TempWatchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+    /// While the device is gone, look for the adapter matching `usb` once per
+    /// interval and rebind to it when it reappears, even under a new port name.
+    pub fn rebind(mut self, usb: UsbMatch) -> Self {
+        self.rebind = Some(usb);
+        self
+    }
+    /// History that scheduled min/max resets archive the pre-reset snapshot
+    /// into. A reset is skipped if that snapshot can't be read.
+    pub fn history(mut self, history: SharedHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+    /// How a sink that can't keep up is decoupled from polling. By default
+    /// the sink runs on the poller thread and a slow sink delays polls.
+    pub fn sink_backpressure(mut self, policy: Backpressure) -> Self {
+        self.backpressure = policy;
+        self
+    }
+    /// Moves the API handle onto a background thread that calls `read` every
+    /// interval. Failed reads leave the previous value in the cache.
+    pub fn spawn<T, F>(self, api: SmdpApi, read: F) -> Poller<T>
+    where
+        T: Clone + Send + 'static,
+        F: FnMut(&mut SmdpApi) -> CResult<T> + Send + 'static,
+    {
+        self.spawn_with_sink(api, read, ())
+    }
+    /// Like `spawn()`, also handing each successful reading to `sink`, e.g. an
+    /// `mpsc::Sender`.
+    pub fn spawn_with_sink<T, F, S>(self, api: SmdpApi, read: F, sink: S) -> Poller<T>
+    where
+        T: Clone + Send + 'static,
+        F: FnMut(&mut SmdpApi) -> CResult<T> + Send + 'static,
+        S: ReadingSink<T> + 'static,
+    {
+        self.spawn_inner(api, read, sink, None)
+    }
+    /// Like `spawn_with_sink()`, backing off from the builder's interval while
+    /// readings are steady (e.g. a cold, settled system) and returning to it as
+    /// soon as they change (cooldown, start-up). The staleness threshold is
+    /// raised to at least three slow intervals.
+    pub fn spawn_adaptive<T, F, S>(
+        mut self,
+        rate: AdaptiveRate,
+        api: SmdpApi,
+        read: F,
+        sink: S,
+    ) -> Poller<T>
+    where
+        T: Steady + Clone + Send + 'static,
+        F: FnMut(&mut SmdpApi) -> CResult<T> + Send + 'static,
+        S: ReadingSink<T> + 'static,
+    {
+        self.stale_after = self.stale_after.max(rate.slow * 3);
+        let state = AdaptiveState::new(self.interval, rate);
+        self.spawn_inner(api, read, sink, Some((state, T::steady as SteadyFn<T>)))
+    }
+    fn spawn_inner<T, F, S>(
+        self,
+        mut api: SmdpApi,
+        mut read: F,
+        sink: S,
+        mut adaptive: Option<(AdaptiveState, SteadyFn<T>)>,
+    ) -> Poller<T>
+    where
+        T: Clone + Send + 'static,
+        F: FnMut(&mut SmdpApi) -> CResult<T> + Send + 'static,
+        S: ReadingSink<T> + 'static,
+    {
+        let cache = Arc::new(Mutex::new(None));
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let mut interval = self.interval;
+        let thread_cache = Arc::clone(&cache);
+        let last_scheduled = Arc::new(Mutex::new(None));
+        let thread_scheduled = Arc::clone(&last_scheduled);
+        let schedule = self.schedule;
+        let watchdog_event = Arc::new(Mutex::new(None));
+        let thread_watchdog = Arc::clone(&watchdog_event);
+        let watchdog = Arc::new(Mutex::new(self.watchdog));
+        let thread_watchdog_limits = Arc::clone(&watchdog);
+        let new_interlock = Arc::new(Mutex::new(None));
+        let thread_new_interlock = Arc::clone(&new_interlock);
+        let link_dead_event = Arc::new(Mutex::new(None));
+        let thread_link_dead = Arc::clone(&link_dead_event);
+        let reboot_event = Arc::new(Mutex::new(None));
+        let thread_reboot = Arc::clone(&reboot_event);
+        let rebind = self.rebind;
+        let history = self.history;
+        let poller_history = history.clone();
+        let reset_link_stats = Arc::new(AtomicBool::new(false));
+        let thread_reset_link_stats = Arc::clone(&reset_link_stats);
+        let sink_dropped = Arc::new(AtomicU64::new(0));
+        let mut sink = SinkDispatch::new(sink, self.backpressure, Arc::clone(&sink_dropped));
+        let clock = api.clock();
+        let labels = api.labels_arc();
+        let thread_clock = Arc::clone(&clock);
+        let thread = thread::spawn(move || {
+            let clock = thread_clock;
+            let mut last_check = clock.system_time();
+            let mut watchdog_state = WatchdogState::default();
+            let mut watchdog_limits = None;
+            loop {
+                let start = clock.now();
+                // Failures just leave the link down until the next interval
+                if let Some(usb) = &rebind
+                    && api.link_down()
+                    && let Ok(port) = discovery::find_port(usb)
+                {
+                    let _ = api.rebind(&port);
+                }
+                if let Some(limits) = thread_new_interlock
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .take()
+                {
+                    api.set_interlock_limits(limits);
+                }
+                let limits = thread_watchdog_limits
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone();
+                if limits != watchdog_limits {
+                    watchdog_state = WatchdogState::default();
+                    watchdog_limits = limits;
+                }
+                if let Some(limits) = &watchdog_limits
+                    && let Some(event) = check_watchdog(&mut api, limits, &mut watchdog_state)
+                {
+                    sink.event(PollerEvent::Watchdog(event.clone()));
+                    *thread_watchdog.lock().unwrap_or_else(|e| e.into_inner()) = Some(event);
+                }
+                if let Some(schedule) = &schedule {
+                    let now = clock.system_time();
+                    if let Some(action) = schedule.due(last_check, now) {
+                        let run = run_scheduled(&mut api, action, history.as_ref());
+                        sink.event(PollerEvent::Scheduled(run.clone()));
+                        *thread_scheduled.lock().unwrap_or_else(|e| e.into_inner()) = Some(run);
+                    }
+                    last_check = now;
+                }
+                if thread_reset_link_stats.swap(false, Ordering::Relaxed) {
+                    api.reset_link_stats();
+                }
+                let result = read(&mut api);
+                {
+                    let mut event = thread_link_dead.lock().unwrap_or_else(|e| e.into_inner());
+                    if !api.link_dead() {
+                        *event = None;
+                    } else if event.is_none() {
+                        let dead = LinkDeadEvent {
+                            at: clock.system_time(),
+                            transactions: api.silent_transactions(),
+                            labels: api.labels_arc(),
+                        };
+                        sink.event(PollerEvent::LinkDead(dead.clone()));
+                        *event = Some(dead);
+                    }
+                }
+                if let Some(reboot) = api.last_reboot() {
+                    let mut event = thread_reboot.lock().unwrap_or_else(|e| e.into_inner());
+                    if event.as_ref() != Some(reboot) {
+                        sink.event(PollerEvent::Rebooted(reboot.clone()));
+                        *event = Some(reboot.clone());
+                    }
+                }
+                let value = match result {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        sink.error(e);
+                        None
+                    }
+                };
+                if let Some(value) = value {
+                    sink.snapshot(&value);
+                    let mut cache = thread_cache.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some((state, steady)) = adaptive.as_mut() {
+                        let unchanged =
+                            cache.as_ref().is_some_and(|(prev, _)| steady(&value, prev));
+                        interval = state.observe(unchanged);
+                    }
+                    *cache = Some((value, clock.now()));
+                }
+                // Wait out the rest of the interval, exiting early if the poller is dropped
+                match stop_rx.recv_timeout(
+                    interval.saturating_sub(clock.now().saturating_duration_since(start)),
+                ) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+            sink.flush();
+        });
+        Poller {
+            cache,
+            stale_after: self.stale_after,
+            clock,
+            labels,
+            last_scheduled,
+            watchdog_event,
+            link_dead_event,
+            reboot_event,
+            history: poller_history,
+            reset_link_stats,
+            sink_dropped,
+            watchdog,
+            new_interlock,
+            stop_tx,
+            thread,
+        }
+    }
+}
+
+fn run_scheduled(
+    api: &mut SmdpApi,
+    action: ScheduledAction,
+    history: Option<&SharedHistory>,
+) -> ScheduledRun {
+    tracing::info!(
+        device = api.device_name(),
+        ?action,
+        "running scheduled action"
+    );
+    let result = match action {
+        ScheduledAction::Start => api.start_compressor_checked().map(|_| ()),
+        ScheduledAction::Stop => api.stop_compressor_ungated().map(|_| ()),
+        ScheduledAction::ResetMinMax => reset_min_max(api, history),
+    };
+    ScheduledRun {
+        action,
+        at: api.clock().system_time(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+fn reset_min_max(api: &mut SmdpApi, history: Option<&SharedHistory>) -> CResult<()> {
+    if let Some(history) = history {
+        let status = api.read_all()?;
+        history
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(HistoryKind::MinMaxArchive, status);
+    }
+    api.clear_press_temp_min_max()
+}
+
+fn check_watchdog(
+    api: &mut SmdpApi,
+    limits: &TempWatchdog,
+    state: &mut WatchdogState,
+) -> Option<WatchdogEvent> {
+    let (Ok(oil_temp), Ok(helium_temp)) = (api.oil_temp(), api.helium_temp()) else {
+        return None;
+    };
+    let clock = api.clock();
+    let over_for = state.observe(limits, oil_temp, helium_temp, clock.now())?;
+    tracing::error!(
+        device = api.device_name(),
+        oil_temp,
+        helium_temp,
+        oil_limit = limits.oil_limit,
+        helium_limit = limits.helium_limit,
+        over_for = ?over_for,
+        "watchdog stopping compressor"
+    );
+    Some(WatchdogEvent {
+        at: clock.system_time(),
+        oil_temp,
+        helium_temp,
+        limits: limits.clone(),
+        over_for,
+        stop: watchdog_stop(api),
+        labels: api.labels_arc(),
+    })
+}
+
+/// Stop write for an over-temperature trip. Goes out even if write arming or
+/// control tokens are required, like `emergency_stop()`, and isn't verified.
+fn watchdog_stop(api: &mut SmdpApi) -> Result<ControlOutcome, String> {
+    api.send_stop()
+        .map(|()| ControlOutcome::Unchecked)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api::CryomechApiSmdpBuilder, link::test::NullPort};
+
+    #[test]
+    fn test_adaptive_backoff() {
+        let fast = Duration::from_secs(1);
+        let mut state = AdaptiveState::new(
+            fast,
+            AdaptiveRate {
+                slow: Duration::from_secs(3),
+                settle_cycles: 2,
+            },
+        );
+        assert_eq!(state.observe(true), fast);
+        assert_eq!(state.observe(true), fast * 2);
+        state.observe(true);
+        // Capped at the slow interval
+        assert_eq!(state.observe(true), Duration::from_secs(3));
+        assert_eq!(state.observe(false), fast);
+    }
+    #[test]
+    fn test_provenance_steady() {
+        let api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .build_on(NullPort::boxed());
+        let provenance = |ms| Provenance {
+            read_at: std::time::UNIX_EPOCH + Duration::from_millis(ms),
+            labels: api.labels_arc(),
+            firmware_checksum: None,
+            model: None,
+            port: "sim".into(),
+            dev_addr: 0x10,
+            protocol: crate::api::SmdpVersion::V3Plus,
+            crate_version: crate::provenance::CRATE_VERSION,
+            field_latency: Default::default(),
+        };
+        let mut status = crate::history::test::status(60.0);
+        let first = (status.clone(), provenance(0));
+        assert!((status.clone(), provenance(1000)).steady(&first));
+        status.helium_temp += 1.0;
+        assert!(!(status, provenance(1000)).steady(&first));
+    }
+    #[test]
+    fn test_watchdog_stop_bypasses_write_gates() {
+        let mut api = CryomechApiSmdpBuilder::new("sim")
+            .read_timeout_ms(1)
+            .reconnect_attempts(0)
+            .armed_writes(true)
+            .control_token(true)
+            .build_on(NullPort::boxed());
+        // No device answers, but the stop got past the gates onto the wire
+        let stop = watchdog_stop(&mut api);
+        assert!(stop.is_err());
+        assert_eq!(api.diagnostics().transactions, 1);
+        let gate_errors = [Error::WritesNotArmed, Error::ControlTokenRequired];
+        assert!(gate_errors.iter().all(|e| stop != Err(e.to_string())));
+    }
+}
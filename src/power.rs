@@ -0,0 +1,108 @@
+/* Electrical power and energy estimated from the motor current */
+
+use crate::{
+    Real,
+    history::{History, HistoryKind},
+};
+use std::{ops::Range, time::SystemTime};
+
+/// Supply wiring of the compressor motor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phases {
+    Single,
+    Three,
+}
+
+/// Estimates electrical power from the motor current. Only as good as the
+/// configured voltage and power factor; use a meter where accuracy matters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerEstimator {
+    /// Line-to-line voltage (V).
+    pub line_voltage: Real,
+    pub power_factor: Real,
+    pub phases: Phases,
+}
+impl Default for PowerEstimator {
+    /// 208 V three-phase with a power factor of 0.85, a common CPA supply.
+    fn default() -> Self {
+        Self {
+            line_voltage: 208.0,
+            power_factor: 0.85,
+            phases: Phases::Three,
+        }
+    }
+}
+impl PowerEstimator {
+    /// Power in kW for a motor current in Amps.
+    pub fn power_kw(&self, current_amps: Real) -> Real {
+        let phase_factor: Real = match self.phases {
+            Phases::Single => 1.0,
+            Phases::Three => (3.0 as Real).sqrt(),
+        };
+        phase_factor * self.line_voltage * current_amps * self.power_factor / 1000.0
+    }
+    /// Estimated power at each polled snapshot in `range`.
+    pub fn series(&self, history: &History, range: Range<SystemTime>) -> Vec<(SystemTime, Real)> {
+        history
+            .entries()
+            .filter(|e| e.kind == HistoryKind::Poll && range.contains(&e.at))
+            .map(|e| (e.at, self.power_kw(e.status.motor_current_amps as Real)))
+            .collect()
+    }
+    /// Energy in kWh used within `range`, holding each polled current until the
+    /// next poll.
+    pub fn energy_kwh(&self, history: &History, range: Range<SystemTime>) -> Real {
+        let polls: Vec<_> = history
+            .entries()
+            .filter(|e| e.kind == HistoryKind::Poll)
+            .map(|e| (e.at, e.status.motor_current_amps))
+            .collect();
+        polls
+            .windows(2)
+            .filter_map(|pair| {
+                let [(from, amps), (to, _)] = pair else {
+                    return None;
+                };
+                let span = (*to)
+                    .min(range.end)
+                    .duration_since((*from).max(range.start))
+                    .ok()?;
+                Some(self.power_kw(*amps as Real) * (span.as_secs_f64() / 3600.0) as Real)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        history::{HistoryEntry, test::status},
+        status::CompressorStatus,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_energy() {
+        let est = PowerEstimator {
+            line_voltage: 200.0,
+            power_factor: 1.0,
+            phases: Phases::Single,
+        };
+        assert!((est.power_kw(10.0) - 2.0).abs() < 1e-4);
+        let mut history = History::new(4);
+        for (hour, amps) in [(0, 10), (1, 20), (2, 0)] {
+            history.push(HistoryEntry {
+                at: UNIX_EPOCH + Duration::from_secs(hour * 3600),
+                kind: HistoryKind::Poll,
+                status: CompressorStatus {
+                    motor_current_amps: amps,
+                    ..status(0.0)
+                },
+            });
+        }
+        let all = UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(3 * 3600);
+        assert!((est.energy_kwh(&history, all.clone()) - 6.0).abs() < 1e-3);
+        assert_eq!(est.series(&history, all).len(), 3);
+    }
+}
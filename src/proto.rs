@@ -0,0 +1,276 @@
+/* Protobuf types for proto/cryomech.proto, in the layout prost-build generates */
+// Checked in instead of built, so compiling the crate doesn't need protoc.
+// Keep in sync with the .proto file.
+
+use crate::{Real, status};
+
+/// Mirrors `cryomech_api::CompressorStatus`. Readings are always `double` on
+/// the wire, whether or not the `f64` feature is enabled.
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CompressorStatus {
+    #[prost(bool, tag = "1")]
+    pub comp_on: bool,
+    #[prost(bool, tag = "2")]
+    pub err_code_status: bool,
+    #[prost(bool, tag = "3")]
+    pub mem_loss: bool,
+    #[prost(bool, tag = "4")]
+    pub clock_batt_ok: bool,
+    #[prost(bool, tag = "5")]
+    pub clock_batt_low: bool,
+    #[prost(bool, tag = "6")]
+    pub temp_sensor_fail: bool,
+    #[prost(bool, tag = "7")]
+    pub pressure_sensor_fail: bool,
+    #[prost(uint32, tag = "8")]
+    pub comp_minutes: u32,
+    #[prost(uint32, tag = "9")]
+    pub motor_current_amps: u32,
+    #[prost(double, tag = "10")]
+    pub cpu_temp: f64,
+    #[prost(double, tag = "11")]
+    pub input_water_temp: f64,
+    #[prost(double, tag = "12")]
+    pub output_water_temp: f64,
+    #[prost(double, tag = "13")]
+    pub helium_temp: f64,
+    #[prost(double, tag = "14")]
+    pub oil_temp: f64,
+    #[prost(double, tag = "15")]
+    pub min_input_water_temp: f64,
+    #[prost(double, tag = "16")]
+    pub min_output_water_temp: f64,
+    #[prost(double, tag = "17")]
+    pub min_helium_temp: f64,
+    #[prost(double, tag = "18")]
+    pub min_oil_temp: f64,
+    #[prost(double, tag = "19")]
+    pub max_input_water_temp: f64,
+    #[prost(double, tag = "20")]
+    pub max_output_water_temp: f64,
+    #[prost(double, tag = "21")]
+    pub max_helium_temp: f64,
+    #[prost(double, tag = "22")]
+    pub max_oil_temp: f64,
+    #[prost(double, tag = "23")]
+    pub high_side_pressure: f64,
+    #[prost(double, tag = "24")]
+    pub low_side_pressure: f64,
+    #[prost(double, tag = "25")]
+    pub max_high_side_pressure: f64,
+    #[prost(double, tag = "26")]
+    pub max_low_side_pressure: f64,
+    #[prost(double, tag = "27")]
+    pub min_high_side_pressure: f64,
+    #[prost(double, tag = "28")]
+    pub min_low_side_pressure: f64,
+    #[prost(double, tag = "29")]
+    pub avg_high_side_pressure: f64,
+    #[prost(double, tag = "30")]
+    pub avg_low_side_pressure: f64,
+    #[prost(double, tag = "31")]
+    pub high_side_pressure_deriv: f64,
+    #[prost(double, tag = "32")]
+    pub avg_delta_pressure: f64,
+}
+
+/// One value per `cryomech_api::Error` variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ErrorKind {
+    Unspecified = 0,
+    InvalidFormat = 1,
+    AddressMismatch = 2,
+    UnexpectedOpcode = 3,
+    ResponseNotOk = 4,
+    Smdp = 5,
+    Serial = 6,
+    DeviceBusy = 7,
+    ExtraFrames = 8,
+    Interlock = 9,
+    WritesNotArmed = 10,
+    ControlTokenRequired = 11,
+    ActiveFault = 12,
+    Io = 13,
+    Policy = 14,
+    Encoding = 15,
+    PortNotFound = 16,
+    UnsupportedRegister = 17,
+    ModbusException = 18,
+    UnknownUnit = 19,
+    BusClosed = 20,
+    LinkDead = 21,
+    WaitTimeout = 22,
+    BroadcastRead = 23,
+    SrlnoMismatch = 24,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Error {
+    #[prost(enumeration = "ErrorKind", tag = "1")]
+    pub kind: i32,
+    /// Rendered error message
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+
+#[cfg(not(feature = "f64"))]
+fn wide(v: Real) -> f64 {
+    f64::from(v)
+}
+#[cfg(feature = "f64")]
+fn wide(v: Real) -> f64 {
+    v
+}
+#[cfg(not(feature = "f64"))]
+fn narrow(v: f64) -> Real {
+    v as Real
+}
+#[cfg(feature = "f64")]
+fn narrow(v: f64) -> Real {
+    v
+}
+
+impl From<&status::CompressorStatus> for CompressorStatus {
+    fn from(s: &status::CompressorStatus) -> Self {
+        Self {
+            comp_on: s.comp_on,
+            err_code_status: s.err_code_status,
+            mem_loss: s.mem_loss,
+            clock_batt_ok: s.clock_batt_ok,
+            clock_batt_low: s.clock_batt_low,
+            temp_sensor_fail: s.temp_sensor_fail,
+            pressure_sensor_fail: s.pressure_sensor_fail,
+            comp_minutes: s.comp_minutes,
+            motor_current_amps: s.motor_current_amps,
+            cpu_temp: wide(s.cpu_temp),
+            input_water_temp: wide(s.input_water_temp),
+            output_water_temp: wide(s.output_water_temp),
+            helium_temp: wide(s.helium_temp),
+            oil_temp: wide(s.oil_temp),
+            min_input_water_temp: wide(s.min_input_water_temp),
+            min_output_water_temp: wide(s.min_output_water_temp),
+            min_helium_temp: wide(s.min_helium_temp),
+            min_oil_temp: wide(s.min_oil_temp),
+            max_input_water_temp: wide(s.max_input_water_temp),
+            max_output_water_temp: wide(s.max_output_water_temp),
+            max_helium_temp: wide(s.max_helium_temp),
+            max_oil_temp: wide(s.max_oil_temp),
+            high_side_pressure: wide(s.high_side_pressure),
+            low_side_pressure: wide(s.low_side_pressure),
+            max_high_side_pressure: wide(s.max_high_side_pressure),
+            max_low_side_pressure: wide(s.max_low_side_pressure),
+            min_high_side_pressure: wide(s.min_high_side_pressure),
+            min_low_side_pressure: wide(s.min_low_side_pressure),
+            avg_high_side_pressure: wide(s.avg_high_side_pressure),
+            avg_low_side_pressure: wide(s.avg_low_side_pressure),
+            high_side_pressure_deriv: wide(s.high_side_pressure_deriv),
+            avg_delta_pressure: wide(s.avg_delta_pressure),
+        }
+    }
+}
+impl From<CompressorStatus> for status::CompressorStatus {
+    fn from(s: CompressorStatus) -> Self {
+        Self {
+            comp_on: s.comp_on,
+            err_code_status: s.err_code_status,
+            mem_loss: s.mem_loss,
+            clock_batt_ok: s.clock_batt_ok,
+            clock_batt_low: s.clock_batt_low,
+            temp_sensor_fail: s.temp_sensor_fail,
+            pressure_sensor_fail: s.pressure_sensor_fail,
+            comp_minutes: s.comp_minutes,
+            motor_current_amps: s.motor_current_amps,
+            cpu_temp: narrow(s.cpu_temp),
+            input_water_temp: narrow(s.input_water_temp),
+            output_water_temp: narrow(s.output_water_temp),
+            helium_temp: narrow(s.helium_temp),
+            oil_temp: narrow(s.oil_temp),
+            min_input_water_temp: narrow(s.min_input_water_temp),
+            min_output_water_temp: narrow(s.min_output_water_temp),
+            min_helium_temp: narrow(s.min_helium_temp),
+            min_oil_temp: narrow(s.min_oil_temp),
+            max_input_water_temp: narrow(s.max_input_water_temp),
+            max_output_water_temp: narrow(s.max_output_water_temp),
+            max_helium_temp: narrow(s.max_helium_temp),
+            max_oil_temp: narrow(s.max_oil_temp),
+            high_side_pressure: narrow(s.high_side_pressure),
+            low_side_pressure: narrow(s.low_side_pressure),
+            max_high_side_pressure: narrow(s.max_high_side_pressure),
+            max_low_side_pressure: narrow(s.max_low_side_pressure),
+            min_high_side_pressure: narrow(s.min_high_side_pressure),
+            min_low_side_pressure: narrow(s.min_low_side_pressure),
+            avg_high_side_pressure: narrow(s.avg_high_side_pressure),
+            avg_low_side_pressure: narrow(s.avg_low_side_pressure),
+            high_side_pressure_deriv: narrow(s.high_side_pressure_deriv),
+            avg_delta_pressure: narrow(s.avg_delta_pressure),
+        }
+    }
+}
+impl From<&crate::Error> for ErrorKind {
+    fn from(e: &crate::Error) -> Self {
+        use crate::Error as E;
+        match e {
+            E::InvalidFormat(_) => Self::InvalidFormat,
+            E::AddressMismatch { .. } => Self::AddressMismatch,
+            E::UnexpectedOpcode(_) => Self::UnexpectedOpcode,
+            E::ResponseNotOk(_) => Self::ResponseNotOk,
+            E::Smdp(_) => Self::Smdp,
+            E::Serial(_) => Self::Serial,
+            E::DeviceBusy => Self::DeviceBusy,
+            E::ExtraFrames(_) => Self::ExtraFrames,
+            E::WithFrames { source, .. } => Self::from(source.as_ref()),
+            E::Interlock(_) => Self::Interlock,
+            E::WritesNotArmed => Self::WritesNotArmed,
+            E::ControlTokenRequired => Self::ControlTokenRequired,
+            E::ActiveFault => Self::ActiveFault,
+            E::Io(_) => Self::Io,
+            #[cfg(feature = "policy")]
+            E::Policy(_) => Self::Policy,
+            #[cfg(any(feature = "cbor", feature = "msgpack"))]
+            E::Encoding(_) => Self::Encoding,
+            E::PortNotFound => Self::PortNotFound,
+            E::UnsupportedRegister(_) => Self::UnsupportedRegister,
+            E::ModbusException(_) => Self::ModbusException,
+            E::UnknownUnit(_) => Self::UnknownUnit,
+            E::BusClosed => Self::BusClosed,
+            E::LinkDead(_) => Self::LinkDead,
+            E::WaitTimeout(_) => Self::WaitTimeout,
+            E::BroadcastRead => Self::BroadcastRead,
+            E::SrlnoMismatch => Self::SrlnoMismatch,
+        }
+    }
+}
+impl From<&crate::Error> for Error {
+    fn from(e: &crate::Error) -> Self {
+        Self {
+            kind: ErrorKind::from(e).into(),
+            message: e.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history::test::status;
+    use prost::Message;
+
+    #[test]
+    fn test_status_round_trip() {
+        let s = status(60.5);
+        let bytes = CompressorStatus::from(&s).encode_to_vec();
+        let decoded = CompressorStatus::decode(bytes.as_slice()).unwrap();
+        assert_eq!(status::CompressorStatus::from(decoded), s);
+    }
+    #[test]
+    fn test_error_kind() {
+        let e = crate::Error::WithFrames {
+            source: Box::new(crate::Error::DeviceBusy),
+            dump: Default::default(),
+        };
+        let proto = Error::from(&e);
+        assert_eq!(proto.kind(), ErrorKind::DeviceBusy);
+        assert_eq!(proto.message, e.to_string());
+    }
+}
@@ -0,0 +1,97 @@
+/* Where and how a snapshot was read, kept alongside archived data */
+
+use crate::{api::SmdpVersion, labels::DeviceLabels, model::CompressorModel};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Version of this crate, recorded with each snapshot.
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Describes the read behind a snapshot, so archived readings stay
+/// interpretable after the setup that produced them is gone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// When the read started.
+    pub read_at: SystemTime,
+    pub labels: Arc<DeviceLabels>,
+    /// None if the unit didn't answer the firmware checksum read.
+    pub firmware_checksum: Option<u32>,
+    pub model: Option<CompressorModel>,
+    pub port: String,
+    pub dev_addr: u8,
+    pub protocol: SmdpVersion,
+    pub crate_version: &'static str,
+    /// Time spent reading each dictionary entry, by hash value. Array entries
+    /// are timed as a whole.
+    pub field_latency: BTreeMap<u16, Duration>,
+}
+impl Provenance {
+    /// Flattens the record into key/value pairs, e.g. for file headers or
+    /// metrics tags. Labels come first, then the read metadata, then one
+    /// `latency_us.<hash>` pair per entry.
+    pub fn tags(&self) -> Vec<(String, String)> {
+        let read_at = self
+            .read_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let mut tags: Vec<_> = self
+            .labels
+            .tags()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        tags.extend([
+            ("read_at_ms".into(), read_at.to_string()),
+            ("port".into(), self.port.clone()),
+            ("dev_addr".into(), format!("0x{:02X}", self.dev_addr)),
+            ("protocol".into(), format!("SMDP {:?}", self.protocol)),
+            ("crate_version".into(), self.crate_version.into()),
+        ]);
+        if let Some(checksum) = self.firmware_checksum {
+            tags.push(("firmware_checksum".into(), format!("0x{checksum:08X}")));
+        }
+        if let Some(model) = self.model {
+            tags.push(("model".into(), format!("{model:?}")));
+        }
+        tags.extend(self.field_latency.iter().map(|(hashval, latency)| {
+            (
+                format!("latency_us.0x{hashval:04X}"),
+                latency.as_micros().to_string(),
+            )
+        }));
+        tags
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let provenance = Provenance {
+            read_at: UNIX_EPOCH + Duration::from_secs(1),
+            labels: Arc::new(DeviceLabels::new().name("cryo-a")),
+            firmware_checksum: Some(0xBEEF),
+            model: None,
+            port: "/dev/ttyUSB0".into(),
+            dev_addr: 0x10,
+            protocol: SmdpVersion::V3Plus,
+            crate_version: CRATE_VERSION,
+            field_latency: BTreeMap::from([(0x0D8F, Duration::from_micros(4200))]),
+        };
+        let tags = provenance.tags();
+        let get = |key: &str| tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(tags[0], ("name".into(), "cryo-a".into()));
+        assert_eq!(get("read_at_ms"), Some("1000"));
+        assert_eq!(get("dev_addr"), Some("0x10"));
+        assert_eq!(get("protocol"), Some("SMDP V3Plus"));
+        assert_eq!(get("firmware_checksum"), Some("0x0000BEEF"));
+        assert_eq!(get("crate_version"), Some(CRATE_VERSION));
+        assert_eq!(get("latency_us.0x0D8F"), Some("4200"));
+        assert_eq!(get("model"), None);
+    }
+}
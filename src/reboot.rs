@@ -0,0 +1,120 @@
+/* Detection of a panel reset in the middle of a session */
+
+use crate::{Error, labels::DeviceLabels};
+use std::{collections::HashSet, sync::Arc, time::SystemTime};
+
+// Suspicious replies in a row before the panel is taken to have rebooted
+const REBOOT_ERRORS: u32 = 2;
+
+/// What gave the reboot away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebootSignature {
+    /// V3+ replies stopped echoing the request's SRLNO.
+    SrlnoMismatch,
+    /// The panel rejected dictionary entries it had answered earlier.
+    Naks,
+}
+
+/// Raised when a handle saw a panel reboot and re-handshook with the unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceRebooted {
+    pub at: SystemTime,
+    pub signature: RebootSignature,
+    /// Firmware checksum read after the reboot, None if the unit didn't
+    /// answer yet.
+    pub firmware_checksum: Option<u32>,
+    pub labels: Arc<DeviceLabels>,
+}
+
+/// Watches transaction results for the signature of a panel reset. Only read
+/// NAKs count, and only for entries the same device answered before, so
+/// probing entries a unit doesn't have is not mistaken for a reboot.
+#[derive(Debug, Default)]
+pub(crate) struct RebootDetector {
+    /// (device address, hash value) of entries that were read successfully
+    answered: HashSet<(u8, u16)>,
+    suspect: u32,
+}
+impl RebootDetector {
+    pub(crate) fn observe<T>(
+        &mut self,
+        addr: u8,
+        is_read: bool,
+        hashval: u16,
+        result: &Result<T, Error>,
+    ) -> Option<RebootSignature> {
+        let signature = match result {
+            Ok(_) => {
+                if is_read {
+                    self.answered.insert((addr, hashval));
+                }
+                self.suspect = 0;
+                return None;
+            }
+            Err(e) if matches!(e.inner(), Error::SrlnoMismatch) => RebootSignature::SrlnoMismatch,
+            Err(e) if is_read && e.is_nak() && self.answered.contains(&(addr, hashval)) => {
+                RebootSignature::Naks
+            }
+            Err(_) => return None,
+        };
+        self.suspect += 1;
+        if self.suspect < REBOOT_ERRORS {
+            return None;
+        }
+        self.suspect = 0;
+        Some(signature)
+    }
+    /// Drops the run of suspicious replies when the handle is pointed at
+    /// another device. Answered entries are kept, they are per device.
+    pub(crate) fn switch_device(&mut self) {
+        self.suspect = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reboot_signature() {
+        // Any rejection counts, the Modbus one is just easy to build
+        let nak = || Err::<(), _>(Error::ModbusException(0x02));
+        let mut detector = RebootDetector::default();
+        // Probing entries that were never answered
+        assert_eq!(detector.observe(0x10, true, 0x1111, &nak()), None);
+        assert_eq!(detector.observe(0x10, true, 0x1111, &nak()), None);
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &Ok(())), None);
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &nak()), None);
+        assert_eq!(
+            detector.observe(0x10, true, 0x5F95, &nak()),
+            Some(RebootSignature::Naks)
+        );
+        let mismatch = || Err::<(), _>(Error::SrlnoMismatch);
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &mismatch()), None);
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &Ok(())), None);
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &mismatch()), None);
+        assert_eq!(
+            detector.observe(0x10, true, 0xAA50, &mismatch()),
+            Some(RebootSignature::SrlnoMismatch)
+        );
+    }
+    #[test]
+    fn test_naks_per_device_and_read() {
+        let nak = || Err::<(), _>(Error::ModbusException(0x02));
+        let mut detector = RebootDetector::default();
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &Ok(())), None);
+        // Another device on the bus without the entry
+        assert_eq!(detector.observe(0x11, true, 0x5F95, &nak()), None);
+        assert_eq!(detector.observe(0x11, true, 0x5F95, &nak()), None);
+        // Refused writes, e.g. while the panel is busy
+        assert_eq!(detector.observe(0x10, false, 0x5F95, &nak()), None);
+        assert_eq!(detector.observe(0x10, false, 0x5F95, &nak()), None);
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &nak()), None);
+        detector.switch_device();
+        assert_eq!(detector.observe(0x10, true, 0x5F95, &nak()), None);
+        assert_eq!(
+            detector.observe(0x10, true, 0x5F95, &nak()),
+            Some(RebootSignature::Naks)
+        );
+    }
+}
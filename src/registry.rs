@@ -0,0 +1,129 @@
+/* Remembers which port each compressor was last seen on, across adapter re-enumeration */
+
+use crate::{
+    CResult,
+    discovery::{self, UsbMatch},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Where a device was last seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortRecord {
+    pub port: String,
+    /// Serial number of the USB adapter on `port`, if it has one.
+    pub usb_serial: Option<String>,
+}
+
+/// Device-to-port mapping persisted in a small text file, one device per
+/// line: `device<TAB>port<TAB>usb serial`. Devices are keyed by a caller-chosen
+/// name, typically the panel serial number.
+#[derive(Debug, Clone)]
+pub struct PortRegistry {
+    path: PathBuf,
+    entries: BTreeMap<String, PortRecord>,
+}
+impl PortRegistry {
+    /// Loads the registry at `path`. A missing file gives an empty registry.
+    pub fn load(path: impl AsRef<Path>) -> CResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, entries })
+    }
+    pub fn save(&self) -> CResult<()> {
+        fs::write(&self.path, format(&self.entries))?;
+        Ok(())
+    }
+    pub fn get(&self, device: &str) -> Option<&PortRecord> {
+        self.entries.get(device)
+    }
+    /// Records `device` as seen on `port`, along with the port's USB adapter
+    /// serial number if it has one. Call `save()` to persist.
+    pub fn remember(&mut self, device: &str, port: &str) -> CResult<()> {
+        let usb_serial = discovery::usb_serial(port)?;
+        self.entries.insert(
+            device.into(),
+            PortRecord {
+                port: port.into(),
+                usb_serial,
+            },
+        );
+        Ok(())
+    }
+    /// Current port of `device`: the port of its recorded USB adapter if that
+    /// is present, else the recorded port name if it still exists.
+    pub fn resolve(&self, device: &str) -> CResult<Option<String>> {
+        let Some(record) = self.entries.get(device) else {
+            return Ok(None);
+        };
+        if let Some(serial) = &record.usb_serial
+            && let Some(port) = discovery::find_ports(&UsbMatch::new().serial_number(serial))?
+                .into_iter()
+                .next()
+        {
+            return Ok(Some(port));
+        }
+        let present = serialport::available_ports()?
+            .into_iter()
+            .any(|p| p.port_name == record.port);
+        Ok(present.then(|| record.port.clone()))
+    }
+}
+
+fn parse(text: &str) -> BTreeMap<String, PortRecord> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let device = fields.next().filter(|d| !d.is_empty())?;
+            let port = fields.next().filter(|p| !p.is_empty())?;
+            let usb_serial = fields.next().filter(|s| !s.is_empty()).map(Into::into);
+            Some((
+                device.into(),
+                PortRecord {
+                    port: port.into(),
+                    usb_serial,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn format(entries: &BTreeMap<String, PortRecord>) -> String {
+    let mut text = String::new();
+    for (device, record) in entries {
+        let serial = record.usb_serial.as_deref().unwrap_or("");
+        let _ = writeln!(text, "{device}\t{}\t{serial}", record.port);
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registry_round_trip() {
+        let text = "CP2870-1234\t/dev/ttyUSB0\tA10K5XYZ\nCP1110-77\tCOM4\t\n";
+        let entries = parse(text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries.get("CP1110-77"),
+            Some(&PortRecord {
+                port: "COM4".into(),
+                usb_serial: None
+            })
+        );
+        assert_eq!(
+            format(&entries),
+            "CP1110-77\tCOM4\t\nCP2870-1234\t/dev/ttyUSB0\tA10K5XYZ\n"
+        );
+    }
+}
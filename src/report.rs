@@ -0,0 +1,245 @@
+/* Per-day summaries of the snapshot history, as JSON or text */
+
+use crate::{
+    Real,
+    history::{History, HistoryKind},
+    status::CompressorStatus,
+};
+use std::{
+    fmt::Write as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Output format of `DailySummary::render()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// One JSON object per summary.
+    Json,
+    /// Human-readable block of `name: value` lines.
+    Text,
+}
+
+/// Lowest and highest value seen during the day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinMax {
+    pub min: Real,
+    pub max: Real,
+}
+impl MinMax {
+    fn new(value: Real) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+    fn add(&mut self, value: Real) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+/// Summary of one day of polled snapshots. Min/max are over the live readings
+/// in the history, not the panel's own min/max registers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailySummary {
+    /// Local date as `YYYY-MM-DD`.
+    pub date: String,
+    /// Compressor run time accumulated during the day.
+    pub run_hours: Real,
+    pub starts: u32,
+    pub stops: u32,
+    /// Number of times an error or sensor failure became active.
+    pub alarms: u32,
+    pub high_side_pressure: MinMax,
+    pub low_side_pressure: MinMax,
+    pub helium_temp: MinMax,
+    pub oil_temp: MinMax,
+    pub input_water_temp: MinMax,
+    pub output_water_temp: MinMax,
+}
+impl DailySummary {
+    fn new(date: String, status: &CompressorStatus) -> Self {
+        Self {
+            date,
+            run_hours: 0.0,
+            starts: 0,
+            stops: 0,
+            alarms: 0,
+            high_side_pressure: MinMax::new(status.high_side_pressure),
+            low_side_pressure: MinMax::new(status.low_side_pressure),
+            helium_temp: MinMax::new(status.helium_temp),
+            oil_temp: MinMax::new(status.oil_temp),
+            input_water_temp: MinMax::new(status.input_water_temp),
+            output_water_temp: MinMax::new(status.output_water_temp),
+        }
+    }
+    fn add(&mut self, status: &CompressorStatus, prev: Option<&CompressorStatus>) {
+        self.high_side_pressure.add(status.high_side_pressure);
+        self.low_side_pressure.add(status.low_side_pressure);
+        self.helium_temp.add(status.helium_temp);
+        self.oil_temp.add(status.oil_temp);
+        self.input_water_temp.add(status.input_water_temp);
+        self.output_water_temp.add(status.output_water_temp);
+        let Some(prev) = prev else {
+            return;
+        };
+        match (prev.comp_on, status.comp_on) {
+            (false, true) => self.starts += 1,
+            (true, false) => self.stops += 1,
+            _ => {}
+        }
+        if alarm(status) && !alarm(prev) {
+            self.alarms += 1;
+        }
+        // Counter resets (e.g. a replaced panel) are not counted as run time
+        let minutes = status.comp_minutes.saturating_sub(prev.comp_minutes);
+        self.run_hours += minutes as Real / 60.0;
+    }
+    fn fields(&self) -> [(&'static str, MinMax); 6] {
+        [
+            ("high_side_pressure", self.high_side_pressure),
+            ("low_side_pressure", self.low_side_pressure),
+            ("helium_temp", self.helium_temp),
+            ("oil_temp", self.oil_temp),
+            ("input_water_temp", self.input_water_temp),
+            ("output_water_temp", self.output_water_temp),
+        ]
+    }
+    pub fn render(&self, format: ReportFormat) -> String {
+        let mut out = String::new();
+        match format {
+            ReportFormat::Json => {
+                let _ = write!(
+                    out,
+                    "{{\"date\":\"{}\",\"run_hours\":{:.2},\"starts\":{},\"stops\":{},\"alarms\":{}",
+                    self.date, self.run_hours, self.starts, self.stops, self.alarms
+                );
+                for (name, range) in self.fields() {
+                    let _ = write!(
+                        out,
+                        ",\"{name}\":{{\"min\":{:.1},\"max\":{:.1}}}",
+                        range.min, range.max
+                    );
+                }
+                out.push('}');
+            }
+            ReportFormat::Text => {
+                let _ = writeln!(out, "date: {}", self.date);
+                let _ = writeln!(out, "run_hours: {:.2}", self.run_hours);
+                let _ = writeln!(out, "starts: {}", self.starts);
+                let _ = writeln!(out, "stops: {}", self.stops);
+                let _ = writeln!(out, "alarms: {}", self.alarms);
+                for (name, range) in self.fields() {
+                    let _ = writeln!(out, "{name}: {:.1} .. {:.1}", range.min, range.max);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn alarm(status: &CompressorStatus) -> bool {
+    status.err_code_status || status.temp_sensor_fail || status.pressure_sensor_fail
+}
+
+fn local_day(t: SystemTime, utc_offset_secs: i64) -> i64 {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    (secs + utc_offset_secs).div_euclid(SECS_PER_DAY)
+}
+
+/// `YYYY-MM-DD` of a day number counted from 1970-01-01 (proleptic Gregorian).
+fn civil_date(day: i64) -> String {
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+impl History {
+    /// One summary per local day that has polled snapshots, oldest first. Days
+    /// are split at midnight UTC shifted by `utc_offset_secs`. Transitions and
+    /// run time across midnight count towards the later day.
+    pub fn daily_summaries(&self, utc_offset_secs: i64) -> Vec<DailySummary> {
+        let mut summaries: Vec<(i64, DailySummary)> = Vec::new();
+        let mut prev: Option<&CompressorStatus> = None;
+        for entry in self.entries().filter(|e| e.kind == HistoryKind::Poll) {
+            let day = local_day(entry.at, utc_offset_secs);
+            match summaries.last_mut() {
+                Some((d, summary)) if *d == day => summary.add(&entry.status, prev),
+                _ => {
+                    let mut summary = DailySummary::new(civil_date(day), &entry.status);
+                    summary.add(&entry.status, prev);
+                    summaries.push((day, summary));
+                }
+            }
+            prev = Some(&entry.status);
+        }
+        summaries.into_iter().map(|(_, s)| s).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history::{HistoryEntry, test::status};
+    use std::time::Duration;
+
+    fn entry(hour: u64, comp_on: bool, comp_minutes: u32, helium_temp: f32) -> HistoryEntry {
+        HistoryEntry {
+            at: UNIX_EPOCH + Duration::from_secs(hour * 3600),
+            kind: HistoryKind::Poll,
+            status: CompressorStatus {
+                comp_on,
+                comp_minutes,
+                ..status(helium_temp)
+            },
+        }
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(20_742), "2026-10-16");
+        assert_eq!(civil_date(-1), "1969-12-31");
+    }
+    #[test]
+    fn test_daily_summaries() {
+        let mut history = History::new(16);
+        history.push(entry(1, false, 0, 20.0));
+        history.push(entry(2, true, 0, 25.0));
+        history.push(entry(12, true, 600, 40.0));
+        history.push(entry(23, false, 1200, 30.0));
+        history.push(entry(25, true, 1200, 30.0));
+        let days = history.daily_summaries(0);
+        assert_eq!(days.len(), 2);
+        let first = &days[0];
+        assert_eq!(first.date, "1970-01-01");
+        assert_eq!((first.starts, first.stops), (1, 1));
+        assert!((first.run_hours - 20.0).abs() < 1e-4);
+        assert_eq!(
+            first.helium_temp,
+            MinMax {
+                min: 20.0,
+                max: 40.0
+            }
+        );
+        assert_eq!(days[1].starts, 1);
+        assert!(
+            days[1]
+                .render(ReportFormat::Json)
+                .starts_with("{\"date\":\"1970-01-02\"")
+        );
+        assert!(first.render(ReportFormat::Text).contains("starts: 1\n"));
+    }
+}
@@ -0,0 +1,175 @@
+/* Time-of-day start/stop schedule, executed by the background poller */
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+impl Weekday {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+    // 1970-01-01 was a Thursday
+    fn of_day(day: i64) -> Self {
+        const DAYS: [Weekday; 7] = [
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+            Weekday::Sun,
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+        ];
+        DAYS[day.rem_euclid(7) as usize]
+    }
+}
+
+/// What a schedule entry does when it fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledAction {
+    /// Interlock-checked start, skipped if any interlock fails.
+    Start,
+    Stop,
+    /// Clears the pressure/temperature min/max values, e.g. at a shift change.
+    /// With a poller history, the values are archived there first.
+    ResetMinMax,
+}
+
+/// Fires at `hour:minute` on the selected weekdays (every day by default).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleEntry {
+    hour: u8,
+    minute: u8,
+    days: u8,
+    action: ScheduledAction,
+}
+impl ScheduleEntry {
+    /// Entry firing every day. Hours and minutes are clamped to 23:59.
+    pub fn daily(hour: u8, minute: u8, action: ScheduledAction) -> Self {
+        Self {
+            hour: hour.min(23),
+            minute: minute.min(59),
+            days: 0x7F,
+            action,
+        }
+    }
+    /// Restricts the entry to the given weekdays.
+    pub fn on(mut self, days: &[Weekday]) -> Self {
+        self.days = days.iter().fold(0, |mask, d| mask | d.bit());
+        self
+    }
+    pub fn action(&self) -> ScheduledAction {
+        self.action
+    }
+    /// Most recent firing time at or before `local` (seconds, local time).
+    fn last_fire(&self, local: i64) -> Option<i64> {
+        let offset = i64::from(self.hour) * 3600 + i64::from(self.minute) * 60;
+        let today = local.div_euclid(SECS_PER_DAY);
+        (0..=7)
+            .map(|back| today - back)
+            .filter(|day| self.days & Weekday::of_day(*day).bit() != 0)
+            .map(|day| day * SECS_PER_DAY + offset)
+            .find(|t| *t <= local)
+    }
+}
+
+/// Set of start/stop/reset times. Times are evaluated in UTC shifted by the
+/// configured offset; there is no daylight saving handling.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    entries: Vec<ScheduleEntry>,
+    utc_offset_secs: i64,
+}
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn entry(mut self, entry: ScheduleEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+    /// Offset of local time from UTC, e.g. -5 h for US Eastern standard time.
+    pub fn utc_offset_secs(mut self, offset: i64) -> Self {
+        self.utc_offset_secs = offset;
+        self
+    }
+    fn local(&self, t: SystemTime) -> i64 {
+        let secs = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        secs + self.utc_offset_secs
+    }
+    /// Action of the latest entry that fired in `(since, now]`, if any. Only
+    /// the latest one matters: a stop followed by a start ends up started.
+    pub(crate) fn due(&self, since: SystemTime, now: SystemTime) -> Option<ScheduledAction> {
+        let (since, now) = (self.local(since), self.local(now));
+        self.entries
+            .iter()
+            .filter_map(|e| e.last_fire(now).map(|t| (t, e.action)))
+            .filter(|(t, _)| *t > since)
+            .max_by_key(|(t, _)| *t)
+            .map(|(_, action)| action)
+    }
+}
+
+/// Record of the last scheduled action the poller carried out.
+#[derive(Debug, Clone)]
+pub struct ScheduledRun {
+    pub action: ScheduledAction,
+    pub at: SystemTime,
+    /// Rendered error if the action failed or was refused by an interlock.
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(day: u64, hour: u64, minute: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(day * 86_400 + hour * 3600 + minute * 60)
+    }
+
+    #[test]
+    fn test_due_window() {
+        let sched = Schedule::new()
+            .entry(ScheduleEntry::daily(22, 0, ScheduledAction::Stop))
+            .entry(ScheduleEntry::daily(6, 30, ScheduledAction::Start));
+        assert_eq!(
+            sched.due(at(3, 21, 59), at(3, 22, 0)),
+            Some(ScheduledAction::Stop)
+        );
+        assert_eq!(sched.due(at(3, 22, 0), at(3, 22, 1)), None);
+        assert_eq!(
+            sched.due(at(3, 23, 0), at(4, 6, 31)),
+            Some(ScheduledAction::Start)
+        );
+        // Both fired while the poller was away, latest wins
+        assert_eq!(
+            sched.due(at(3, 21, 0), at(4, 7, 0)),
+            Some(ScheduledAction::Start)
+        );
+    }
+    #[test]
+    fn test_weekdays_and_offset() {
+        // Day 0 was a Thursday; 08:00 at UTC-5 is 13:00 UTC
+        let sched = Schedule::new()
+            .utc_offset_secs(-5 * 3600)
+            .entry(ScheduleEntry::daily(8, 0, ScheduledAction::Start).on(&[Weekday::Fri]));
+        assert_eq!(sched.due(at(0, 12, 0), at(0, 14, 0)), None);
+        assert_eq!(
+            sched.due(at(1, 12, 0), at(1, 14, 0)),
+            Some(ScheduledAction::Start)
+        );
+    }
+}
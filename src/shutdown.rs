@@ -0,0 +1,61 @@
+/* Monitoring of a compressor shutdown until pressures equalize and the motor stops */
+
+use crate::{Real, api::ControlOutcome};
+use std::time::Duration;
+
+/// How `stop_and_verify()` watches the compressor after the stop command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShutdownCheck {
+    /// Longest time to wait for the shutdown to complete.
+    pub window: Duration,
+    /// Delay between readings.
+    pub interval: Duration,
+    /// High/low side difference, in PSI, below which pressures count as equalized.
+    pub max_delta_pressure: Real,
+    /// Motor current, in Amps, at or below which the motor counts as stopped.
+    pub max_motor_current: u32,
+}
+impl Default for ShutdownCheck {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(120),
+            interval: Duration::from_secs(1),
+            max_delta_pressure: 20.0,
+            max_motor_current: 0,
+        }
+    }
+}
+impl ShutdownCheck {
+    pub(crate) fn settled(&self, delta_pressure: Real, motor_current: u32) -> bool {
+        delta_pressure.abs() <= self.max_delta_pressure && motor_current <= self.max_motor_current
+    }
+}
+
+/// Result of `stop_and_verify()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShutdownReport {
+    /// Outcome of the stop command itself.
+    pub outcome: ControlOutcome,
+    /// True if pressures equalized and the motor stopped within the window.
+    pub completed: bool,
+    /// High minus low side pressure at the last reading, PSI.
+    pub delta_pressure: Real,
+    /// Motor current at the last reading, Amps.
+    pub motor_current: u32,
+    /// Time from the stop command to the last reading.
+    pub elapsed: Duration,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_settled() {
+        let check = ShutdownCheck::default();
+        assert!(check.settled(5.0, 0));
+        assert!(check.settled(-5.0, 0));
+        assert!(!check.settled(150.0, 0));
+        assert!(!check.settled(5.0, 12));
+    }
+}
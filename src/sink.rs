@@ -0,0 +1,315 @@
+/* Decouples the poller from slow reading sinks */
+
+use crate::{
+    Error,
+    poller::{PollerEvent, ReadingSink},
+};
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// How often a poller waiting on a full queue checks that the sink thread is
+// still alive
+const SINK_ALIVE_CHECK: Duration = Duration::from_millis(100);
+
+/// What the poller does when its sink can't keep up, e.g. a network exporter
+/// or a stalled disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Call the sink on the poller thread. A slow sink delays the next poll.
+    #[default]
+    Block,
+    /// Queue up to this many items for a sink thread and drop the oldest
+    /// reading when full, or the oldest item if no reading is queued. Polling
+    /// never waits.
+    DropOldest(usize),
+    /// Queue up to this many items for a sink thread. Polling only waits
+    /// once the queue is full.
+    Buffer(usize),
+}
+
+enum Message<T> {
+    Snapshot(T),
+    Event(PollerEvent),
+    Error(Error),
+}
+
+struct Queue<T> {
+    items: VecDeque<Message<T>>,
+    closed: bool,
+}
+
+pub(crate) struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    // Signalled when items are added or the queue is closed
+    filled: Condvar,
+    // Signalled when the sink thread takes an item
+    drained: Condvar,
+}
+
+/// The poller's side of its sink, applying the backpressure policy.
+pub(crate) enum SinkDispatch<T, S> {
+    Inline(S),
+    Queued {
+        shared: Arc<Shared<T>>,
+        capacity: usize,
+        drop_oldest: bool,
+        dropped: Arc<AtomicU64>,
+        thread: JoinHandle<()>,
+    },
+}
+impl<T, S> SinkDispatch<T, S>
+where
+    T: Clone + Send + 'static,
+    S: ReadingSink<T> + 'static,
+{
+    pub(crate) fn new(sink: S, policy: Backpressure, dropped: Arc<AtomicU64>) -> Self {
+        let (capacity, drop_oldest) = match policy {
+            Backpressure::Block => return Self::Inline(sink),
+            Backpressure::DropOldest(n) => (n.max(1), true),
+            Backpressure::Buffer(n) => (n.max(1), false),
+        };
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                items: VecDeque::with_capacity(capacity.min(4096)),
+                closed: false,
+            }),
+            filled: Condvar::new(),
+            drained: Condvar::new(),
+        });
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::spawn(move || drain(&thread_shared, sink));
+        Self::Queued {
+            shared,
+            capacity,
+            drop_oldest,
+            dropped,
+            thread,
+        }
+    }
+    pub(crate) fn snapshot(&mut self, value: &T) {
+        match self {
+            Self::Inline(sink) => sink.on_snapshot(value),
+            Self::Queued { .. } => self.enqueue(Message::Snapshot(value.clone())),
+        }
+    }
+    pub(crate) fn event(&mut self, event: PollerEvent) {
+        match self {
+            Self::Inline(sink) => sink.on_event(&event),
+            Self::Queued { .. } => self.enqueue(Message::Event(event)),
+        }
+    }
+    pub(crate) fn error(&mut self, error: Error) {
+        match self {
+            Self::Inline(sink) => sink.on_error(&error),
+            Self::Queued { .. } => self.enqueue(Message::Error(error)),
+        }
+    }
+    /// Delivers everything still queued, then flushes the sink.
+    pub(crate) fn flush(self) {
+        match self {
+            Self::Inline(mut sink) => sink.flush(),
+            Self::Queued { shared, thread, .. } => {
+                shared
+                    .queue
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .closed = true;
+                shared.filled.notify_one();
+                let _ = thread.join();
+            }
+        }
+    }
+    fn enqueue(&mut self, message: Message<T>) {
+        let Self::Queued {
+            shared,
+            capacity,
+            drop_oldest,
+            dropped,
+            thread,
+        } = self
+        else {
+            return;
+        };
+        // Nobody will take the item if the sink panicked
+        if thread.is_finished() {
+            dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let mut queue = shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if *drop_oldest {
+            if queue.items.len() >= *capacity {
+                // Readings go first so events and errors survive a burst
+                let oldest = queue
+                    .items
+                    .iter()
+                    .position(|m| matches!(m, Message::Snapshot(_)))
+                    .unwrap_or(0);
+                queue.items.remove(oldest);
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        } else {
+            while queue.items.len() >= *capacity {
+                if thread.is_finished() {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                queue = shared
+                    .drained
+                    .wait_timeout(queue, SINK_ALIVE_CHECK)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+        queue.items.push_back(message);
+        drop(queue);
+        shared.filled.notify_one();
+    }
+}
+
+/// Sink thread: hands queued items to the sink until the queue is closed and
+/// empty.
+fn drain<T, S: ReadingSink<T>>(shared: &Shared<T>, mut sink: S) {
+    loop {
+        let mut queue = shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+        while queue.items.is_empty() && !queue.closed {
+            queue = shared.filled.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+        let Some(message) = queue.items.pop_front() else {
+            break;
+        };
+        drop(queue);
+        shared.drained.notify_one();
+        match message {
+            Message::Snapshot(value) => sink.on_snapshot(&value),
+            Message::Event(event) => sink.on_event(&event),
+            Message::Error(error) => sink.on_error(&error),
+        }
+    }
+    sink.flush();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+
+    // Blocks on each reading until the test lets it through
+    struct Gate {
+        started: mpsc::Sender<()>,
+        open: mpsc::Receiver<()>,
+        out: mpsc::Sender<u32>,
+    }
+    impl ReadingSink<u32> for Gate {
+        fn on_snapshot(&mut self, value: &u32) {
+            let _ = self.started.send(());
+            let _ = self.open.recv();
+            let _ = self.out.send(*value);
+        }
+        fn on_error(&mut self, _error: &Error) {
+            let _ = self.out.send(0);
+        }
+    }
+    fn gate() -> (
+        Gate,
+        mpsc::Receiver<()>,
+        mpsc::Sender<()>,
+        mpsc::Receiver<u32>,
+    ) {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (open_tx, open_rx) = mpsc::channel();
+        let (out_tx, out_rx) = mpsc::channel();
+        let gate = Gate {
+            started: started_tx,
+            open: open_rx,
+            out: out_tx,
+        };
+        (gate, started_rx, open_tx, out_rx)
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (gate, started_rx, open_tx, out_rx) = gate();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut dispatch =
+            SinkDispatch::new(gate, Backpressure::DropOldest(2), Arc::clone(&dropped));
+        dispatch.snapshot(&1);
+        // The sink thread holds the first reading, the queue takes the rest
+        started_rx.recv().unwrap();
+        dispatch.snapshot(&2);
+        dispatch.error(Error::BusClosed);
+        for value in 3..=5 {
+            dispatch.snapshot(&value);
+        }
+        // Only readings make room, the error stays queued
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+        for _ in 0..2 {
+            open_tx.send(()).unwrap();
+        }
+        dispatch.flush();
+        let delivered: Vec<_> = out_rx.try_iter().collect();
+        assert_eq!(delivered, [1, 0, 5]);
+    }
+    #[test]
+    fn test_drop_oldest_errors() {
+        let (gate, started_rx, open_tx, out_rx) = gate();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut dispatch =
+            SinkDispatch::new(gate, Backpressure::DropOldest(2), Arc::clone(&dropped));
+        dispatch.snapshot(&1);
+        started_rx.recv().unwrap();
+        // A dead link reporting one error per poll stays within capacity
+        for _ in 0..10 {
+            dispatch.error(Error::BusClosed);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 8);
+        open_tx.send(()).unwrap();
+        dispatch.flush();
+        let delivered: Vec<_> = out_rx.try_iter().collect();
+        assert_eq!(delivered, [1, 0, 0]);
+    }
+    #[test]
+    fn test_buffer() {
+        let (gate, started_rx, open_tx, out_rx) = gate();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut dispatch = SinkDispatch::new(gate, Backpressure::Buffer(1), Arc::clone(&dropped));
+        dispatch.snapshot(&1);
+        started_rx.recv().unwrap();
+        dispatch.snapshot(&2);
+        // The queue is full, the next reading waits for the sink
+        let opener = thread::spawn(move || {
+            for _ in 0..3 {
+                open_tx.send(()).unwrap();
+            }
+        });
+        dispatch.snapshot(&3);
+        opener.join().unwrap();
+        dispatch.flush();
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        let delivered: Vec<_> = out_rx.try_iter().collect();
+        assert_eq!(delivered, [1, 2, 3]);
+    }
+    #[test]
+    fn test_buffer_dead_sink() {
+        struct Panics;
+        impl ReadingSink<u32> for Panics {
+            fn on_snapshot(&mut self, _value: &u32) {
+                panic!("sink failed");
+            }
+        }
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut dispatch = SinkDispatch::new(Panics, Backpressure::Buffer(1), Arc::clone(&dropped));
+        // Must not hang once the sink thread is gone
+        for value in 1..=4 {
+            dispatch.snapshot(&value);
+        }
+        dispatch.flush();
+        assert!(dropped.load(Ordering::Relaxed) >= 2);
+    }
+}
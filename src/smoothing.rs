@@ -0,0 +1,153 @@
+/* EMA and median filtering of noisy registers for polled readings */
+
+use crate::{
+    CResult, Real, api::CryomechApiSmdp, core::register::Register, poller::Steady,
+    provenance::Provenance, status::CompressorStatus,
+};
+use serialport::SerialPort;
+use std::collections::{HashMap, VecDeque};
+
+type SmdpApi = CryomechApiSmdp<Box<dyn SerialPort>>;
+
+/// Readings that expose individual register values, as needed for smoothing.
+pub trait RegisterValues {
+    fn value(&self, register: Register) -> Option<Real>;
+}
+impl RegisterValues for CompressorStatus {
+    fn value(&self, register: Register) -> Option<Real> {
+        CompressorStatus::value(self, register)
+    }
+}
+/// Output of `read_all_with_provenance()`.
+impl<T: RegisterValues> RegisterValues for (T, Provenance) {
+    fn value(&self, register: Register) -> Option<Real> {
+        self.0.value(register)
+    }
+}
+/// Output of `PollPlan::poll()`.
+impl RegisterValues for HashMap<Register, Real> {
+    fn value(&self, register: Register) -> Option<Real> {
+        self.get(&register).copied()
+    }
+}
+
+/// How a register is smoothed across readings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Exponential moving average, `alpha` in (0, 1]. Higher follows the raw
+    /// value more closely.
+    Ema { alpha: Real },
+    /// Median of the last `window` readings, which drops single spikes.
+    Median { window: usize },
+}
+
+#[derive(Debug, Clone)]
+enum FilterState {
+    Ema(Option<Real>),
+    Median(VecDeque<Real>),
+}
+
+/// A raw reading with the smoothed values of the filtered registers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Smoothed<T> {
+    pub raw: T,
+    /// Filtered registers only. A register missing from the raw reading
+    /// keeps its previous smoothed value.
+    pub smoothed: HashMap<Register, Real>,
+}
+/// Compares the raw readings.
+impl<T: Steady> Steady for Smoothed<T> {
+    fn steady(&self, previous: &Self) -> bool {
+        self.raw.steady(&previous.raw)
+    }
+}
+
+/// Per-register filters applied to successive readings, e.g. motor current
+/// and the high side pressure derivative.
+#[derive(Debug, Clone, Default)]
+pub struct Smoothing {
+    filters: HashMap<Register, (Filter, FilterState)>,
+    smoothed: HashMap<Register, Real>,
+}
+impl Smoothing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn filter(mut self, register: Register, filter: Filter) -> Self {
+        let state = match filter {
+            Filter::Ema { .. } => FilterState::Ema(None),
+            Filter::Median { window } => FilterState::Median(VecDeque::with_capacity(window)),
+        };
+        self.filters.insert(register, (filter, state));
+        self
+    }
+    /// Feeds a new reading through the filters.
+    pub fn apply<T: RegisterValues>(&mut self, raw: T) -> Smoothed<T> {
+        for (register, (filter, state)) in &mut self.filters {
+            let Some(value) = raw.value(*register) else {
+                continue;
+            };
+            let smoothed = match (filter, state) {
+                (Filter::Ema { alpha }, FilterState::Ema(prev)) => {
+                    let alpha = alpha.clamp(0.0, 1.0);
+                    let next = prev.map_or(value, |prev| alpha * value + (1.0 - alpha) * prev);
+                    *prev = Some(next);
+                    next
+                }
+                (Filter::Median { window }, FilterState::Median(recent)) => {
+                    if recent.len() >= (*window).max(1) {
+                        recent.pop_front();
+                    }
+                    recent.push_back(value);
+                    let mut sorted: Vec<_> = recent.iter().copied().collect();
+                    sorted.sort_by(|a, b| a.total_cmp(b));
+                    sorted.get(sorted.len() / 2).copied().unwrap_or(value)
+                }
+                _ => value,
+            };
+            self.smoothed.insert(*register, smoothed);
+        }
+        Smoothed {
+            raw,
+            smoothed: self.smoothed.clone(),
+        }
+    }
+    /// Wraps a poller read closure so every reading is smoothed, e.g.
+    /// `builder.spawn(api, smoothing.wrap(|api| api.read_all()))`.
+    pub fn wrap<T, F>(mut self, mut read: F) -> impl FnMut(&mut SmdpApi) -> CResult<Smoothed<T>>
+    where
+        T: RegisterValues,
+        F: FnMut(&mut SmdpApi) -> CResult<T>,
+    {
+        move |api| Ok(self.apply(read(api)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let mut smoothing = Smoothing::new()
+            .filter(Register::MotorCurrent, Filter::Ema { alpha: 0.5 })
+            .filter(
+                Register::HighSidePressureDeriv,
+                Filter::Median { window: 3 },
+            );
+        let mut last = None;
+        for (current, deriv) in [(10.0, 0.1), (12.0, 5.0), (12.0, 0.2)] {
+            let raw = HashMap::from([
+                (Register::MotorCurrent, current),
+                (Register::HighSidePressureDeriv, deriv),
+            ]);
+            last = Some(smoothing.apply(raw));
+        }
+        let last = last.unwrap();
+        assert_eq!(last.raw[&Register::MotorCurrent], 12.0);
+        assert_eq!(last.smoothed[&Register::MotorCurrent], 11.5);
+        // The spike is dropped by the median
+        assert_eq!(last.smoothed[&Register::HighSidePressureDeriv], 0.2);
+        assert!(!last.smoothed.contains_key(&Register::HeliumTemp));
+    }
+}
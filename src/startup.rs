@@ -0,0 +1,101 @@
+/* Start verification with automatic rollback for start_transaction() */
+
+use crate::{Real, api::ControlOutcome};
+use std::{fmt, ops::RangeInclusive, time::Duration};
+
+/// Run ranges `start_transaction()` waits for after the start command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartCheck {
+    /// Longest time to wait for the run ranges to be reached.
+    pub timeout: Duration,
+    /// Delay between readings.
+    pub interval: Duration,
+    /// Smallest high/low side difference, in PSI, that counts as running.
+    pub min_delta_pressure: Real,
+    /// Motor current range, in Amps, that counts as running.
+    pub motor_current: RangeInclusive<u32>,
+}
+impl Default for StartCheck {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            interval: Duration::from_secs(1),
+            min_delta_pressure: 100.0,
+            motor_current: 1..=40,
+        }
+    }
+}
+impl StartCheck {
+    pub(crate) fn failures(
+        &self,
+        running: bool,
+        delta_pressure: Real,
+        motor_current: u32,
+    ) -> Vec<StartFailure> {
+        let mut failures = Vec::new();
+        if !running {
+            failures.push(StartFailure::NotRunning);
+        }
+        if delta_pressure < self.min_delta_pressure {
+            failures.push(StartFailure::DeltaPressure(delta_pressure));
+        }
+        if !self.motor_current.contains(&motor_current) {
+            failures.push(StartFailure::MotorCurrent(motor_current));
+        }
+        failures
+    }
+}
+
+/// Run criterion not met when the start check timed out, with the last reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartFailure {
+    NotRunning,
+    DeltaPressure(Real),
+    MotorCurrent(u32),
+}
+impl fmt::Display for StartFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotRunning => f.write_str("compressor not running"),
+            Self::DeltaPressure(p) => write!(f, "delta pressure {p} PSI below run range"),
+            Self::MotorCurrent(a) => write!(f, "motor current {a} A outside run range"),
+        }
+    }
+}
+
+/// Result of `start_transaction()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartReport {
+    /// Outcome of the start command itself.
+    pub outcome: ControlOutcome,
+    /// Criteria still unmet at the timeout. Empty if the start succeeded.
+    pub failures: Vec<StartFailure>,
+    /// True if a stop was sent because the start could not be verified.
+    pub rolled_back: bool,
+    /// Time from the start command to the last reading.
+    pub elapsed: Duration,
+}
+impl StartReport {
+    pub fn succeeded(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_start_failures() {
+        let check = StartCheck::default();
+        assert!(check.failures(true, 180.0, 12).is_empty());
+        assert_eq!(
+            check.failures(false, 2.0, 0),
+            [
+                StartFailure::NotRunning,
+                StartFailure::DeltaPressure(2.0),
+                StartFailure::MotorCurrent(0)
+            ]
+        );
+    }
+}
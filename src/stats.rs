@@ -0,0 +1,219 @@
+/* Per-register response time statistics */
+
+use std::{collections::BTreeMap, time::Duration};
+
+// Upper bounds of the histogram buckets, the last bucket takes everything above
+const BUCKET_BOUNDS_MS: [u64; 9] = [1, 2, 5, 10, 20, 50, 100, 200, 500];
+
+/// Response time histogram with fixed buckets from 1 ms to 500 ms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; BUCKET_BOUNDS_MS.len() + 1],
+    total: Duration,
+    max: Duration,
+}
+impl LatencyHistogram {
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let idx = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|ms| latency < Duration::from_millis(*ms))
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        if let Some(count) = self.counts.get_mut(idx) {
+            *count += 1;
+        }
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count()).ok().filter(|c| *c > 0)?;
+        Some(self.total / count)
+    }
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+    /// `(upper bound, count)` per bucket. The last bucket has no upper bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        BUCKET_BOUNDS_MS
+            .iter()
+            .map(|ms| Some(Duration::from_millis(*ms)))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+    /// Upper bound of the bucket holding the `q` quantile (0.0 to 1.0), e.g.
+    /// 0.99 for a p99 estimate. Falls back to the maximum seen for the last bucket.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let target = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bound, n) in self.buckets() {
+            seen += n;
+            if seen >= target {
+                return Some(bound.unwrap_or(self.max));
+            }
+        }
+        Some(self.max)
+    }
+    fn merge(&mut self, other: &Self) {
+        for (count, n) in self.counts.iter_mut().zip(other.counts) {
+            *count += n;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Response time histograms per dictionary entry, from request sent to reply
+/// received. Only successful transactions are counted in the histograms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    registers: BTreeMap<u16, LatencyHistogram>,
+    corrupt_frames: u64,
+}
+impl LinkStats {
+    pub(crate) fn record_corrupt(&mut self) {
+        self.corrupt_frames += 1;
+    }
+    /// Replies rejected by the smdp layer (e.g. bad checksum), including the
+    /// ones that were retried successfully.
+    pub fn corrupt_frames(&self) -> u64 {
+        self.corrupt_frames
+    }
+    pub(crate) fn record(&mut self, hashval: u16, latency: Duration) {
+        self.registers.entry(hashval).or_default().record(latency);
+    }
+    /// Histogram of one dictionary entry, by hash value.
+    pub fn register(&self, hashval: u16) -> Option<&LatencyHistogram> {
+        self.registers.get(&hashval)
+    }
+    /// All dictionary entries combined into one histogram.
+    pub fn overall(&self) -> LatencyHistogram {
+        let mut all = LatencyHistogram::default();
+        for hist in self.registers.values() {
+            all.merge(hist);
+        }
+        all
+    }
+    /// All dictionary entries seen so far, by hash value.
+    pub fn registers(&self) -> impl Iterator<Item = (u16, &LatencyHistogram)> {
+        self.registers.iter().map(|(hash, hist)| (*hash, hist))
+    }
+}
+
+/// Bounds for a read timeout tuned from observed response times. After every
+/// `window` successful transactions the timeout is set to `margin` times the
+/// p99 latency of that window, clamped to `min..=max`. A failed transaction
+/// other than a NAK resets it to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoTimeout {
+    pub min: Duration,
+    pub max: Duration,
+    pub margin: u32,
+    pub window: u32,
+}
+impl Default for AutoTimeout {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_millis(20),
+            max: Duration::from_secs(1),
+            margin: 3,
+            window: 32,
+        }
+    }
+}
+impl AutoTimeout {
+    fn clamp(&self, timeout: Duration) -> Duration {
+        timeout.clamp(self.min, self.max.max(self.min))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TimeoutTuner {
+    bounds: AutoTimeout,
+    window: LatencyHistogram,
+    current: Duration,
+}
+impl TimeoutTuner {
+    /// Starts from `initial`, clamped to the bounds.
+    pub(crate) fn new(bounds: AutoTimeout, initial: Duration) -> Self {
+        Self {
+            bounds,
+            window: LatencyHistogram::default(),
+            current: bounds.clamp(initial),
+        }
+    }
+    pub(crate) fn current(&self) -> Duration {
+        self.current
+    }
+    /// Records a successful transaction. Returns the new timeout if it changed.
+    pub(crate) fn observe(&mut self, latency: Duration) -> Option<Duration> {
+        self.window.record(latency);
+        if self.window.count() < u64::from(self.bounds.window.max(1)) {
+            return None;
+        }
+        let p99 = self.window.quantile(0.99)?;
+        self.window = LatencyHistogram::default();
+        self.set(self.bounds.clamp(p99 * self.bounds.margin))
+    }
+    /// Records a failed transaction, falling back to the upper bound.
+    pub(crate) fn failed(&mut self) -> Option<Duration> {
+        self.window = LatencyHistogram::default();
+        self.set(self.bounds.clamp(self.bounds.max))
+    }
+    fn set(&mut self, timeout: Duration) -> Option<Duration> {
+        (timeout != self.current).then(|| {
+            self.current = timeout;
+            timeout
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut hist = LatencyHistogram::default();
+        assert_eq!(hist.mean(), None);
+        for ms in [3, 4, 4, 8, 700] {
+            hist.record(Duration::from_millis(ms));
+        }
+        assert_eq!(hist.count(), 5);
+        assert_eq!(hist.max(), Duration::from_millis(700));
+        assert_eq!(hist.mean(), Some(Duration::from_micros(143_800)));
+        assert_eq!(hist.quantile(0.5), Some(Duration::from_millis(5)));
+        assert_eq!(hist.quantile(0.8), Some(Duration::from_millis(10)));
+        assert_eq!(hist.quantile(1.0), Some(Duration::from_millis(700)));
+        let buckets: Vec<_> = hist.buckets().collect();
+        assert_eq!(buckets[2], (Some(Duration::from_millis(5)), 3));
+        assert_eq!(buckets[9], (None, 1));
+    }
+    #[test]
+    fn test_timeout_tuner() {
+        let bounds = AutoTimeout {
+            window: 4,
+            ..AutoTimeout::default()
+        };
+        let mut tuner = TimeoutTuner::new(bounds, Duration::from_millis(80));
+        for _ in 0..3 {
+            assert_eq!(tuner.observe(Duration::from_millis(7)), None);
+        }
+        // p99 lands in the 10 ms bucket
+        assert_eq!(
+            tuner.observe(Duration::from_millis(7)),
+            Some(Duration::from_millis(30))
+        );
+        assert_eq!(tuner.failed(), Some(Duration::from_secs(1)));
+        assert_eq!(tuner.failed(), None);
+        for _ in 0..4 {
+            tuner.observe(Duration::from_micros(100));
+        }
+        assert_eq!(tuner.current(), Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,235 @@
+/* Snapshot of all readable values of a Cryomech compressor */
+
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+use crate::{CResult, Error};
+use crate::{Real, core::register::Register};
+
+/// Allowed differences for `CompressorStatus::approx_eq()`. The defaults are
+/// half of the 0.1 resolution of the scaled readings, which absorbs float
+/// rounding but still catches a one-step change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerances {
+    /// In °C, applied to all temperatures.
+    pub temp: Real,
+    /// In PSI, applied to all pressures.
+    pub pressure: Real,
+}
+impl Default for Tolerances {
+    fn default() -> Self {
+        Self {
+            temp: 0.05,
+            pressure: 0.05,
+        }
+    }
+}
+
+/// All read-only values of a compressor, read in one pass by `read_all()`.
+/// Units match the individual getters: °C, PSI absolute, Amps.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    any(feature = "cbor", feature = "msgpack"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CompressorStatus {
+    pub comp_on: bool,
+    pub err_code_status: bool,
+    pub mem_loss: bool,
+    pub clock_batt_ok: bool,
+    pub clock_batt_low: bool,
+    pub temp_sensor_fail: bool,
+    pub pressure_sensor_fail: bool,
+    pub comp_minutes: u32,
+    pub motor_current_amps: u32,
+    pub cpu_temp: Real,
+    pub input_water_temp: Real,
+    pub output_water_temp: Real,
+    pub helium_temp: Real,
+    pub oil_temp: Real,
+    pub min_input_water_temp: Real,
+    pub min_output_water_temp: Real,
+    pub min_helium_temp: Real,
+    pub min_oil_temp: Real,
+    pub max_input_water_temp: Real,
+    pub max_output_water_temp: Real,
+    pub max_helium_temp: Real,
+    pub max_oil_temp: Real,
+    pub high_side_pressure: Real,
+    pub low_side_pressure: Real,
+    pub max_high_side_pressure: Real,
+    pub max_low_side_pressure: Real,
+    pub min_high_side_pressure: Real,
+    pub min_low_side_pressure: Real,
+    pub avg_high_side_pressure: Real,
+    pub avg_low_side_pressure: Real,
+    pub high_side_pressure_deriv: Real,
+    pub avg_delta_pressure: Real,
+}
+impl CompressorStatus {
+    /// Value of a register in this snapshot, scaled as by `read()`. None for
+    /// registers a snapshot doesn't hold.
+    pub fn value(&self, register: Register) -> Option<Real> {
+        let flag = |b: bool| if b { 1.0 } else { 0.0 };
+        Some(match register {
+            Register::CompOn => flag(self.comp_on),
+            Register::ErrCodeStatus => flag(self.err_code_status),
+            Register::InputWaterTemp => self.input_water_temp,
+            Register::OutputWaterTemp => self.output_water_temp,
+            Register::HeliumTemp => self.helium_temp,
+            Register::OilTemp => self.oil_temp,
+            Register::HighSidePressure => self.high_side_pressure,
+            Register::LowSidePressure => self.low_side_pressure,
+            Register::AvgHighSidePressure => self.avg_high_side_pressure,
+            Register::AvgLowSidePressure => self.avg_low_side_pressure,
+            Register::AvgDeltaPressure => self.avg_delta_pressure,
+            Register::HighSidePressureDeriv => self.high_side_pressure_deriv,
+            Register::MotorCurrent => self.motor_current_amps as Real,
+            Register::RunHours => self.comp_minutes as Real / 60.0,
+            Register::CpuTemp => self.cpu_temp,
+            Register::OperatingState => return None,
+        })
+    }
+    /// True if flags and counters are equal and every temperature and pressure
+    /// is within its tolerance of `other`.
+    pub fn approx_eq(&self, other: &Self, tolerances: Tolerances) -> bool {
+        let temps = |s: &Self| {
+            [
+                s.cpu_temp,
+                s.input_water_temp,
+                s.output_water_temp,
+                s.helium_temp,
+                s.oil_temp,
+                s.min_input_water_temp,
+                s.min_output_water_temp,
+                s.min_helium_temp,
+                s.min_oil_temp,
+                s.max_input_water_temp,
+                s.max_output_water_temp,
+                s.max_helium_temp,
+                s.max_oil_temp,
+            ]
+        };
+        let pressures = |s: &Self| {
+            [
+                s.high_side_pressure,
+                s.low_side_pressure,
+                s.max_high_side_pressure,
+                s.max_low_side_pressure,
+                s.min_high_side_pressure,
+                s.min_low_side_pressure,
+                s.avg_high_side_pressure,
+                s.avg_low_side_pressure,
+                s.high_side_pressure_deriv,
+                s.avg_delta_pressure,
+            ]
+        };
+        let within =
+            |a: &[Real], b: &[Real], tol: Real| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tol);
+        let discrete = |s: &Self| {
+            (
+                s.comp_on,
+                s.err_code_status,
+                s.mem_loss,
+                s.clock_batt_ok,
+                s.clock_batt_low,
+                s.temp_sensor_fail,
+                s.pressure_sensor_fail,
+                s.comp_minutes,
+                s.motor_current_amps,
+            )
+        };
+        discrete(self) == discrete(other)
+            && within(&temps(self), &temps(other), tolerances.temp)
+            && within(&pressures(self), &pressures(other), tolerances.pressure)
+    }
+    /// CBOR encoding, a map keyed by field name.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> CResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(self, &mut buf).map_err(|e| Error::Encoding(e.to_string()))?;
+        Ok(buf)
+    }
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(bytes: &[u8]) -> CResult<Self> {
+        ciborium::from_reader(bytes).map_err(|e| Error::Encoding(e.to_string()))
+    }
+    /// MessagePack encoding as a positional array, without field names. The
+    /// smallest option, but both ends must agree on the field order.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> CResult<Vec<u8>> {
+        rmp_serde::to_vec(self).map_err(|e| Error::Encoding(e.to_string()))
+    }
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> CResult<Self> {
+        rmp_serde::from_slice(bytes).map_err(|e| Error::Encoding(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn status() -> CompressorStatus {
+        CompressorStatus {
+            comp_on: true,
+            err_code_status: false,
+            mem_loss: false,
+            clock_batt_ok: true,
+            clock_batt_low: false,
+            temp_sensor_fail: false,
+            pressure_sensor_fail: false,
+            comp_minutes: 1000,
+            motor_current_amps: 12,
+            cpu_temp: 40.0,
+            input_water_temp: 20.0,
+            output_water_temp: 30.0,
+            helium_temp: 60.0,
+            oil_temp: 45.0,
+            min_input_water_temp: 19.0,
+            min_output_water_temp: 29.0,
+            min_helium_temp: 59.0,
+            min_oil_temp: 44.0,
+            max_input_water_temp: 21.0,
+            max_output_water_temp: 31.0,
+            max_helium_temp: 61.0,
+            max_oil_temp: 46.0,
+            high_side_pressure: 290.0,
+            low_side_pressure: 90.0,
+            max_high_side_pressure: 300.0,
+            max_low_side_pressure: 100.0,
+            min_high_side_pressure: 280.0,
+            min_low_side_pressure: 80.0,
+            avg_high_side_pressure: 290.0,
+            avg_low_side_pressure: 90.0,
+            high_side_pressure_deriv: 0.5,
+            avg_delta_pressure: 200.0,
+        }
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let a = status();
+        let mut b = status();
+        // Rounding jitter from the 0.1 scaling
+        b.oil_temp += 0.000_01;
+        b.high_side_pressure -= 0.000_1;
+        assert!(a.approx_eq(&b, Tolerances::default()));
+        b.oil_temp += 0.1;
+        assert!(!a.approx_eq(&b, Tolerances::default()));
+        let mut c = status();
+        c.comp_on = false;
+        assert!(!a.approx_eq(&c, Tolerances::default()));
+    }
+    #[cfg(all(feature = "cbor", feature = "msgpack"))]
+    #[test]
+    fn test_binary_round_trip() {
+        let a = status();
+        assert_eq!(
+            CompressorStatus::from_cbor(&a.to_cbor().unwrap()).unwrap(),
+            a
+        );
+        let packed = a.to_msgpack().unwrap();
+        assert!(packed.len() < a.to_cbor().unwrap().len());
+        assert_eq!(CompressorStatus::from_msgpack(&packed).unwrap(), a);
+        assert!(CompressorStatus::from_msgpack(&packed[..10]).is_err());
+    }
+}
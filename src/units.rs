@@ -0,0 +1,83 @@
+/* Pressure newtypes with absolute/gauge and unit conversions */
+
+use crate::Real;
+
+/// Bar per PSI
+pub const PSI_TO_BAR: Real = 0.068_947_57;
+/// Kilopascal per PSI
+pub const PSI_TO_KPA: Real = 6.894_757;
+
+/// Pressure in PSI absolute, as reported by the pressure getters.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Psia(pub Real);
+
+/// Pressure in PSI gauge, relative to the surrounding atmosphere.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Psig(pub Real);
+
+impl Psia {
+    /// Standard atmosphere, for when the local barometric pressure is unknown.
+    pub const STANDARD_ATMOSPHERE: Psia = Psia(14.695_95);
+
+    /// Gauge pressure given the local atmospheric pressure.
+    pub fn to_gauge(self, atmosphere: Psia) -> Psig {
+        Psig(self.0 - atmosphere.0)
+    }
+    pub fn from_bar(bar: Real) -> Self {
+        Self(bar / PSI_TO_BAR)
+    }
+    pub fn from_kpa(kpa: Real) -> Self {
+        Self(kpa / PSI_TO_KPA)
+    }
+    /// Absolute pressure in bar.
+    pub fn bar(self) -> Real {
+        self.0 * PSI_TO_BAR
+    }
+    /// Absolute pressure in kPa.
+    pub fn kpa(self) -> Real {
+        self.0 * PSI_TO_KPA
+    }
+}
+
+impl Psig {
+    /// Absolute pressure given the local atmospheric pressure.
+    pub fn to_absolute(self, atmosphere: Psia) -> Psia {
+        Psia(self.0 + atmosphere.0)
+    }
+    pub fn from_bar(bar: Real) -> Self {
+        Self(bar / PSI_TO_BAR)
+    }
+    pub fn from_kpa(kpa: Real) -> Self {
+        Self(kpa / PSI_TO_KPA)
+    }
+    /// Gauge pressure in bar.
+    pub fn bar(self) -> Real {
+        self.0 * PSI_TO_BAR
+    }
+    /// Gauge pressure in kPa.
+    pub fn kpa(self) -> Real {
+        self.0 * PSI_TO_KPA
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: Real, b: Real) -> bool {
+        (a - b).abs() < 1e-2
+    }
+
+    #[test]
+    fn test_pressure_conversions() {
+        let atm = Psia::STANDARD_ATMOSPHERE;
+        assert!(close(atm.kpa(), 101.325));
+        assert!(close(atm.bar(), 1.01325));
+        let static_charge = Psig(235.0);
+        let abs = static_charge.to_absolute(atm);
+        assert!(close(abs.0, 249.696));
+        assert!(close(abs.to_gauge(atm).0, 235.0));
+        assert!(close(Psig::from_bar(16.2).0, 234.961));
+        assert!(close(Psia::from_kpa(abs.kpa()).0, abs.0));
+    }
+}
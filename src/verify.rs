@@ -0,0 +1,98 @@
+/* Read-back checks applied by write_register_verified() */
+
+use crate::dictionary::{COMP_ON_HASH, START_HASH, STOP_HASH};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+type Predicate = Arc<dyn Fn(u32, u32) -> bool + Send + Sync>;
+
+/// How a write to one dictionary entry is confirmed: which entry to read
+/// back, and a predicate over the written and the read value.
+#[derive(Clone)]
+pub struct WriteVerifier {
+    hashval: u16,
+    array_idx: Option<u8>,
+    predicate: Predicate,
+}
+impl WriteVerifier {
+    /// Reads `hashval` at the index that was written and passes
+    /// `(written, read)` to `predicate`.
+    pub fn new(hashval: u16, predicate: impl Fn(u32, u32) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            hashval,
+            array_idx: None,
+            predicate: Arc::new(predicate),
+        }
+    }
+    /// Reads the written entry back and expects the written value.
+    pub fn read_back(hashval: u16) -> Self {
+        Self::new(hashval, |written, read| written == read)
+    }
+    /// Reads a fixed index instead of the one that was written, e.g. for
+    /// scalar status flags.
+    pub fn at_index(mut self, array_idx: u8) -> Self {
+        self.array_idx = Some(array_idx);
+        self
+    }
+    pub(crate) fn read_target(&self, written_idx: u8) -> (u16, u8) {
+        (self.hashval, self.array_idx.unwrap_or(written_idx))
+    }
+    pub(crate) fn accepts(&self, written: u32, read: u32) -> bool {
+        (self.predicate)(written, read)
+    }
+}
+impl fmt::Debug for WriteVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteVerifier")
+            .field("hashval", &self.hashval)
+            .field("array_idx", &self.array_idx)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Verifiers keyed by the hash of the entry being written.
+#[derive(Debug, Clone)]
+pub(crate) struct WriteVerifiers(HashMap<u16, WriteVerifier>);
+impl Default for WriteVerifiers {
+    /// Start and stop are confirmed through the run state flag, as
+    /// `start_compressor()` and `stop_compressor()` do.
+    fn default() -> Self {
+        Self(HashMap::from([
+            (
+                START_HASH,
+                WriteVerifier::new(COMP_ON_HASH, |_, on| on == 1).at_index(0),
+            ),
+            (
+                STOP_HASH,
+                WriteVerifier::new(COMP_ON_HASH, |_, on| on == 0).at_index(0),
+            ),
+        ]))
+    }
+}
+impl WriteVerifiers {
+    pub(crate) fn insert(&mut self, hashval: u16, verifier: WriteVerifier) {
+        self.0.insert(hashval, verifier);
+    }
+    pub(crate) fn get(&self, hashval: u16) -> Option<&WriteVerifier> {
+        self.0.get(&hashval)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verifier_targets() {
+        let mut verifiers = WriteVerifiers::default();
+        let stop = verifiers.get(STOP_HASH).unwrap();
+        assert_eq!(stop.read_target(3), (COMP_ON_HASH, 0));
+        assert!(stop.accepts(0, 0));
+        assert!(!stop.accepts(0, 1));
+        verifiers.insert(0x1234, WriteVerifier::read_back(0x1234));
+        let setting = verifiers.get(0x1234).unwrap();
+        assert_eq!(setting.read_target(2), (0x1234, 2));
+        assert!(setting.accepts(70, 70));
+        assert!(!setting.accepts(70, 69));
+        assert!(verifiers.get(0x4321).is_none());
+    }
+}
@@ -0,0 +1,102 @@
+/* Over-temperature watchdog that stops the compressor on sustained overheating */
+
+use crate::{Real, api::ControlOutcome, labels::DeviceLabels};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Stops the compressor when the oil or helium temperature stays above its
+/// limit for `hold`, e.g. after a cooling water failure. Runs on the poller.
+/// The default limits are examples; set them from the model's manual.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempWatchdog {
+    /// Hard oil temperature limit, °C.
+    pub oil_limit: Real,
+    /// Hard helium temperature limit, °C.
+    pub helium_limit: Real,
+    /// How long a limit must be exceeded before the compressor is stopped.
+    pub hold: Duration,
+}
+impl Default for TempWatchdog {
+    fn default() -> Self {
+        Self {
+            oil_limit: 52.0,
+            helium_limit: 88.0,
+            hold: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Record of a watchdog stop.
+#[derive(Debug, Clone)]
+pub struct WatchdogEvent {
+    pub at: SystemTime,
+    pub oil_temp: Real,
+    pub helium_temp: Real,
+    /// Limits in effect when the watchdog tripped.
+    pub limits: TempWatchdog,
+    /// How long the temperature had been over the limit.
+    pub over_for: Duration,
+    /// Result of the stop write, `Unchecked` once the panel acknowledged it.
+    /// Rendered error on failure.
+    pub stop: Result<ControlOutcome, String>,
+    /// Labels of the stopped unit.
+    pub labels: Arc<DeviceLabels>,
+}
+
+/// Over-limit tracking between poller ticks.
+#[derive(Debug, Default)]
+pub(crate) struct WatchdogState {
+    over_since: Option<Instant>,
+}
+impl WatchdogState {
+    /// Feeds one reading. Returns how long the limit has been exceeded once
+    /// that reaches the hold time, then starts counting again.
+    pub(crate) fn observe(
+        &mut self,
+        limits: &TempWatchdog,
+        oil_temp: Real,
+        helium_temp: Real,
+        now: Instant,
+    ) -> Option<Duration> {
+        if oil_temp <= limits.oil_limit && helium_temp <= limits.helium_limit {
+            self.over_since = None;
+            return None;
+        }
+        let since = *self.over_since.get_or_insert(now);
+        let over_for = now.saturating_duration_since(since);
+        if over_for >= limits.hold {
+            self.over_since = None;
+            Some(over_for)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_hold() {
+        let limits = TempWatchdog {
+            hold: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let mut state = WatchdogState::default();
+        let t0 = Instant::now();
+        let secs = |s| t0 + Duration::from_secs(s);
+        assert_eq!(state.observe(&limits, 60.0, 40.0, t0), None);
+        // Dipping below the limit resets the hold timer
+        assert_eq!(state.observe(&limits, 40.0, 40.0, secs(5)), None);
+        assert_eq!(state.observe(&limits, 40.0, 95.0, secs(6)), None);
+        assert_eq!(state.observe(&limits, 40.0, 95.0, secs(15)), None);
+        assert_eq!(
+            state.observe(&limits, 40.0, 95.0, secs(16)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(state.observe(&limits, 40.0, 95.0, secs(17)), None);
+    }
+}